@@ -1,86 +1,75 @@
 //! # Examples
 //!
-//! This file contains examples on how to write simple to complex rules for ReBackup's [walker](rebackup::walk).
+//! This file contains examples on how to use ReBackup's [built-in rules](rebackup::rules) and how to write your own
+//! rules for the [walker](rebackup::walk).
 
-use rebackup::{WalkerItemType, WalkerRule, WalkerRuleResult};
-use std::env;
-use std::ffi::OsString;
+use rebackup::{rules, RuleSet, WalkerItemType, WalkerItemTypes, WalkerRule, WalkerRuleResult};
+use std::io;
+use std::path::PathBuf;
 use std::process::Command;
 
-/// Exclude the 'target' directory in Cargo projects
-pub fn rust_cargo_build() -> WalkerRule {
+/// Exclude the 'cache' directories (a simple rule written by hand)
+pub fn cache_dirs() -> WalkerRule {
     WalkerRule {
-        name: "rust_cargo_build",
+        name: "cache_dirs",
         description: None,
-        only_for: Some(WalkerItemType::Directory),
-        matches: Box::new(|path, _, _| path.file_name() == Some(OsString::from("target").as_os_str()) && path.join("..").join("Cargo.toml").is_file()),
-        action: Box::new(|_, _, _| Ok(WalkerRuleResult::ExcludeItem)),
+        priority: 0,
+        only_for: WalkerItemTypes::only(WalkerItemType::Directory),
+        min_depth: None,
+        max_depth: None,
+        cacheable: false,
+        timeout: None,
+        matches: Box::new(|ctx| Ok(ctx.path.file_name().is_some_and(|name| name == "cache"))),
+        action: Box::new(|_| Ok(WalkerRuleResult::ExcludeItem)),
     }
 }
 
-/// Exclude directories containing a '.nomedia' file
-pub fn nomedia() -> WalkerRule {
+/// Only include the files tracked by Git in repositories (a rule mapping items using an external tool)
+///
+/// As `git ls-files` lists paths relative to the repository, they are made relative to the source directory
+/// to be used with [`WalkerRuleResult::MapAsListFromSource`].
+pub fn git_tracked_files() -> WalkerRule {
     WalkerRule {
-        name: "nomedia",
-        description: None,
-        only_for: Some(WalkerItemType::Directory),
-        matches: Box::new(|path, _, _| path.join(".nomedia").is_file()),
-        action: Box::new(|_, _, _| Ok(WalkerRuleResult::ExcludeItem)),
-    }
-}
+        name: "git_tracked_files",
+        description: Some("Only include files tracked by Git".to_string()),
+        priority: 0,
+        only_for: WalkerItemTypes::only(WalkerItemType::Directory),
+        min_depth: None,
+        max_depth: None,
+        cacheable: false,
+        timeout: None,
+        matches: Box::new(|ctx| Ok(ctx.path.join(".git").is_dir())),
+        action: Box::new(|ctx| {
+            let output = Command::new("git").arg("ls-files").arg("-z").current_dir(ctx.path).output()?;
 
-/// Exclude the '.git' directories
-pub fn dotgit() -> WalkerRule {
-    WalkerRule {
-        name: "dotgit",
-        description: None,
-        only_for: Some(WalkerItemType::Directory),
-        matches: Box::new(|path, _, _| path.file_name() == Some(OsString::from(".git").as_os_str())),
-        action: Box::new(|_, _, _| Ok(WalkerRuleResult::ExcludeItem)),
-    }
-}
-
-/// Exclude the 'node_modules' directory
-pub fn node_modules() -> WalkerRule {
-    WalkerRule {
-        name: "node_modules",
-        description: None,
-        only_for: Some(WalkerItemType::Directory),
-        matches: Box::new(|path, _, _| path.file_name() == Some(OsString::from("node_modules").as_os_str())),
-        action: Box::new(|_, _, _| Ok(WalkerRuleResult::ExcludeItem)),
-    }
-}
-
-/// Exclude files based on the '.gitignore' file in Git repositories
-pub fn gitignore() -> WalkerRule {
-    WalkerRule {
-        name: "gitignore",
-        description: None,
-        only_for: None,
-        matches: Box::new(|path, _, _| path.ancestors().any(|path| path.join(".git").is_dir())),
-        action: Box::new(|dir, _, _| {
-            let cwd = env::current_dir()?;
-
-            if dir.is_dir() {
-                env::set_current_dir(dir)?;
-            } else if let Some(parent) = dir.parent() {
-                env::set_current_dir(parent)?;
+            if !output.status.success() {
+                return Err(io::Error::other(String::from_utf8_lossy(&output.stderr).into_owned()));
             }
 
-            let is_excluded = Command::new("git").arg("check-ignore").arg(dir.to_string_lossy().to_string()).output();
+            let repo = ctx.path.strip_prefix(ctx.source).unwrap_or(ctx.path);
 
-            // Restore the current directory before returning eventual error from the command
-            env::set_current_dir(cwd)?;
+            let files = String::from_utf8_lossy(&output.stdout)
+                .split('\0')
+                .filter(|file| !file.is_empty())
+                .map(|file| repo.join(file))
+                .collect::<Vec<PathBuf>>();
 
-            if is_excluded?.status.success() {
-                Ok(WalkerRuleResult::ExcludeItem)
-            } else {
-                Ok(WalkerRuleResult::IncludeItem)
-            }
+            Ok(WalkerRuleResult::MapAsListFromSource(files, true))
         }),
     }
 }
 
+/// Extend the built-in groups of rules, allowing to toggle them by group (e.g. `set.disable("vcs")`)
+pub fn dev_rule_set() -> RuleSet {
+    let mut set = RuleSet::dev_defaults();
+
+    set.add("vcs", rules::gitignore());
+    set.add("caches", cache_dirs());
+    set.add("media", rules::nomedia());
+
+    set
+}
+
 fn main() {
     println!("This example is not runnable by itself.");
     println!("Its purpose is to show how to make simple or advanced rules for the walker.");
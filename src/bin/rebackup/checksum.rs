@@ -0,0 +1,114 @@
+//! Checksums of the listed files (see the `--checksum` option)
+
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+
+/// Size of the buffer files are read with
+pub const BUFFER_SIZE: usize = 64 * 1024;
+
+/// Names of the supported checksum algorithms (BLAKE3 requires the `blake3` feature)
+#[cfg(not(feature = "blake3"))]
+pub const ALGORITHMS: &[&str] = &["sha256"];
+
+/// Names of the supported checksum algorithms (BLAKE3 requires the `blake3` feature)
+#[cfg(feature = "blake3")]
+pub const ALGORITHMS: &[&str] = &["sha256", "blake3"];
+
+/// Compute the digest of a file as an hexadecimal string with one of the [supported algorithms](ALGORITHMS),
+/// reading it with the provided buffer
+pub fn file_checksum(algorithm: &str, path: &Path, buffer: &mut [u8]) -> io::Result<String> {
+    let mut file = File::open(path)?;
+
+    match algorithm {
+        "sha256" => {
+            let mut hasher = Sha256::new();
+            read_chunks(&mut file, buffer, |chunk| hasher.update(chunk))?;
+            Ok(to_hex(&hasher.finalize()))
+        }
+
+        #[cfg(feature = "blake3")]
+        "blake3" => {
+            let mut hasher = blake3::Hasher::new();
+            read_chunks(&mut file, buffer, |chunk| {
+                hasher.update(chunk);
+            })?;
+            Ok(hasher.finalize().to_hex().to_string())
+        }
+
+        _ => unreachable!("Internal error: unknown checksum algorithm: {}", algorithm),
+    }
+}
+
+/// (Internal) Read a file entirely with the provided buffer, calling a function with each chunk
+fn read_chunks(file: &mut File, buffer: &mut [u8], mut on_chunk: impl FnMut(&[u8])) -> io::Result<()> {
+    loop {
+        match file.read(buffer) {
+            Ok(0) => return Ok(()),
+            Ok(read) => on_chunk(&buffer[..read]),
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+/// (Internal) Format bytes as a lowercase hexadecimal string
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    /// Compute the checksum of a file with the provided content, using a small buffer to read it in multiple chunks
+    fn checksum_of(algorithm: &str, content: &[u8]) -> String {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all(content).unwrap();
+
+        file_checksum(algorithm, file.path(), &mut [0; 7]).unwrap()
+    }
+
+    #[test]
+    fn sha256_known_answers() {
+        assert_eq!(checksum_of("sha256", b""), "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855");
+        assert_eq!(
+            checksum_of("sha256", b"abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+
+        // Two blocks
+        assert_eq!(
+            checksum_of("sha256", b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq"),
+            "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1"
+        );
+
+        // Many blocks, larger than the read buffer
+        assert_eq!(
+            checksum_of("sha256", &[b'a'; 1_000_000]),
+            "cdc76e5c9914fb9281a1c7e284d73e67f1809a48a497200e046d39ccc7112cd0"
+        );
+    }
+
+    #[cfg(feature = "blake3")]
+    #[test]
+    fn blake3_known_answers() {
+        assert_eq!(checksum_of("blake3", b""), "af1349b9f5f9a1a6a0404dea36dcc9499bcb25c9adc112b7cc9a93cae41f3262");
+        assert_eq!(
+            checksum_of("blake3", b"abc"),
+            "6437b3ac38465133ffb63b75273a8db548c558465d79db03fd359c6cd5bd9d85"
+        );
+
+        // Multiple chunks of 1 KiB, read with a buffer much smaller than them
+        let input: Vec<u8> = (0..4096u32).map(|i| (i % 251) as u8).collect();
+        assert_eq!(checksum_of("blake3", &input), blake3::hash(&input).to_hex().to_string());
+    }
+
+    #[test]
+    fn missing_file() {
+        let err = file_checksum("sha256", Path::new("/nonexistent/rebackup/file"), &mut [0; 16]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+    }
+}
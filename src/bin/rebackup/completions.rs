@@ -0,0 +1,26 @@
+//! Shell completion scripts (see the `completions` subcommand)
+//!
+//! Scripts are generated from the application's options with `clap_generate`, so they are always up-to-date with the
+//! command line.
+
+use clap::App;
+use clap_generate::generators::{Bash, Fish, PowerShell, Zsh};
+
+/// Shells to generate completion scripts for
+pub const SHELLS: &[&str] = &["bash", "zsh", "fish", "powershell"];
+
+/// Generate the completion script of a shell
+pub fn generate(shell: &str, mut app: App) -> String {
+    let name = app.get_name().to_string();
+    let mut out = vec![];
+
+    match shell {
+        "bash" => clap_generate::generate::<Bash, _>(&mut app, name, &mut out),
+        "zsh" => clap_generate::generate::<Zsh, _>(&mut app, name, &mut out),
+        "fish" => clap_generate::generate::<Fish, _>(&mut app, name, &mut out),
+        "powershell" => clap_generate::generate::<PowerShell, _>(&mut app, name, &mut out),
+        _ => unreachable!("Internal error: unknown shell: {}", shell),
+    }
+
+    String::from_utf8(out).expect("Internal error: completion script is not valid UTF-8")
+}
@@ -0,0 +1,111 @@
+//! Compression of the output (see the `--compress` option, requires the `gzip` or `zstd` feature)
+
+use crate::output::Destination;
+use std::ffi::OsStr;
+use std::io;
+
+#[cfg(feature = "gzip")]
+impl<W: Destination> Destination for flate2::write::GzEncoder<W> {
+    fn finish(&mut self) -> io::Result<()> {
+        self.try_finish()?;
+        self.get_mut().finish()
+    }
+}
+
+#[cfg(feature = "zstd")]
+impl<W: Destination> Destination for zstd::Encoder<'static, W> {
+    fn finish(&mut self) -> io::Result<()> {
+        self.do_finish()?;
+        self.get_mut().finish()
+    }
+}
+
+/// Wrap a destination to compress the data written to it with one of the [supported formats](crate::COMPRESSION_FORMATS)
+pub fn encoder(format: &str, dest: Box<dyn Destination>) -> io::Result<Box<dyn Destination>> {
+    Ok(match format {
+        #[cfg(feature = "gzip")]
+        "gzip" => Box::new(flate2::write::GzEncoder::new(dest, flate2::Compression::default())),
+
+        #[cfg(feature = "zstd")]
+        "zstd" => Box::new(zstd::Encoder::new(dest, zstd::DEFAULT_COMPRESSION_LEVEL)?),
+
+        _ => dest,
+    })
+}
+
+/// Get the compression format matching an output file's extension, if any
+pub fn format_of_extension(ext: &OsStr) -> Option<&'static str> {
+    match ext.to_str()? {
+        #[cfg(feature = "gzip")]
+        "gz" => Some("gzip"),
+
+        #[cfg(feature = "zstd")]
+        "zst" => Some("zstd"),
+
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::io::{Read, Write};
+
+    /// Compress a files list into a file with the provided format, and get the compressed file's content
+    fn compress(format: &str, listing: &str) -> Vec<u8> {
+        let file = tempfile::NamedTempFile::new().unwrap();
+
+        let mut encoder = encoder(format, Box::new(file.reopen().unwrap())).unwrap();
+        encoder.write_all(listing.as_bytes()).unwrap();
+        encoder.finish().unwrap();
+
+        fs::read(file.path()).unwrap()
+    }
+
+    /// Build a files list large enough to be written in multiple chunks
+    fn listing() -> String {
+        (0..20_000)
+            .map(|i| format!("home/user/projects/project-{}/src/file-{}.rs\n", i / 100, i))
+            .collect()
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn gzip_round_trip() {
+        let listing = listing();
+        let compressed = compress("gzip", &listing);
+
+        assert!(compressed.len() < listing.len() / 4);
+
+        let mut decompressed = String::new();
+        flate2::read::GzDecoder::new(compressed.as_slice()).read_to_string(&mut decompressed).unwrap();
+
+        assert_eq!(decompressed, listing);
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn zstd_round_trip() {
+        let listing = listing();
+        let compressed = compress("zstd", &listing);
+
+        assert!(compressed.len() < listing.len() / 4);
+
+        let mut decompressed = String::new();
+        zstd::Decoder::new(compressed.as_slice()).unwrap().read_to_string(&mut decompressed).unwrap();
+
+        assert_eq!(decompressed, listing);
+    }
+
+    #[test]
+    fn formats_of_extensions() {
+        assert_eq!(format_of_extension(OsStr::new("txt")), None);
+
+        #[cfg(feature = "gzip")]
+        assert_eq!(format_of_extension(OsStr::new("gz")), Some("gzip"));
+
+        #[cfg(feature = "zstd")]
+        assert_eq!(format_of_extension(OsStr::new("zst")), Some("zstd"));
+    }
+}
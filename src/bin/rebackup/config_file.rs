@@ -0,0 +1,316 @@
+//! Configuration files of the CLI (see the `--config` option)
+//!
+//! Configuration files are written in TOML: each key is the long name of a command-line option (e.g. `exclude-glob` or
+//! `exclude_glob`, `sources` for the source directories), and its value is a string, a number, a date, a boolean (for
+//! flags) or an array of these (for options accepting multiple values). Tables are not supported.
+//!
+//! The file is converted to command-line arguments, so it supports exactly the same options as the command line.
+//! Options that are provided on the command line override the file's ones.
+
+use clap::{App, AppSettings, Arg, ArgMatches, ArgSettings};
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::ffi::OsString;
+use std::path::PathBuf;
+use toml::{Table, Value};
+
+/// Options which can't be set from a configuration file
+const CLI_ONLY_OPTIONS: &[&str] = &["help", "version", "config", "print-config"];
+
+/// Content of a configuration file, mirroring the command-line options
+#[derive(Deserialize)]
+pub struct ConfigFile {
+    /// Source directories (the positional arguments)
+    #[serde(default)]
+    pub sources: Vec<PathBuf>,
+
+    /// Other options, by long name (they are checked against the application's options once the file is loaded)
+    #[serde(flatten)]
+    pub options: Table,
+}
+
+/// Find out which options are present on the command line, without validating them
+///
+/// The arguments are parsed with a copy of the application's options but without their constraints, as these may be
+/// satisfied by the configuration file. Returns `None` if the arguments can't be parsed (e.g. for `--help`), in which
+/// case they should be handled by the actual application.
+pub fn cli_matches(app: &App, args: &[OsString]) -> Option<ArgMatches> {
+    let mut bare = App::new(app.get_name())
+        .setting(AppSettings::DisableHelpFlags)
+        .setting(AppSettings::DisableVersion);
+
+    for arg in app.get_arguments() {
+        let mut copy = Arg::new(arg.get_name());
+
+        // Settings must only be set when enabled, as disabling them may disable related ones
+        for setting in [ArgSettings::MultipleValues, ArgSettings::MultipleOccurrences, ArgSettings::TakesValue] {
+            if arg.is_set(setting) {
+                copy = copy.setting(setting);
+            }
+        }
+
+        if let Some(long) = arg.get_long() {
+            copy = copy.long(long);
+        }
+
+        if let Some(short) = arg.get_short() {
+            copy = copy.short(short);
+        }
+
+        bare = bare.arg(copy);
+    }
+
+    bare.try_get_matches_from(args).ok()
+}
+
+/// Build the command-line arguments corresponding to a configuration file
+///
+/// Options which are present in the provided matches (from the command line) are skipped. Source directories are returned
+/// separately, as positional arguments must be provided after all options.
+pub fn config_args(app: &App, content: &str, cli: &ArgMatches) -> Result<(Vec<OsString>, Vec<OsString>), String> {
+    let config: ConfigFile = toml::from_str(content).map_err(|err| err.to_string())?;
+
+    let mut args = vec![];
+    let mut keys = HashSet::new();
+
+    for (key, value) in config.options {
+        let name = key.replace('_', "-");
+
+        if !keys.insert(name.clone()) {
+            return Err(format!("duplicate key '{}'", key));
+        }
+
+        let arg = app
+            .get_arguments()
+            .filter(|arg| !CLI_ONLY_OPTIONS.contains(&arg.get_name()) && !is_positional(arg))
+            .find(|arg| arg.get_long() == Some(name.as_str()))
+            .ok_or_else(|| format!("unknown key '{}'", key))?;
+
+        let invalid = |expected: &str| format!("invalid value for key '{}', expected {}", key, expected);
+
+        if cli.occurrences_of(arg.get_name()) > 0 {
+            continue;
+        }
+
+        if !arg.is_set(ArgSettings::TakesValue) {
+            match value {
+                Value::Boolean(true) => args.push(format!("--{}", name).into()),
+                Value::Boolean(false) => {}
+                _ => return Err(invalid("a boolean")),
+            }
+
+            continue;
+        }
+
+        let values = match value {
+            Value::Array(values) if arg.is_set(ArgSettings::MultipleValues) => values,
+            Value::Array(_) => return Err(invalid("a single value")),
+            value => vec![value],
+        };
+
+        for value in values {
+            let value = match value {
+                Value::String(string) => string,
+                Value::Integer(int) => int.to_string(),
+                Value::Float(float) => float.to_string(),
+                Value::Boolean(bool) => bool.to_string(),
+                Value::Datetime(datetime) => datetime.to_string(),
+                Value::Array(_) | Value::Table(_) => return Err(invalid("a string, a number, a date or a boolean")),
+            };
+
+            args.push(format!("--{}={}", name, value).into());
+        }
+    }
+
+    let sources = if cli.occurrences_of("sources") > 0 {
+        vec![]
+    } else {
+        config.sources.into_iter().map(OsString::from).collect()
+    };
+
+    Ok((args, sources))
+}
+
+/// Write the options set in the provided matches as a configuration file
+pub fn print_config(app: &App, matches: &ArgMatches) -> String {
+    let mut config = Table::new();
+
+    for arg in app.get_arguments().filter(|arg| !CLI_ONLY_OPTIONS.contains(&arg.get_name())) {
+        if matches.occurrences_of(arg.get_name()) == 0 {
+            continue;
+        }
+
+        let key = arg.get_long().unwrap_or_else(|| arg.get_name());
+
+        if !arg.is_set(ArgSettings::TakesValue) && !is_positional(arg) {
+            config.insert(key.to_string(), Value::Boolean(true));
+            continue;
+        }
+
+        let mut values: Vec<_> = matches
+            .values_of_os(arg.get_name())
+            .into_iter()
+            .flatten()
+            .map(|value| toml_value(&value.to_string_lossy()))
+            .collect();
+
+        let value = if arg.is_set(ArgSettings::MultipleValues) {
+            Value::Array(values)
+        } else {
+            values.remove(0)
+        };
+
+        config.insert(key.to_string(), value);
+    }
+
+    toml::to_string(&config).expect("Internal error: failed to serialize configuration")
+}
+
+/// (Internal) Check if an argument is positional (its index is only known once the application is built)
+fn is_positional(arg: &Arg) -> bool {
+    arg.get_long().is_none() && arg.get_short().is_none()
+}
+
+/// (Internal) Get a value as a TOML integer if it is one, or as a string otherwise
+fn toml_value(value: &str) -> Value {
+    match value.parse::<i64>() {
+        Ok(int) => Value::Integer(int),
+        Err(_) => Value::String(value.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_opts, walker_config, Opts};
+    use clap::IntoApp;
+    use rebackup::WalkerConfig;
+    use std::path::Path;
+
+    /// Path to the example configuration file
+    const EXAMPLE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/rebackup.example.toml");
+
+    /// Parse command-line arguments (the program's name is added automatically)
+    fn opts(args: &[&str]) -> Opts {
+        parse_opts(std::iter::once("rebackup").chain(args.iter().copied()).map(OsString::from).collect())
+    }
+
+    /// Describe the comparable parts of a walker's configuration
+    fn describe(config: &WalkerConfig) -> String {
+        let rules: Vec<_> = config
+            .rules
+            .iter()
+            .map(|rule| (rule.name, rule.description.clone(), rule.priority, rule.only_for))
+            .collect();
+
+        format!(
+            "{:?}",
+            (
+                rules,
+                (
+                    config.output_paths,
+                    config.symlink_mode,
+                    config.symlink_escape,
+                    config.emit_directories,
+                    config.output_types
+                ),
+                (
+                    config.drop_empty_dirs,
+                    config.max_depth,
+                    config.max_items,
+                    config.fail_if_empty,
+                    config.include_special_files
+                ),
+                (config.min_file_size, config.max_file_size, config.modified_after, config.modified_before),
+                (
+                    config.skip_unreadable,
+                    config.dedup_hard_links,
+                    config.sort_entries,
+                    config.strategy,
+                    config.on_error
+                ),
+                (config.time_rules, config.explain, config.progress.is_some()),
+            )
+        )
+    }
+
+    #[test]
+    fn example_matches_command_line() {
+        let from_file = opts(&["--config", EXAMPLE]);
+
+        let from_cli = opts(&[
+            "--output",
+            "backup.list",
+            "--exclude",
+            "*.tmp",
+            "--exclude",
+            "*.log",
+            "--rule",
+            "dotgit",
+            "--rule",
+            "node_modules",
+            "--rule",
+            "rust_cargo_build",
+            "--keep-symlinks",
+            "--max-depth",
+            "20",
+            "--keep-going",
+            "/home/me/projects",
+        ]);
+
+        assert_eq!(from_file.sources, vec![Path::new("/home/me/projects")]);
+        assert_eq!(from_file.sources, from_cli.sources);
+        assert_eq!(from_file.output, from_cli.output);
+
+        let from_file = describe(&walker_config(&from_file, false));
+        assert_eq!(from_file, describe(&walker_config(&from_cli, false)));
+        assert_ne!(from_file, describe(&walker_config(&opts(&["/home/me/projects"]), false)));
+    }
+
+    #[test]
+    fn command_line_overrides_file() {
+        let opts = opts(&["--config", EXAMPLE, "--max-depth", "3", "/tmp"]);
+
+        assert_eq!(opts.max_depth, Some(3));
+        assert_eq!(opts.sources, vec![Path::new("/tmp")]);
+        assert!(opts.keep_going);
+    }
+
+    #[test]
+    fn invalid_files() {
+        let app = Opts::into_app();
+        let cli = cli_matches(&app, &["rebackup".into()]).unwrap();
+
+        let err = |content: &str| config_args(&app, content, &cli).unwrap_err();
+
+        assert!(err("unknown = true").contains("unknown key 'unknown'"));
+        assert!(err("keep-going = 1").contains("expected a boolean"));
+        assert!(err("max-depth = [1, 2]").contains("expected a single value"));
+        assert!(err("max_depth = 1\nmax-depth = 2").contains("duplicate key"));
+        assert!(err("max-depth = { value = 1 }").contains("expected a string, a number, a date or a boolean"));
+        assert!(err("keep-going = ").contains("line 1"));
+    }
+
+    #[test]
+    fn printed_config_can_be_loaded_back() {
+        let app = Opts::into_app();
+        let matches = app
+            .clone()
+            .get_matches_from(["rebackup", "--max-depth", "4", "--prefix", "a \"quoted\"\\path/", "--keep-going", "/home/me"]);
+
+        let printed = print_config(&app, &matches);
+        let cli = cli_matches(&app, &["rebackup".into()]).unwrap();
+
+        let (args, sources) = config_args(&app, &printed, &cli).unwrap();
+
+        assert_eq!(
+            args,
+            vec![
+                OsString::from("--prefix=a \"quoted\"\\path/"),
+                OsString::from("--max-depth=4"),
+                OsString::from("--keep-going")
+            ]
+        );
+        assert_eq!(sources, vec![OsString::from("/home/me")]);
+    }
+}
@@ -3,33 +3,275 @@
 #![forbid(unsafe_code)]
 #![forbid(unused_must_use)]
 
+mod checksum;
+mod completions;
+#[cfg(any(feature = "gzip", feature = "zstd"))]
+mod compress;
+mod config_file;
+mod output;
+mod parsers;
+mod previous;
+mod progress;
 mod rules;
+#[cfg(feature = "tar-output")]
+mod tar;
+mod verify;
+mod watch;
 
-use atomic::Ordering;
-use clap::{crate_authors, crate_description, crate_name, crate_version, Clap};
+use clap::{crate_authors, crate_description, crate_name, crate_version, AppSettings, Clap, FromArgMatches, IntoApp};
+use previous::Previous;
 use rebackup::*;
-use rules::{make_rules, RulesOpts};
+use rules::{apply_post_walk_filters, has_post_walk_filters, make_rules, RulesOpts};
+use std::cmp::{Ordering, Reverse};
+use std::collections::{HashMap, HashSet};
+use std::ffi::OsString;
 use std::fs;
-use std::path::PathBuf;
+use std::io::{self, BufWriter, IsTerminal, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// Output formats of the files list
+#[cfg(feature = "tar-output")]
+const OUTPUT_FORMATS: &[&str] = &["lines", "json", "jsonl", "csv", "tsv", "tar"];
+
+/// Output formats of the files list
+#[cfg(not(feature = "tar-output"))]
+const OUTPUT_FORMATS: &[&str] = &["lines", "json", "jsonl", "csv", "tsv"];
+
+/// Compression formats of the output
+const COMPRESSION_FORMATS: &[&str] = &[
+    "none",
+    #[cfg(feature = "gzip")]
+    "gzip",
+    #[cfg(feature = "zstd")]
+    "zstd",
+];
+
+/// Description of the program displayed with `--help`, including the exit codes
+const LONG_ABOUT: &str = concat!(
+    env!("CARGO_PKG_DESCRIPTION"),
+    "
+
+EXIT CODES:
+    0     Success
+    2     Source directory not found or invalid (also used for invalid arguments)
+    3     Failed to build the files list (walk failure, or checksum failure without --keep-going)
+    4     Invalid UTF-8 item name, or name containing a line break without --print0
+    5     Failed to write the files list or another output file
+    6     Partial success: some items could not be read and were skipped (see --keep-going)
+    7     Too many items (see --max-items)
+    8     Empty files list (see --fail-if-empty)
+    9     Failed to read a snapshot or previous files list
+    10    Invalid pattern, rule or patterns file
+    11    Failed to read the configuration file, or invalid configuration file
+    12    Some entries are missing or changed type (see --verify)"
+);
 
 #[derive(Clap)]
-#[clap(name = crate_name!(), version = crate_version!(), about = crate_description!(), author = crate_authors!())]
+#[clap(
+    name = crate_name!(),
+    version = crate_version!(),
+    about = crate_description!(),
+    long_about = LONG_ABOUT,
+    author = crate_authors!(),
+    setting = AppSettings::SubcommandsNegateReqs,
+    setting = AppSettings::ArgsNegateSubcommands
+)]
 pub struct Opts {
-    #[clap(about = "Source directory")]
-    pub source: PathBuf,
+    #[clap(subcommand)]
+    pub command: Option<Command>,
+
+    #[clap(
+        long,
+        about = "Load options from a TOML file, whose keys are the long names of the options (options provided on the command line take precedence)"
+    )]
+    pub config: Option<PathBuf>,
+
+    #[clap(
+        long,
+        about = "Print the effective configuration (merged from the configuration file and the command line) as TOML, and exit"
+    )]
+    pub print_config: bool,
+
+    #[clap(
+        required_unless_present = "sources-from",
+        min_values = 1,
+        about = "Source directories (relative paths are resolved against the current directory)"
+    )]
+    pub sources: Vec<PathBuf>,
+
+    #[clap(
+        long,
+        about = "Read source directories from a file ('-' for STDIN), one per line (blank lines and lines starting with '#' are ignored)"
+    )]
+    pub sources_from: Option<PathBuf>,
+
+    #[clap(
+        long,
+        requires = "sources-from",
+        about = "Separate the source directories of --sources-from with NUL characters instead of line breaks"
+    )]
+    pub null_sources: bool,
 
     #[clap(short, long, about = "Output file (will print to STDOUT if empty)")]
     pub output: Option<PathBuf>,
 
+    #[clap(
+        long,
+        about = "Check that the entries of a previous files list or snapshot still exist in the source directory with the same type, and print the ones that don't (see --absolute and --mark-dirs for the entries' style)"
+    )]
+    pub verify: Option<PathBuf>,
+
+    #[clap(long, requires = "verify", about = "Remove a prefix from the entries of the files list to verify")]
+    pub strip_prefix: Option<String>,
+
+    #[clap(
+        long,
+        requires = "output",
+        about = "Rebuild the files list when the source directories change, and replace the output file when it changed (until the program is interrupted)"
+    )]
+    pub watch: bool,
+
+    #[clap(
+        long,
+        requires = "watch",
+        parse(try_from_str = parsers::parse_timeout),
+        about = "Delay without changes before the files list is rebuilt in watch mode (e.g. '0.2', '2s', default: 0.5s)"
+    )]
+    pub watch_debounce: Option<Duration>,
+
+    /// Output file of the files list when the program is run by the watch mode (which replaces the actual output file with it)
+    #[clap(long, hidden = true)]
+    pub watch_output: Option<PathBuf>,
+
     #[clap(short, long, about = "Output absolute paths (default is relative)")]
     pub absolute: bool,
 
-    #[clap(short, long, about = "Prefix all output lines with a specific string")]
-    pub prefix: Option<String>,
+    #[clap(
+        long,
+        conflicts_with = "absolute",
+        about = "Output paths relative to this directory instead of the source directory (which must be located inside it)"
+    )]
+    pub relative_to: Option<PathBuf>,
+
+    #[clap(
+        long,
+        about = "Output format: one path per line, a JSON array of objects, one JSON object per line, a CSV or TSV table, or a tar archive of the items (default: lines)",
+        possible_values = OUTPUT_FORMATS
+    )]
+    pub format: Option<String>,
+
+    #[clap(
+        long,
+        about = "Compress the output (default: gzip if the output file's extension is '.gz', zstd if it is '.zst', none otherwise)",
+        possible_values = COMPRESSION_FORMATS
+    )]
+    pub compress: Option<String>,
+
+    #[clap(long, about = "Include the size and modification time of items in the JSON output formats")]
+    pub metadata: bool,
+
+    #[clap(long, about = "Don't write the header row of the CSV and TSV output formats")]
+    pub no_header: bool,
+
+    #[clap(
+        long,
+        about = "Prefix each file with its checksum, in the same format as the 'sha256sum' command (BLAKE3 requires the 'blake3' feature)",
+        possible_values = checksum::ALGORITHMS
+    )]
+    pub checksum: Option<String>,
+
+    #[clap(long, requires = "checksum", about = "Don't list items that are not files when computing checksums")]
+    pub checksum_skip_non_files: bool,
 
-    #[clap(long, about = "Don't sort the items by path")]
+    #[clap(
+        long,
+        requires = "checksum",
+        about = "Number of threads to compute checksums with (0 to use the number of CPU cores, default: 1)"
+    )]
+    pub checksum_jobs: Option<usize>,
+
+    #[clap(
+        short = '0',
+        long,
+        about = "Separate items with NUL characters instead of line breaks, without a trailing separator (like 'find -print0')"
+    )]
+    pub print0: bool,
+
+    #[clap(
+        long,
+        about = "Save a snapshot of the listed items (with their size, modification time and checksum if --checksum is used) to a file"
+    )]
+    pub save_snapshot: Option<PathBuf>,
+
+    #[clap(
+        long,
+        about = "Only list the items added or modified since a snapshot, or the items absent from a previous files list"
+    )]
+    pub diff_against: Option<PathBuf>,
+
+    #[clap(
+        long,
+        requires = "diff-against",
+        about = "Display the entries of the previous files list which were removed to STDERR (always done for snapshots)"
+    )]
+    pub diff_removed: bool,
+
+    #[clap(long, requires = "diff-against", about = "Write the removed entries to a file instead of STDERR")]
+    pub removed_output: Option<PathBuf>,
+
+    #[clap(
+        long,
+        conflicts_with = "diff-against",
+        about = "Only list the items added or modified since a snapshot (like --diff-against, without reporting removed items)"
+    )]
+    pub changed_since: Option<PathBuf>,
+
+    #[clap(
+        long,
+        parse(try_from_str = parsers::parse_mtime_window),
+        about = "Consider modification times within this duration of the snapshot's ones as unchanged (e.g. '2s' for FAT filesystems)"
+    )]
+    pub mtime_window: Option<Duration>,
+
+    #[clap(
+        short,
+        long,
+        number_of_values = 1,
+        about = "Prefix all output lines with a specific string (provide it once per source directory to use a different prefix for each, default with multiple sources: each source directory's name followed by a slash)"
+    )]
+    pub prefix: Vec<String>,
+
+    #[clap(
+        long,
+        about = "Append a specific string to all output lines, after the path (it is not taken into account when sorting items)"
+    )]
+    pub suffix: Option<String>,
+
+    /// Prefix of the items of each source directory, indexed by their canonicalized path (filled from `--prefix`)
+    #[clap(skip)]
+    pub source_prefixes: HashMap<PathBuf, String>,
+
+    #[clap(long, about = "Don't sort the items by path (items are then written as soon as they are found)")]
     pub no_sort: bool,
 
+    #[clap(
+        long,
+        conflicts_with = "no-sort",
+        possible_values = &["name", "size", "mtime"],
+        about = "Sort items by path, by size (largest first) or by modification time (most recent first) (default: name)"
+    )]
+    pub sort_by: Option<String>,
+
+    #[clap(long, conflicts_with = "no-sort", about = "Reverse the sorting order")]
+    pub reverse: bool,
+
+    #[clap(
+        long,
+        about = "Traverse each directory's entries sorted by name (useful with --no-sort for a deterministic output)"
+    )]
+    pub sort_entries: bool,
+
     #[clap(
         long,
         about = "Convert invalid UTF-8 filenames to lossy filenames (this may cause problems with custom commands)"
@@ -39,103 +281,1082 @@ pub struct Opts {
     #[clap(short, long, about = "Don't backup items with invalid UTF-8 filenames")]
     pub ignore_non_utf8_filenames: bool,
 
+    #[clap(
+        long,
+        conflicts_with_all = &["allow-non-utf8-filenames", "ignore-non-utf8-filenames"],
+        about = "Output invalid UTF-8 filenames as their raw bytes (WTF-8 on Windows), best used with --print0"
+    )]
+    pub raw_paths: bool,
+
+    #[clap(
+        long,
+        about = "Keep the verbatim prefix of absolute paths ('\\\\?\\'), which is removed by default (Windows only)"
+    )]
+    pub keep_verbatim: bool,
+
+    #[clap(long, about = "Separate path components with slashes instead of backslashes (Windows only)")]
+    pub unix_separators: bool,
+
     #[clap(short = 's', long, about = "Follow symbolic links")]
     pub follow_symlinks: bool,
 
+    #[clap(long, conflicts_with = "follow-symlinks", about = "List symbolic links as entries without following them")]
+    pub keep_symlinks: bool,
+
+    #[clap(
+        long,
+        about = "What to do with followed symbolic links pointing outside of the source directory (default: skip)",
+        possible_values = &["allow", "skip", "error"]
+    )]
+    pub symlink_escape: Option<String>,
+
     #[clap(long, about = "Drop empty directories")]
     pub drop_empty_dirs: bool,
 
+    #[clap(long, about = "Append a slash to the path of directories (like 'ls -p')")]
+    pub mark_dirs: bool,
+
+    #[clap(
+        long,
+        about = "Only list files (can be combined with the other '--*-only' options, directories are still traversed)"
+    )]
+    pub files_only: bool,
+
+    #[clap(long, about = "Only list directories (can be combined with the other '--*-only' options)")]
+    pub dirs_only: bool,
+
+    #[clap(
+        long,
+        requires = "keep-symlinks",
+        about = "Only list symbolic links (can be combined with the other '--*-only' options)"
+    )]
+    pub symlinks_only: bool,
+
+    #[clap(long, about = "List every traversed directory, not only empty ones")]
+    pub list_dirs: bool,
+
+    #[clap(long, about = "Maximum depth to traverse (0 to only list the source directory's direct children)")]
+    pub max_depth: Option<usize>,
+
+    #[clap(long, about = "Fail with exit code 7 if the files list contains more than the provided number of items")]
+    pub max_items: Option<usize>,
+
+    #[clap(long, about = "Fail with exit code 8 if the files list is empty (the source directory itself doesn't count)")]
+    pub fail_if_empty: bool,
+
+    #[clap(
+        long,
+        parse(try_from_str = parsers::parse_size),
+        about = "Exclude files smaller than the provided size (e.g. '500', '10K', '1.5G')"
+    )]
+    pub min_size: Option<u64>,
+
+    #[clap(
+        long,
+        parse(try_from_str = parsers::parse_size),
+        about = "Exclude files larger than the provided size (e.g. '500', '10K', '1.5G')"
+    )]
+    pub max_size: Option<u64>,
+
+    #[clap(
+        long,
+        parse(try_from_str = parsers::parse_time),
+        about = "Exclude files modified before the provided time (RFC 3339 timestamp, duration like '7d' or '36h', or '@file' to use a file's modification time)"
+    )]
+    pub newer_than: Option<SystemTime>,
+
+    #[clap(
+        long,
+        parse(try_from_str = parsers::parse_time),
+        about = "Exclude files modified after the provided time (RFC 3339 timestamp, duration like '7d' or '36h', or '@file' to use a file's modification time)"
+    )]
+    pub older_than: Option<SystemTime>,
+
+    #[clap(long, about = "Skip items that cannot be read because of insufficient permissions")]
+    pub skip_unreadable: bool,
+
+    #[clap(long, about = "Include special files (named pipes, sockets, devices)")]
+    pub include_special_files: bool,
+
+    #[clap(long, about = "Only list the first path found for files with multiple hard links (Unix only)")]
+    pub dedup_hard_links: bool,
+
+    #[clap(
+        short,
+        long,
+        about = "Skip items that cannot be read instead of failing, and exit with code 6 after producing the partial list"
+    )]
+    pub keep_going: bool,
+
     #[clap(short, long, about = "Display debug informations")]
     pub verbose: bool,
 
+    #[clap(
+        long,
+        about = "Display errors to STDERR as JSON objects, one per line (with the error's code and path when available)"
+    )]
+    pub errors_json: bool,
+
+    #[clap(
+        long,
+        about = "Display the progress of the walk to STDERR (only when it is a terminal, and the files list isn't written to the same terminal)"
+    )]
+    pub progress: bool,
+
+    #[clap(long, about = "Display statistics about each rule to STDERR (also displayed in verbose mode)")]
+    pub rule_stats: bool,
+
+    #[clap(
+        long,
+        about = "Display the time spent in each rule and the number of items it was run on to STDERR, from the slowest rule to the fastest"
+    )]
+    pub benchmark_rules: bool,
+
+    #[clap(
+        long,
+        about = "Display a summary of the files list (counts, total size, largest files) to STDERR after the listing"
+    )]
+    pub stats: bool,
+
+    #[clap(long, about = "Display the items excluded or mapped by rules to STDERR, with the rule responsible for it")]
+    pub explain: bool,
+
     #[clap(flatten)]
     pub rules: RulesOpts,
 
     #[clap(long, about = "Simulate the listing without priting / writing the actual files list (useful for debugging)")]
     pub dry_run: bool,
+
+    #[clap(
+        long,
+        conflicts_with_all = &["output", "format", "checksum"],
+        about = "Only print the number of items of the files list"
+    )]
+    pub count: bool,
+
+    #[clap(
+        long,
+        conflicts_with_all = &["output", "format", "checksum"],
+        about = "Only print the total size of the listed files, in bytes (printed after the number of items with --count)"
+    )]
+    pub total_size: bool,
+
+    #[clap(long, requires = "total-size", about = "Print the total size in a human-readable format (e.g. '1.4 GiB')")]
+    pub human_readable: bool,
+}
+
+impl Opts {
+    /// Get the prefix of the items of a (canonicalized) source directory
+    pub fn prefix_of(&self, source: &Path) -> &str {
+        self.source_prefixes.get(source).or_else(|| self.prefix.first()).map_or("", String::as_str)
+    }
+
+    /// Get the suffix of all items
+    pub fn suffix(&self) -> &str {
+        self.suffix.as_deref().unwrap_or("")
+    }
+}
+
+/// Subcommands (the files list is built when none is provided)
+#[derive(Clap)]
+pub enum Command {
+    #[clap(about = "Print a completion script for a shell to STDOUT")]
+    Completions {
+        #[clap(possible_values = completions::SHELLS, about = "Shell to generate the completion script for")]
+        shell: String,
+    },
 }
 
 fn main() {
-    let opts = Opts::parse();
+    let mut opts = parse_opts(std::env::args_os().collect());
+
+    if let Some(Command::Completions { shell }) = &opts.command {
+        print!("{}", completions::generate(shell, Opts::into_app()));
+        return;
+    }
+
+    if opts.errors_json {
+        set_log_format(LoggerFormat::Json);
+    }
+
+    // When run by the watch mode, the files list is built once to the provided file
+    if let Some(output) = opts.watch_output.take() {
+        opts.output = Some(output);
+        opts.watch = false;
+    }
 
     if opts.verbose {
-        LOGGER_LEVEL.store(LoggerLevel::Debug, Ordering::SeqCst);
+        set_log_level(LoggerLevel::Debug);
     } else if opts.output.is_none() {
         // Prevent STDOUT from being polluated with messages when the files list is output to it
-        LOGGER_LEVEL.store(LoggerLevel::Error, Ordering::SeqCst);
+        set_log_level(LoggerLevel::Error);
     }
 
-    if !opts.source.is_dir() {
-        fail!(exit 2, "Source directory was not found at path: {}", opts.source.display());
+    if let Some(path) = &opts.sources_from {
+        let content = if path == Path::new("-") {
+            let mut content = vec![];
+            io::stdin().lock().read_to_end(&mut content).map(|_| content)
+        } else {
+            fs::read(path)
+        };
+
+        let content = content.unwrap_or_else(|err| fail!(exit 2, "Failed to read source directories from {}: {}", path.display(), err));
+        let sources = parsers::parse_sources_list(&content, opts.null_sources);
+
+        if sources.is_empty() && opts.sources.is_empty() {
+            fail!(exit 2, "No source directory was provided in {}", path.display());
+        }
+
+        opts.sources.extend(sources);
+    }
+
+    // All source directories are checked before any of them is walked
+    let missing: Vec<_> = opts.sources.iter().filter(|source| !source.is_dir()).collect();
+
+    if !missing.is_empty() {
+        for source in &missing {
+            err!("Source directory was not found at path: {}", source.display());
+        }
+
+        fail!(exit 2, "{} source directory(ies) were not found", missing.len());
+    }
+
+    let sources: Vec<_> = opts
+        .sources
+        .iter()
+        .map(|source| {
+            fs::canonicalize(source)
+                .unwrap_or_else(|err| fail!(exit 2, "Failed to canonicalize source directory: {} (from path {})", err, source.display()))
+        })
+        .collect();
+
+    if let Some(list) = &opts.verify {
+        if sources.len() > 1 {
+            fail!(exit 2, "Only one source directory can be provided with --verify");
+        }
+
+        verify::run(&opts, list, &sources[0]);
+    }
+
+    let relative_to = opts.relative_to.as_ref().map(|base| {
+        let base =
+            fs::canonicalize(base).unwrap_or_else(|err| fail!(exit 2, "Failed to canonicalize base directory: {} (from path {})", err, base.display()));
+
+        for source in &sources {
+            if !source.starts_with(&base) {
+                fail!(exit 2, "Source directory {} is not located inside base directory {}", source.display(), base.display());
+            }
+        }
+
+        base
+    });
+
+    opts.source_prefixes = source_prefixes(&opts, &sources, relative_to.is_some());
+
+    if opts.watch {
+        watch::run(&opts, &sources);
     }
 
     info!("Building files list...");
 
-    let source = fs::canonicalize(&opts.source)
-        .unwrap_or_else(|err| fail!(exit 2, "Failed to canonicalize source directory: {} (from path {})", err, opts.source.display()));
+    // The progress indicator would be mixed with the files list if it was written to the terminal
+    let show_progress = opts.progress && io::stderr().is_terminal() && !(opts.output.is_none() && io::stdout().is_terminal());
+
+    let config = walker_config(&opts, show_progress);
+
+    let dest: Box<dyn output::Destination> = match &opts.output {
+        _ if opts.dry_run || opts.count || opts.total_size => Box::new(io::sink()),
+        Some(dest) => Box::new(fs::File::create(dest).unwrap_or_else(|err| fail!(exit 5, "Failed to create output file: {}", err))),
+        None => Box::new(io::stdout()),
+    };
+
+    // Compression wraps the destination, so items are compressed as they are written
+    #[cfg(any(feature = "gzip", feature = "zstd"))]
+    let dest = match opts.compress.as_deref().or_else(|| {
+        opts.output
+            .as_ref()
+            .and_then(|output| output.extension())
+            .and_then(compress::format_of_extension)
+    }) {
+        Some(format) if !opts.dry_run => {
+            compress::encoder(format, dest).unwrap_or_else(|err| fail!(exit 5, "Failed to initialize the output's compression: {}", err))
+        }
+        _ => dest,
+    };
+
+    let mut writer = output::ListWriter::new(&opts, dest, opts.output.is_none());
 
-    let items = walk(
-        &source,
-        &WalkerConfig {
-            rules: make_rules(&opts.rules),
-            follow_symlinks: opts.follow_symlinks,
-            drop_empty_dirs: opts.drop_empty_dirs,
+    // Items are written as soon as they are found, unless they must be sorted or compared to a snapshot first
+    let stream = opts.no_sort
+        && opts.save_snapshot.is_none()
+        && opts.diff_against.is_none()
+        && opts.changed_since.is_none()
+        && !opts.count
+        && !opts.total_size
+        && !has_post_walk_filters(&opts.rules);
+
+    // Directories are marked with a trailing slash, except in archives which already do it
+    let mark_dirs = opts.mark_dirs && opts.format.as_deref() != Some("tar");
+
+    let mut found = HashMap::<PathBuf, Vec<PathBuf>>::new();
+
+    // Size and modification time of items when sorting by them, indexed by their full path
+    let sort_by = opts.sort_by.as_deref().unwrap_or("name");
+    let mut sort_keys = HashMap::<PathBuf, (u64, Option<SystemTime>)>::new();
+
+    let outcome = walk_multiple_tolerant_with(&sources, &config, |source, item| {
+        let path = match item.item_type {
+            WalkerItemType::Directory if mark_dirs => mark_dir(item.path),
+            _ => item.path,
+        };
+
+        if stream {
+            let (source, path) = match &relative_to {
+                Some(base) => (base.as_path(), relative_to_base(base, source, path)),
+                None => (source, path),
+            };
+
+            writer.write_item(source, &path).unwrap_or_else(|err| write_failed(err))
+        } else {
+            if sort_by != "name" {
+                sort_keys.insert(source.join(&path), (item.metadata.len(), item.metadata.modified().ok()));
+            }
+
+            found.entry(source.to_path_buf()).or_default().push(path);
+        }
+    });
+
+    if show_progress {
+        progress::finish();
+    }
+
+    let MultiWalkOutcome {
+        mut items,
+        errors,
+        stats,
+        exclusions,
+    } = outcome.unwrap_or_else(|err| walk_failed(err));
+
+    for (source, items) in &mut items {
+        *items = found.remove(source).unwrap_or_default();
+    }
+
+    // Filters are applied before paths are made relative to the base directory, as they need their source directory
+    if has_post_walk_filters(&opts.rules) {
+        apply_post_walk_filters(&opts.rules, &mut items);
+    }
+
+    // Make paths relative to the base directory instead of their source directory
+    if let Some(base) = &relative_to {
+        for (source, items) in &mut items {
+            for path in items.iter_mut() {
+                *path = relative_to_base(base, source, std::mem::take(path));
+            }
+
+            *source = base.clone();
+        }
+    }
+
+    if opts.rule_stats || opts.verbose {
+        print_rule_stats(&stats);
+    }
+
+    if opts.benchmark_rules {
+        print_rules_benchmark(&stats);
+    }
+
+    if opts.verbose && !stats.duplicates.is_empty() {
+        eprintln!("{} item(s) were skipped as they were already walked on:", stats.duplicates.len());
+
+        for duplicate in &stats.duplicates {
+            let cause = match duplicate.cause {
+                DuplicateCause::AlreadyVisited => "already visited",
+                DuplicateCause::SymlinkTargetVisited => "symbolic link's target already visited",
+            };
+
+            match &duplicate.original {
+                Some(original) => eprintln!("  {} => {} ({})", duplicate.duplicate.display(), original.display(), cause),
+                None => eprintln!("  {} ({})", duplicate.duplicate.display(), cause),
+            }
+        }
+    }
+
+    for (path, reason) in exclusions {
+        // Items are relative to the innermost (walked) source directory containing them
+        let source = items
+            .iter()
+            .map(|(source, _)| source)
+            .filter(|source| path.starts_with(source))
+            .max_by_key(|source| source.as_os_str().len());
+
+        let path = match source {
+            Some(source) if !opts.absolute => path.strip_prefix(source).unwrap_or(&path),
+            _ => path.as_path(),
+        };
+
+        let action = match reason.result {
+            WalkerRuleResult::MapAsList(_, _) | WalkerRuleResult::MapAsListFromSource(_, _) => "MAPPED",
+            _ => "EXCLUDED",
+        };
+
+        eprintln!("{} {} by {}", action, path.display(), reason.rule_name);
+    }
+
+    let previous = opts.diff_against.as_ref().map(|path| {
+        fs::read(path)
+            .and_then(|content| Previous::parse(&content))
+            .unwrap_or_else(|err| fail!(exit 9, "Failed to read previous files list or snapshot: {}", err))
+    });
+
+    let previous = match &opts.changed_since {
+        Some(path) => match fs::read(path).and_then(|content| Previous::parse(&content)) {
+            Ok(previous @ Previous::Snapshot(_)) => Some(previous),
+            Ok(Previous::List(_)) => fail!(exit 9, "Failed to read snapshot file: {} is not a snapshot", path.display()),
+            Err(err) => fail!(exit 9, "Failed to read snapshot file: {}", err),
         },
-    )
-    .unwrap_or_else(|err| fail!(exit 3, "Failed to build files list: {}", err));
+        None => previous,
+    };
+
+    // Compare the files list to a previous one
+    if let Some(Previous::List(old)) = &previous {
+        previous::check_style(&opts, old);
 
-    debug!("Converting filenames...");
+        let mut current = HashSet::new();
 
-    // Convert the files list to filenames
-    let mut out = vec![];
+        for (source, items) in &mut items {
+            items.retain(|path| {
+                let entry = previous::list_entry(&opts, source, path);
+                let is_new = !old.contains(&entry);
+                current.insert(entry);
+                is_new
+            });
+        }
+
+        if opts.diff_removed || opts.removed_output.is_some() {
+            let mut removed: Vec<_> = old.difference(&current).cloned().collect();
+            removed.sort();
+
+            report_removed(&opts, &removed);
+        }
+    }
+
+    // Compare the files list to a previous snapshot, and save the new one
+    if opts.save_snapshot.is_some() || matches!(previous, Some(Previous::Snapshot(_))) {
+        let snapshot = make_snapshot(&opts, &items);
+
+        if let Some(Previous::Snapshot(old)) = &previous {
+            let diff = Snapshot::diff_within(old, &snapshot, opts.mtime_window.unwrap_or_default());
 
-    for mut path in items {
-        if !opts.absolute {
-            path = path
-                .strip_prefix(&source)
-                .unwrap_or_else(
-                    |err| fail!(exit 3, "Internal: cannot strip prefix from item '{}' with source '{}': {}", path.display(), source.display(), err),
+            if opts.changed_since.is_none() {
+                let removed: Vec<_> = diff.removed.iter().map(|path| output::raw_path_bytes(path)).collect();
+                report_removed(&opts, &removed);
+            }
+
+            let changed: HashSet<_> = diff.added.into_iter().chain(diff.modified).collect();
+
+            for (source, items) in &mut items {
+                items.retain(|path| changed.contains(&snapshot_key(&opts, source, path)));
+            }
+        }
+
+        if let Some(path) = &opts.save_snapshot {
+            if !opts.dry_run {
+                fs::File::create(path)
+                    .and_then(|file| snapshot.write_to(BufWriter::new(file)))
+                    .unwrap_or_else(|err| fail!(exit 5, "Failed to write snapshot file: {}", err));
+            }
+        }
+    }
+
+    // Only display a summary of the files list if asked to
+    if opts.count || opts.total_size {
+        if opts.count {
+            println!("{}", items.iter().map(|(_, items)| items.len()).sum::<usize>());
+        }
+
+        if opts.total_size {
+            // Sizes come from the walk's statistics, unless the files list was then filtered by a comparison
+            let total_size = match previous {
+                None => stats.total_size,
+                Some(_) => files_size(&items),
+            };
+
+            if opts.human_readable {
+                println!("{}", format_size(total_size));
+            } else {
+                println!("{}", total_size);
+            }
+        }
+    } else if !stream {
+        let mut items: Vec<_> = items
+            .into_iter()
+            .flat_map(|(source, items)| items.into_iter().map(move |path| (source.clone(), path)))
+            .collect();
+
+        // Paths are compared as raw bytes, so the order doesn't depend on the locale
+        if !opts.no_sort {
+            debug!("Sorting files list...");
+
+            items.sort_by(|(a_source, a), (b_source, b)| {
+                // Items of different source directories are ordered by their prefix first, the suffix is the same for all items
+                compare_items(
+                    sort_by,
+                    ((opts.prefix_of(a_source), a.as_os_str()), sort_keys.get(&a_source.join(a))),
+                    ((opts.prefix_of(b_source), b.as_os_str()), sort_keys.get(&b_source.join(b))),
                 )
-                .to_path_buf();
+            });
+
+            if opts.reverse {
+                items.reverse();
+            }
+        }
+
+        for (source, path) in &items {
+            writer.write_item(source, path).unwrap_or_else(|err| write_failed(err));
+        }
+    }
+
+    let checksum_errors = writer.finish().unwrap_or_else(|err| write_failed(err));
+
+    if opts.stats {
+        print_walk_stats(&stats);
+    }
+
+    if stats.skipped_unreadable > 0 {
+        err!("{} unreadable item(s) were skipped", stats.skipped_unreadable);
+    }
+
+    if !errors.is_empty() || checksum_errors > 0 {
+        err!(
+            "{} item(s) could not be read and were skipped ({})",
+            errors.len() + checksum_errors,
+            errors_summary(&errors, checksum_errors)
+        );
+
+        std::process::exit(6);
+    }
+
+    debug!("Done!");
+}
+
+/// Report the entries of a previous files list or snapshot which were removed, to STDERR or to the removed entries' file
+fn report_removed(opts: &Opts, removed: &[Vec<u8>]) {
+    let path = match &opts.removed_output {
+        Some(path) => path,
+        None => {
+            for entry in removed {
+                eprintln!("REMOVED {}", String::from_utf8_lossy(entry));
+            }
+
+            return;
+        }
+    };
+
+    if opts.dry_run {
+        return;
+    }
+
+    let separator: &[u8] = if opts.print0 { b"\0" } else { b"\n" };
+
+    let result = fs::File::create(path).and_then(|file| {
+        let mut out = BufWriter::new(file);
+
+        for entry in removed {
+            out.write_all(entry)?;
+            out.write_all(separator)?;
+        }
+
+        out.flush()
+    });
+
+    result.unwrap_or_else(|err| fail!(exit 5, "Failed to write removed entries file: {}", err));
+}
+
+/// Build the walker's configuration from the command-line options
+fn walker_config(opts: &Opts, show_progress: bool) -> WalkerConfig {
+    WalkerConfig {
+        rules: make_rules(&opts.rules),
+        output_paths: if opts.absolute { PathStyle::Absolute } else { PathStyle::RelativeToSource },
+        symlink_mode: if opts.follow_symlinks {
+            SymlinkMode::Follow
+        } else if opts.keep_symlinks {
+            SymlinkMode::ListAsEntry
+        } else {
+            SymlinkMode::Skip
+        },
+        symlink_escape: match opts.symlink_escape.as_deref() {
+            Some("allow") => SymlinkEscapePolicy::Allow,
+            Some("error") => SymlinkEscapePolicy::Error,
+            _ => SymlinkEscapePolicy::Skip,
+        },
+        drop_empty_dirs: opts.drop_empty_dirs,
+        output_types: if opts.files_only || opts.dirs_only || opts.symlinks_only {
+            WalkerItemTypes::of(
+                [
+                    (opts.files_only, WalkerItemType::File),
+                    (opts.dirs_only, WalkerItemType::Directory),
+                    (opts.symlinks_only, WalkerItemType::Symlink),
+                ]
+                .iter()
+                .filter(|(enabled, _)| *enabled)
+                .map(|(_, item_type)| *item_type),
+            )
+        } else {
+            WalkerItemTypes::all()
+        },
+        emit_directories: if opts.list_dirs { DirEmitPolicy::All } else { DirEmitPolicy::OnlyEmpty },
+        max_depth: opts.max_depth,
+        max_items: opts.max_items,
+        fail_if_empty: opts.fail_if_empty,
+        include_special_files: opts.include_special_files,
+        min_file_size: opts.min_size,
+        max_file_size: opts.max_size,
+        modified_after: opts.newer_than,
+        modified_before: opts.older_than,
+        skip_unreadable: opts.skip_unreadable,
+        dedup_hard_links: opts.dedup_hard_links,
+        sort_entries: opts.sort_entries,
+        on_error: if opts.keep_going { ErrorPolicy::Collect } else { ErrorPolicy::Abort },
+        time_rules: opts.rule_stats || opts.verbose || opts.benchmark_rules,
+        explain: opts.explain,
+        progress: if show_progress { Some(progress::callback()) } else { None },
+        ..WalkerConfig::default()
+    }
+}
+
+/// Parse the command-line options, merged with the configuration file's ones if provided
+fn parse_opts(cli_args: Vec<OsString>) -> Opts {
+    let mut args = cli_args.clone();
+
+    let cli = config_file::cli_matches(&Opts::into_app(), &cli_args);
+    let config = cli.as_ref().and_then(|cli| Some((PathBuf::from(cli.value_of_os("config")?), cli)));
+
+    if let Some((path, cli)) = config {
+        let content = fs::read_to_string(&path).unwrap_or_else(|err| fail!(exit 11, "Failed to read configuration file: {}", err));
+
+        let (file_args, sources) = config_file::config_args(&Opts::into_app(), &content, cli)
+            .unwrap_or_else(|err| fail!(exit 11, "Invalid configuration file {}: {}", path.display(), err));
+
+        // Options from the file come first, and source directories last (as positional arguments)
+        args = std::iter::once(cli_args[0].clone())
+            .chain(file_args)
+            .chain(cli_args.into_iter().skip(1))
+            .collect();
+
+        if !sources.is_empty() {
+            args.push("--".into());
+            args.extend(sources);
+        }
+    }
+
+    let matches = Opts::into_app().get_matches_from(args);
+
+    if matches.is_present("print-config") {
+        print!("{}", config_file::print_config(&Opts::into_app(), &matches));
+        std::process::exit(0);
+    }
+
+    Opts::from_arg_matches(&matches)
+}
+
+/// Summarize the errors of a partial walk by kind (e.g. `2 permission errors, 1 broken symlinks`)
+fn errors_summary(errors: &[WalkerErr], checksum_errors: usize) -> String {
+    let mut counts = [0; 5];
+
+    for err in errors {
+        let (path, io_err) = match err {
+            WalkerErr::FailedToCanonicalize(path, err)
+            | WalkerErr::FailedToWalkDir(path, err)
+            | WalkerErr::FailedToReadDirEntry(path, err)
+            | WalkerErr::FailedToReadSymlinkTarget(path, err)
+            | WalkerErr::FailedToGetItemMetadata(path, err) => (path, err),
+            _ => {
+                counts[3] += 1;
+                continue;
+            }
+        };
+
+        let is_symlink = || path.symlink_metadata().is_ok_and(|metadata| metadata.file_type().is_symlink());
+
+        match io_err.kind() {
+            io::ErrorKind::PermissionDenied => counts[0] += 1,
+            io::ErrorKind::NotFound if is_symlink() => counts[1] += 1,
+            io::ErrorKind::NotFound => counts[2] += 1,
+            _ => counts[3] += 1,
+        }
+    }
+
+    counts[4] = checksum_errors;
+
+    let kinds = ["permission errors", "broken symlinks", "vanished items", "other errors", "checksum errors"];
+
+    counts
+        .iter()
+        .zip(kinds.iter())
+        .filter(|(count, _)| **count > 0)
+        .map(|(count, kind)| format!("{} {}", count, kind))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Compare two items of the files list by their size or modification time (see the `--sort-by` option), then by their name
+///
+/// Larger and more recently modified items come first, and items whose metadata is missing come last.
+fn compare_items<N: Ord>(
+    sort_by: &str,
+    (a_name, a_key): (N, Option<&(u64, Option<SystemTime>)>),
+    (b_name, b_key): (N, Option<&(u64, Option<SystemTime>)>),
+) -> Ordering {
+    let (a_key, b_key) = match (a_key, b_key) {
+        (Some(a_key), Some(b_key)) => (a_key, b_key),
+        (Some(_), None) => return Ordering::Less,
+        (None, Some(_)) => return Ordering::Greater,
+        (None, None) => return a_name.cmp(&b_name),
+    };
+
+    let ordering = match sort_by {
+        "size" => b_key.0.cmp(&a_key.0),
+        "mtime" => b_key.1.cmp(&a_key.1),
+        _ => Ordering::Equal,
+    };
+
+    ordering.then_with(|| a_name.cmp(&b_name))
+}
+
+/// Append a trailing slash to a directory's path (the source directory itself is left as is when paths are relative)
+fn mark_dir(path: PathBuf) -> PathBuf {
+    if path.as_os_str().is_empty() {
+        return path;
+    }
+
+    let mut path = path.into_os_string();
+    path.push("/");
+    path.into()
+}
+
+/// Assign a prefix to each (canonicalized) source directory, from the `--prefix` option
+///
+/// When a single prefix (or none) is provided, it is used for all source directories, except when listing relative paths from
+/// multiple source directories: each of them then gets its name followed by a slash as a prefix, so their items don't collide.
+fn source_prefixes(opts: &Opts, sources: &[PathBuf], relative_to: bool) -> HashMap<PathBuf, String> {
+    let mut prefixes = HashMap::new();
+
+    if opts.prefix.len() > 1 {
+        if opts.prefix.len() != sources.len() {
+            fail!(exit 2, "Got {} prefixes for {} source directories (expected one per source directory)", opts.prefix.len(), sources.len());
+        }
+
+        if relative_to {
+            fail!(exit 2, "A different prefix for each source directory cannot be used with --relative-to");
         }
 
-        let mut path_str = match path.to_str() {
-            Some(str) => str.to_string(),
-            None => {
-                let lossy_path = path.display().to_string();
+        for (source, prefix) in sources.iter().zip(&opts.prefix) {
+            // Identical source directories are only walked once, with the first prefix they were provided with
+            prefixes.entry(source.clone()).or_insert_with(|| prefix.clone());
+        }
+
+        return prefixes;
+    }
+
+    if !opts.prefix.is_empty() || opts.absolute || relative_to {
+        return prefixes;
+    }
+
+    // Source directories located inside another one are walked as part of it, so they don't need a prefix
+    let outer: Vec<_> = sources
+        .iter()
+        .enumerate()
+        .filter(|(index, source)| {
+            !sources
+                .iter()
+                .enumerate()
+                .any(|(other_index, other)| source.starts_with(other) && (*source != other || other_index < *index))
+        })
+        .map(|(_, source)| source)
+        .collect();
+
+    if outer.len() < 2 {
+        return prefixes;
+    }
+
+    let mut by_prefix = HashMap::<String, &PathBuf>::new();
+
+    for source in outer {
+        let name = source.file_name().map(|name| name.to_string_lossy()).unwrap_or_default();
+        let prefix = format!("{}/", name);
+
+        if let Some(other) = by_prefix.insert(prefix.clone(), source) {
+            fail!(
+                exit 2,
+                "Source directories {} and {} have the same name, use --prefix once per source directory to set their prefixes",
+                other.display(),
+                source.display()
+            );
+        }
+
+        prefixes.insert(source.clone(), prefix);
+    }
 
-                if opts.allow_non_utf8_filenames {
-                    debug!("> Converting invalid UTF-8 item to lossy item name: {}", lossy_path);
-                    lossy_path
-                } else if opts.ignore_non_utf8_filenames {
-                    err!("> Found invalid UTF-8 name: {}", lossy_path);
+    prefixes
+}
+
+/// Make a path relative to its source directory relative to a base directory containing the source instead
+fn relative_to_base(base: &Path, source: &Path, path: PathBuf) -> PathBuf {
+    let source = source
+        .strip_prefix(base)
+        .expect("Internal error: source directory is not inside base directory");
+
+    if path.as_os_str().is_empty() {
+        source.to_path_buf()
+    } else {
+        source.join(path)
+    }
+}
+
+/// Exit after failing to build the files list
+fn walk_failed(err: WalkerErr) -> ! {
+    err.log(LoggerLevel::Failure, "Failed to build files list: ");
+
+    std::process::exit(match err {
+        WalkerErr::TooManyItems { .. } => 7,
+        WalkerErr::EmptyResult { .. } => 8,
+        _ => 3,
+    })
+}
+
+/// Exit after failing to write the files list
+fn write_failed(err: io::Error) -> ! {
+    fail!(exit 5, "Failed to write files list: {}", err)
+}
+
+/// Build a snapshot of the files list, reading the metadata of each item again (and computing checksums if asked to)
+fn make_snapshot(opts: &Opts, items: &RootsItems) -> Snapshot {
+    let mut snapshot = Snapshot::new();
+    let mut buffer = vec![0; checksum::BUFFER_SIZE];
+
+    for (source, items) in items {
+        for path in items {
+            // Relative paths are relative to the source directory
+            let full_path = source.join(path);
+
+            let metadata = match full_path.symlink_metadata() {
+                Ok(metadata) => metadata,
+                Err(err) => {
+                    err!("Failed to get metadata of item, excluding it from the snapshot: {} ({})", path.display(), err);
                     continue;
-                } else {
-                    fail!(exit 4, "> Found invalid UTF-8 name: {}", lossy_path);
+                }
+            };
+
+            let mut entry = SnapshotEntry::from_metadata(&metadata);
+
+            if let (Some(algorithm), true) = (&opts.checksum, metadata.is_file()) {
+                match checksum::file_checksum(algorithm, &full_path, &mut buffer) {
+                    Ok(checksum) => entry.checksum = Some(checksum),
+                    Err(err) => err!("Failed to compute checksum of item for the snapshot: {} ({})", path.display(), err),
                 }
             }
-        };
 
-        if let Some(prefix) = &opts.prefix {
-            path_str = format!("{}{}", prefix, path_str);
+            snapshot.entries.insert(snapshot_key(opts, source, path), entry);
         }
+    }
 
-        out.push(path_str);
+    snapshot
+}
+
+/// Get the path an item is recorded with in snapshots (prefixed when each source directory has its own prefix, so they don't collide)
+fn snapshot_key(opts: &Opts, source: &Path, path: &Path) -> PathBuf {
+    if opts.source_prefixes.is_empty() {
+        return path.to_path_buf();
     }
 
-    if !opts.no_sort {
-        out.sort();
+    let mut key = OsString::from(opts.prefix_of(source));
+    key.push(path);
+    key.into()
+}
+
+/// Compute the total size of the files of a files list, reading the metadata of each item again
+fn files_size(items: &RootsItems) -> u64 {
+    items
+        .iter()
+        .flat_map(|(source, items)| items.iter().map(move |path| source.join(path)))
+        .filter_map(|path| path.symlink_metadata().ok())
+        .filter(|metadata| metadata.is_file())
+        .map(|metadata| metadata.len())
+        .sum()
+}
+
+/// Display a summary of the files list (to STDERR)
+fn print_walk_stats(stats: &WalkStats) {
+    eprintln!("Files:         {}", stats.files);
+    eprintln!("Directories:   {}", stats.directories);
+    eprintln!("Symlinks:      {}", stats.symlinks);
+
+    if stats.special_files > 0 {
+        eprintln!("Special files: {}", stats.special_files);
     }
 
-    let out = out.join("\n");
+    eprintln!("Total size:    {} ({} bytes)", format_size(stats.total_size), stats.total_size);
+    eprintln!("Excluded:      {}", stats.excluded);
 
-    // Output the result
-    if !opts.dry_run {
-        match &opts.output {
-            Some(dest) => fs::write(dest, out).unwrap_or_else(|err| fail!(exit 5, "Failed to write output file: {}", err)),
-            None => println!("{}", out),
+    if !stats.largest_files.is_empty() {
+        eprintln!("Largest files:");
+
+        for (path, size) in &stats.largest_files {
+            eprintln!("  {:>10}  {}", format_size(*size), path.display());
         }
     }
+}
 
-    debug!("Done!");
+/// Format a size in bytes to a human-readable string (e.g. `1.50 MiB`)
+fn format_size(size: u64) -> String {
+    const UNITS: [&str; 5] = ["KiB", "MiB", "GiB", "TiB", "PiB"];
+
+    if size < 1024 {
+        return format!("{} B", size);
+    }
+
+    let mut value = size as f64 / 1024.0;
+    let mut unit = 0;
+
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+
+    format!("{:.2} {}", value, UNITS[unit])
+}
+
+/// Display statistics about each rule as a table (to STDERR)
+fn print_rule_stats(stats: &WalkStats) {
+    let names: Vec<_> = stats.rules.iter().map(rule_label).collect();
+
+    let width = names.iter().map(|name| name.chars().count()).max().unwrap_or(0).max(4);
+
+    eprintln!(
+        "{:<width$}  {:>8}  {:>8}  {:>8}  {:>10}",
+        "Rule",
+        "Matched",
+        "Excluded",
+        "Mapped",
+        "Time",
+        width = width
+    );
+
+    for (rule, name) in stats.rules.iter().zip(names) {
+        let duration = rule
+            .duration
+            .map(|duration| format!("{:.3}ms", duration.as_secs_f64() * 1000.0))
+            .unwrap_or_default();
+
+        eprintln!(
+            "{:<width$}  {:>8}  {:>8}  {:>8}  {:>10}",
+            name,
+            rule.matched,
+            rule.excluded,
+            rule.mapped,
+            duration,
+            width = width
+        );
+    }
+}
+
+/// Display the time spent in each rule as a table sorted from the slowest rule to the fastest (to STDERR)
+fn print_rules_benchmark(stats: &WalkStats) {
+    let mut rules: Vec<_> = stats.rules.iter().collect();
+    rules.sort_by_key(|rule| Reverse(rule.duration));
+
+    let names: Vec<_> = rules.iter().map(|rule| rule_label(rule)).collect();
+    let width = names.iter().map(|name| name.chars().count()).max().unwrap_or(0).max(4);
+
+    eprintln!(
+        "{:<width$}  {:>10}  {:>8}  {:>12}  {:>12}",
+        "Rule",
+        "Calls",
+        "Matched",
+        "Total",
+        "Average",
+        width = width
+    );
+
+    for (rule, name) in rules.into_iter().zip(names) {
+        let duration = rule.duration.unwrap_or_default();
+
+        let average = match rule.evaluated {
+            0 => String::from("-"),
+            evaluated => format!("{:.3}µs", duration.as_secs_f64() * 1_000_000.0 / evaluated as f64),
+        };
+
+        eprintln!(
+            "{:<width$}  {:>10}  {:>8}  {:>12}  {:>12}",
+            name,
+            rule.evaluated,
+            rule.matched,
+            format!("{:.3}ms", duration.as_secs_f64() * 1000.0),
+            average,
+            width = width
+        );
+    }
+}
+
+/// Get the name of a rule as displayed in the statistics, with its description if any
+fn rule_label(rule: &RuleStats) -> String {
+    match &rule.description {
+        Some(description) => format!("{} ({})", rule.name, description),
+        None => rule.name.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn errors_summary_buckets() {
+        let dir = tempfile::tempdir().unwrap();
+        let error = |kind: io::ErrorKind| io::Error::new(kind, "error");
+
+        let mut errors = vec![
+            WalkerErr::FailedToWalkDir(dir.path().join("locked"), error(io::ErrorKind::PermissionDenied)),
+            WalkerErr::FailedToReadDirEntry(dir.path().join("locked-too"), error(io::ErrorKind::PermissionDenied)),
+            WalkerErr::FailedToGetItemMetadata(dir.path().join("vanished"), error(io::ErrorKind::NotFound)),
+            WalkerErr::FailedToGetItemMetadata(dir.path().join("busy"), error(io::ErrorKind::Other)),
+        ];
+
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink("missing", dir.path().join("broken")).unwrap();
+            errors.push(WalkerErr::FailedToCanonicalize(dir.path().join("broken"), error(io::ErrorKind::NotFound)));
+        }
+
+        let expected = if cfg!(unix) {
+            "2 permission errors, 1 broken symlinks, 1 vanished items, 1 other errors, 3 checksum errors"
+        } else {
+            "2 permission errors, 1 vanished items, 1 other errors, 3 checksum errors"
+        };
+
+        assert_eq!(errors_summary(&errors, 3), expected);
+        assert_eq!(errors_summary(&errors[2..3], 0), "1 vanished items");
+        assert_eq!(errors_summary(&[], 0), "");
+    }
+
+    #[test]
+    fn compare_items_by_key() {
+        let mtime = |secs| Some(SystemTime::UNIX_EPOCH + Duration::from_secs(secs));
+        let (small, large) = ((10, mtime(200)), (20, mtime(100)));
+
+        assert_eq!(compare_items("size", ("a", Some(&large)), ("b", Some(&small))), Ordering::Less);
+        assert_eq!(compare_items("mtime", ("a", Some(&large)), ("b", Some(&small))), Ordering::Greater);
+
+        // Ties are broken by name
+        assert_eq!(compare_items("size", ("b", Some(&small)), ("a", Some(&(10, None)))), Ordering::Greater);
+        assert_eq!(compare_items("mtime", ("a", Some(&small)), ("b", Some(&(30, mtime(200))))), Ordering::Less);
+
+        // Items without a modification time come after the other ones
+        assert_eq!(compare_items("mtime", ("a", Some(&(10, None))), ("b", Some(&small))), Ordering::Greater);
+
+        // Items whose metadata is missing come last
+        assert_eq!(compare_items("size", ("a", None), ("b", Some(&small))), Ordering::Greater);
+        assert_eq!(compare_items("size", ("b", Some(&small)), ("a", None)), Ordering::Less);
+        assert_eq!(compare_items("size", ("b", None), ("a", None)), Ordering::Greater);
+    }
 }
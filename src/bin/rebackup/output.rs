@@ -0,0 +1,727 @@
+//! Output formats of the files list
+
+use crate::checksum::{file_checksum, BUFFER_SIZE};
+use crate::Opts;
+use rebackup::{debug, err, fail};
+use serde::Serialize;
+use serde_json::value::RawValue;
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::UNIX_EPOCH;
+
+/// Number of items after which the output is flushed
+const FLUSH_INTERVAL: usize = 1000;
+
+/// Number of items whose checksum is computed at once
+const CHECKSUM_BATCH: usize = 1024;
+
+/// Output format of the files list (see the `--format` option)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// One path per line
+    Lines,
+
+    /// A JSON array of objects
+    Json,
+
+    /// One JSON object per line
+    JsonLines,
+
+    /// Comma-separated values, with one row per item
+    Csv,
+
+    /// Tab-separated values, with one row per item
+    Tsv,
+
+    /// A tar archive containing the items
+    #[cfg(feature = "tar-output")]
+    Tar,
+}
+
+/// Destination the files list is written to
+pub trait Destination: Write {
+    /// Terminate the output once the whole files list was written
+    fn finish(&mut self) -> io::Result<()> {
+        self.flush()
+    }
+}
+
+impl Destination for fs::File {}
+impl Destination for io::Stdout {}
+impl Destination for io::Sink {}
+
+impl Destination for Box<dyn Destination> {
+    fn finish(&mut self) -> io::Result<()> {
+        (**self).finish()
+    }
+}
+
+/// Writer of the files list, which writes items as soon as they are provided
+pub struct ListWriter<'a> {
+    opts: &'a Opts,
+    format: OutputFormat,
+    out: BufWriter<Box<dyn Destination>>,
+    trailing_newline: bool,
+    written: usize,
+    checksum_jobs: usize,
+    pending: Vec<(PathBuf, PathBuf)>,
+    checksum_errors: usize,
+    #[cfg(feature = "tar-output")]
+    archive: Option<::tar::Builder<BufWriter<Box<dyn Destination>>>>,
+}
+
+/// Checksum of an item (see the `--checksum` option)
+enum Checksum {
+    Digest(String),
+    NotAFile,
+    Failed(io::Error),
+}
+
+impl<'a> ListWriter<'a> {
+    /// Create a writer (a trailing line break is added at the end of the list if asked to)
+    pub fn new(opts: &'a Opts, out: Box<dyn Destination>, trailing_newline: bool) -> Self {
+        let format = match opts.format.as_deref() {
+            Some("json") => OutputFormat::Json,
+            Some("jsonl") => OutputFormat::JsonLines,
+            Some("csv") => OutputFormat::Csv,
+            Some("tsv") => OutputFormat::Tsv,
+            #[cfg(feature = "tar-output")]
+            Some("tar") => OutputFormat::Tar,
+            _ => OutputFormat::Lines,
+        };
+
+        // Rows of the tables already end with a line break
+        let trailing_newline = trailing_newline && !matches!(format, OutputFormat::Csv | OutputFormat::Tsv);
+
+        #[cfg(feature = "tar-output")]
+        let trailing_newline = trailing_newline && format != OutputFormat::Tar;
+
+        // Archives are written through their builder, which gives the output back once the archive is terminated
+        #[cfg(feature = "tar-output")]
+        let (out, archive): (Box<dyn Destination>, _) = match format {
+            OutputFormat::Tar => (Box::new(io::sink()), Some(::tar::Builder::new(BufWriter::new(out)))),
+            _ => (out, None),
+        };
+
+        Self {
+            opts,
+            format,
+            out: BufWriter::new(out),
+            trailing_newline: trailing_newline && !opts.print0,
+            written: 0,
+            checksum_jobs: match opts.checksum_jobs {
+                Some(0) => thread::available_parallelism().map(|jobs| jobs.get()).unwrap_or(1),
+                Some(jobs) => jobs,
+                None => 1,
+            },
+            pending: vec![],
+            checksum_errors: 0,
+            #[cfg(feature = "tar-output")]
+            archive,
+        }
+    }
+
+    /// Write an item of the files list (relative paths are relative to the provided source directory)
+    ///
+    /// When checksums are requested, items are written by batches once their checksum is computed.
+    pub fn write_item(&mut self, source: &Path, path: &Path) -> io::Result<()> {
+        if self.opts.checksum.is_none() {
+            return self.write_entry(path, source, None);
+        }
+
+        self.pending.push((source.to_path_buf(), path.to_path_buf()));
+
+        if self.pending.len() >= CHECKSUM_BATCH {
+            self.write_pending()?;
+        }
+
+        Ok(())
+    }
+
+    /// Terminate the files list, and get the number of items that were skipped as their checksum could not be computed
+    pub fn finish(mut self) -> io::Result<usize> {
+        self.write_pending()?;
+
+        if self.format == OutputFormat::Json {
+            self.out.write_all(if self.written == 0 { b"[]" } else { b"\n]" })?;
+        }
+
+        if self.written == 0 {
+            self.write_table_header()?;
+        }
+
+        #[cfg(feature = "tar-output")]
+        if let Some(archive) = self.archive.take() {
+            self.out = archive.into_inner()?;
+        }
+
+        if self.trailing_newline {
+            self.out.write_all(b"\n")?;
+        }
+
+        self.out.flush()?;
+        self.out.get_mut().finish()?;
+
+        Ok(self.checksum_errors)
+    }
+
+    /// (Internal) Compute the checksum of the pending items, and write them
+    fn write_pending(&mut self) -> io::Result<()> {
+        let pending = std::mem::take(&mut self.pending);
+
+        let algorithm = self.opts.checksum.as_deref().unwrap_or_default();
+
+        for ((source, path), checksum) in pending.iter().zip(compute_checksums(algorithm, &pending, self.checksum_jobs)) {
+            let digest = match checksum {
+                Checksum::Digest(digest) => Some(digest),
+                Checksum::NotAFile if self.opts.checksum_skip_non_files => continue,
+                Checksum::NotAFile => None,
+                Checksum::Failed(err) if self.opts.keep_going || self.opts.skip_unreadable => {
+                    err!("Failed to compute checksum of item, skipping it: {} ({})", path.display(), err);
+                    self.checksum_errors += 1;
+                    continue;
+                }
+                Checksum::Failed(err) => fail!(exit 3, "Failed to compute checksum of item: {} ({})", path.display(), err),
+            };
+
+            self.write_entry(path, source, Some(digest.as_deref()))?;
+        }
+
+        Ok(())
+    }
+
+    /// (Internal) Write an item of the files list, with its checksum if requested (`Some(None)` for items that are not files)
+    fn write_entry(&mut self, path: &Path, source: &Path, checksum: Option<Option<&str>>) -> io::Result<()> {
+        let entry = match self.format {
+            #[cfg(feature = "tar-output")]
+            OutputFormat::Tar => return self.write_archive_entry(path, source),
+
+            OutputFormat::Csv | OutputFormat::Tsv => match self.table_row(path, source, checksum) {
+                Some(row) => row,
+                None => return Ok(()),
+            },
+
+            OutputFormat::Lines => match self.line_entry(path, source) {
+                // Same format as the 'sha256sum' command
+                Some(entry) => match checksum {
+                    Some(checksum) => [checksum.unwrap_or("-").as_bytes(), b"  ", &entry].concat(),
+                    None => entry,
+                },
+                None => return Ok(()),
+            },
+
+            OutputFormat::Json | OutputFormat::JsonLines => match source.join(path).symlink_metadata() {
+                Ok(metadata) => {
+                    let checksum = checksum.map(|checksum| (self.opts.checksum.as_deref().unwrap_or_default(), checksum));
+                    json_item(
+                        &normalize_path(self.opts, path),
+                        (self.opts.prefix_of(source), self.opts.suffix()),
+                        &metadata,
+                        self.opts.metadata,
+                        checksum,
+                    )
+                    .into_bytes()
+                }
+                Err(err) => {
+                    err!("Failed to get metadata of item, excluding it from the output: {} ({})", path.display(), err);
+                    return Ok(());
+                }
+            },
+        };
+
+        if self.written == 0 {
+            self.write_table_header()?;
+        }
+
+        let separator: &[u8] = match (self.format, self.written) {
+            (OutputFormat::Json, 0) => b"[\n  ",
+            (OutputFormat::Csv | OutputFormat::Tsv, _) => b"",
+            (_, 0) => b"",
+            (OutputFormat::Json, _) => b",\n  ",
+            (OutputFormat::Lines, _) if self.opts.print0 => b"\0",
+            (_, _) => b"\n",
+        };
+
+        self.out.write_all(separator)?;
+        self.out.write_all(&entry)?;
+        self.written += 1;
+
+        if self.written.is_multiple_of(FLUSH_INTERVAL) {
+            self.out.flush()?;
+        }
+
+        Ok(())
+    }
+
+    /// (Internal) Append an item to the archive, with its path prefixed and made relative (like the 'tar' command does)
+    #[cfg(feature = "tar-output")]
+    fn write_archive_entry(&mut self, path: &Path, source: &Path) -> io::Result<()> {
+        let path_name = normalize_path(self.opts, path);
+
+        let mut name = std::ffi::OsString::from(self.opts.prefix_of(source));
+        name.push(path_name.strip_prefix("/").unwrap_or(&path_name));
+        name.push(self.opts.suffix());
+
+        let archive = self.archive.as_mut().expect("Internal error: archive builder is missing");
+
+        if crate::tar::append_item(archive, Path::new(&name), &source.join(path))? {
+            self.written += 1;
+        }
+
+        Ok(())
+    }
+
+    /// (Internal) Write the header row of the table formats, unless disabled (does nothing for other formats)
+    fn write_table_header(&mut self) -> io::Result<()> {
+        let delimiter = match self.format {
+            OutputFormat::Csv => ',',
+            OutputFormat::Tsv => '\t',
+            _ => return Ok(()),
+        };
+
+        if self.opts.no_header {
+            return Ok(());
+        }
+
+        let mut columns = vec!["path", "type", "size", "mtime", "target"];
+
+        if let Some(algorithm) = &self.opts.checksum {
+            columns.push(algorithm);
+        }
+
+        writeln!(self.out, "{}", columns.join(&delimiter.to_string()))
+    }
+
+    /// (Internal) Convert an item to a row of the table formats, with its checksum if requested (`None` if the item must be skipped)
+    fn table_row(&self, path: &Path, source: &Path, checksum: Option<Option<&str>>) -> Option<Vec<u8>> {
+        let delimiter = if self.format == OutputFormat::Tsv { b'\t' } else { b',' };
+
+        let path_bytes = self.path_bytes(path, source)?;
+
+        // Relative paths are relative to the source directory
+        let full_path = source.join(path);
+
+        let metadata = match full_path.symlink_metadata() {
+            Ok(metadata) => metadata,
+            Err(err) => {
+                err!("Failed to get metadata of item, excluding it from the output: {} ({})", path.display(), err);
+                return None;
+            }
+        };
+
+        let target = match metadata.file_type().is_symlink() {
+            true => match fs::read_link(&full_path) {
+                Ok(target) => raw_path_bytes(&target),
+                Err(err) => {
+                    err!("Failed to read target of symbolic link: {} ({})", path.display(), err);
+                    vec![]
+                }
+            },
+            false => vec![],
+        };
+
+        let mtime = match metadata.modified().ok().and_then(|modified| modified.duration_since(UNIX_EPOCH).ok()) {
+            Some(mtime) => format!("{}.{:09}", mtime.as_secs(), mtime.subsec_nanos()),
+            None => String::new(),
+        };
+
+        let mut cells = vec![
+            path_bytes,
+            item_type_name(&metadata).as_bytes().to_vec(),
+            metadata.len().to_string().into_bytes(),
+            mtime.into_bytes(),
+            target,
+        ];
+
+        if let Some(checksum) = checksum {
+            cells.push(checksum.unwrap_or("").as_bytes().to_vec());
+        }
+
+        let mut row = vec![];
+
+        for (i, cell) in cells.iter().enumerate() {
+            if i > 0 {
+                row.push(delimiter);
+            }
+
+            push_table_cell(&mut row, cell, delimiter);
+        }
+
+        row.push(b'\n');
+        Some(row)
+    }
+
+    /// (Internal) Convert an item's path to a line of the files list (`None` if the item must be skipped)
+    fn line_entry(&self, path: &Path, source: &Path) -> Option<Vec<u8>> {
+        let path_str = self.path_bytes(path, source)?;
+
+        // Line breaks would make the files list ambiguous
+        if !self.opts.print0 && path_str.iter().any(|byte| matches!(byte, b'\n' | b'\r')) {
+            fail!(
+                exit 4,
+                "> Found name containing a line break (use --print0 to separate items with NUL characters): {:?}",
+                String::from_utf8_lossy(&path_str)
+            );
+        }
+
+        Some(path_str)
+    }
+
+    /// (Internal) Convert an item's path to the bytes written in the files list, with its prefix and suffix (`None` if the item must be skipped)
+    fn path_bytes(&self, path: &Path, source: &Path) -> Option<Vec<u8>> {
+        let opts = self.opts;
+        let path = &*normalize_path(opts, path);
+
+        let path_str = match path.to_str() {
+            Some(str) => str.as_bytes().to_vec(),
+            None if opts.raw_paths => raw_path_bytes(path),
+            None => {
+                let lossy_path = path.display().to_string();
+
+                if opts.allow_non_utf8_filenames {
+                    debug!("> Converting invalid UTF-8 item to lossy item name: {}", lossy_path);
+                    lossy_path.into_bytes()
+                } else if opts.ignore_non_utf8_filenames {
+                    err!("> Found invalid UTF-8 name: {}", lossy_path);
+                    return None;
+                } else {
+                    fail!(exit 4, "> Found invalid UTF-8 name: {}", lossy_path);
+                }
+            }
+        };
+
+        Some([opts.prefix_of(source).as_bytes(), &path_str, opts.suffix().as_bytes()].concat())
+    }
+}
+
+/// (Internal) Compute the checksum of items using the provided number of threads (the results are in the same order)
+fn compute_checksums(algorithm: &str, items: &[(PathBuf, PathBuf)], jobs: usize) -> Vec<Checksum> {
+    let checksum = |(source, path): &(PathBuf, PathBuf), buffer: &mut [u8]| {
+        // Relative paths are relative to the source directory
+        let path = source.join(path);
+
+        match path.symlink_metadata() {
+            Ok(metadata) if metadata.is_file() => match file_checksum(algorithm, &path, buffer) {
+                Ok(digest) => Checksum::Digest(digest),
+                Err(err) => Checksum::Failed(err),
+            },
+            Ok(_) => Checksum::NotAFile,
+            Err(err) => Checksum::Failed(err),
+        }
+    };
+
+    if jobs <= 1 || items.len() <= 1 {
+        let mut buffer = vec![0; BUFFER_SIZE];
+        return items.iter().map(|item| checksum(item, &mut buffer)).collect();
+    }
+
+    let next = AtomicUsize::new(0);
+
+    let mut results: Vec<_> = thread::scope(|scope| {
+        let workers: Vec<_> = (0..jobs.min(items.len()))
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut buffer = vec![0; BUFFER_SIZE];
+                    let mut results = vec![];
+
+                    loop {
+                        let index = next.fetch_add(1, Ordering::SeqCst);
+
+                        match items.get(index) {
+                            Some(item) => results.push((index, checksum(item, &mut buffer))),
+                            None => break results,
+                        }
+                    }
+                })
+            })
+            .collect();
+
+        workers
+            .into_iter()
+            .flat_map(|worker| worker.join().unwrap_or_else(|err| std::panic::resume_unwind(err)))
+            .collect()
+    });
+
+    results.sort_by_key(|(index, _)| *index);
+    results.into_iter().map(|(_, checksum)| checksum).collect()
+}
+
+/// An item of the files list in the JSON output formats (see [`json_item`])
+#[derive(Serialize)]
+struct JsonItem<'a> {
+    /// Item's path (lossy if it is not valid UTF-8)
+    path: String,
+
+    /// Raw bytes of the item's path, if it is not valid UTF-8 (on Unix platforms)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    path_bytes: Option<Vec<u8>>,
+
+    /// Item's type (see [`item_type_name`])
+    #[serde(rename = "type")]
+    item_type: &'static str,
+
+    /// Item's size in bytes, with `--metadata`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    size: Option<u64>,
+
+    /// Item's modification time as a UNIX timestamp with nanoseconds, with `--metadata` (`null` if it isn't available)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    mtime: Option<Option<Box<RawValue>>>,
+
+    /// Item's checksum, in a field named after the algorithm
+    #[serde(flatten)]
+    checksum: BTreeMap<&'a str, Option<&'a str>>,
+}
+
+/// Format an item as a JSON object (see the `--format` option)
+///
+/// Paths that are not valid UTF-8 are provided lossily in the `path` field, along with their raw bytes
+/// in a `path_bytes` field (on Unix platforms). If provided, the checksum is set in a field named after its algorithm
+/// (`null` for items that are not files).
+pub fn json_item(
+    path: &Path,
+    (prefix, suffix): (&str, &str),
+    metadata: &fs::Metadata,
+    with_metadata: bool,
+    checksum: Option<(&str, Option<&str>)>,
+) -> String {
+    #[cfg(unix)]
+    let path_bytes = match path.to_str() {
+        Some(_) => None,
+        None => {
+            use std::os::unix::ffi::OsStrExt;
+            Some([prefix.as_bytes(), path.as_os_str().as_bytes(), suffix.as_bytes()].concat())
+        }
+    };
+
+    #[cfg(not(unix))]
+    let path_bytes = None;
+
+    let mtime = metadata
+        .modified()
+        .ok()
+        .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+        .map(|mtime| {
+            RawValue::from_string(format!("{}.{:09}", mtime.as_secs(), mtime.subsec_nanos())).expect("Internal error: invalid JSON modification time")
+        });
+
+    let item = JsonItem {
+        path: format!("{}{}{}", prefix, path.display(), suffix),
+        path_bytes,
+        item_type: item_type_name(metadata),
+        size: with_metadata.then_some(metadata.len()),
+        mtime: with_metadata.then_some(mtime),
+        checksum: checksum.into_iter().collect(),
+    };
+
+    serde_json::to_string(&item).expect("Internal error: failed to serialize item to JSON")
+}
+
+/// Get the name of an item's type, as written in the JSON and table formats and provided to shell filters
+pub fn item_type_name(metadata: &fs::Metadata) -> &'static str {
+    let file_type = metadata.file_type();
+
+    if file_type.is_symlink() {
+        "symlink"
+    } else if file_type.is_dir() {
+        "dir"
+    } else if file_type.is_file() {
+        "file"
+    } else {
+        "other"
+    }
+}
+
+/// (Internal) Push a cell of the table formats, quoted if it contains the delimiter, a double quote or a line break (as per RFC 4180)
+fn push_table_cell(out: &mut Vec<u8>, cell: &[u8], delimiter: u8) {
+    if !cell.iter().any(|&byte| byte == delimiter || matches!(byte, b'"' | b'\n' | b'\r')) {
+        out.extend_from_slice(cell);
+        return;
+    }
+
+    out.push(b'"');
+
+    for &byte in cell {
+        if byte == b'"' {
+            out.push(b'"');
+        }
+
+        out.push(byte);
+    }
+
+    out.push(b'"');
+}
+
+/// Normalize a path before writing it in the files list (see the `--keep-verbatim` and `--unix-separators` options)
+///
+/// This only has an effect on Windows, as verbatim prefixes and backslashes are valid in paths on other platforms.
+pub fn normalize_path<'p>(opts: &Opts, path: &'p Path) -> Cow<'p, Path> {
+    if !cfg!(windows) {
+        return Cow::Borrowed(path);
+    }
+
+    #[cfg(windows)]
+    let verbatim = matches!(path.components().next(), Some(std::path::Component::Prefix(prefix)) if prefix.kind().is_verbatim());
+
+    #[cfg(not(windows))]
+    let verbatim = false;
+
+    // Paths with unpaired surrogates are left as they are
+    match path.to_str() {
+        Some(str) => match normalize_path_str(str, verbatim && !opts.keep_verbatim, opts.unix_separators) {
+            Cow::Borrowed(_) => Cow::Borrowed(path),
+            Cow::Owned(str) => Cow::Owned(PathBuf::from(str)),
+        },
+        None => Cow::Borrowed(path),
+    }
+}
+
+/// (Internal) Remove the verbatim prefix of a Windows path if asked to (`\\?\C:\...` becomes `C:\...` and `\\?\UNC\server\...`
+/// becomes `\\server\...`), and replace its backslashes with slashes if asked to
+///
+/// Other verbatim paths (e.g. `\\?\Volume{...}\...`) have no equivalent without their prefix, so they are kept as they are.
+fn normalize_path_str(path: &str, strip_verbatim: bool, unix_separators: bool) -> Cow<'_, str> {
+    let mut path = Cow::Borrowed(path);
+
+    if strip_verbatim {
+        if let Some(unc) = path.strip_prefix(r"\\?\UNC\") {
+            path = Cow::Owned(format!(r"\\{}", unc));
+        } else if let Some(disk) = path.strip_prefix(r"\\?\") {
+            let disk_bytes = disk.as_bytes();
+
+            if disk_bytes.len() >= 2 && disk_bytes[0].is_ascii_alphabetic() && disk_bytes[1] == b':' {
+                path = Cow::Owned(disk.to_owned());
+            }
+        }
+    }
+
+    if unix_separators && path.contains('\\') {
+        path = Cow::Owned(path.replace('\\', "/"));
+    }
+
+    path
+}
+
+/// Get the raw bytes of a path (see the `--raw-paths` option)
+///
+/// On Unix platforms, these are the bytes of the path as provided by the operating system.
+/// On Windows, paths are UTF-16 strings which may contain unpaired surrogates, so they are encoded as WTF-8
+/// (like UTF-8, but unpaired surrogates are encoded as if they were regular code points).
+pub fn raw_path_bytes(path: &Path) -> Vec<u8> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStrExt;
+        path.as_os_str().as_bytes().to_vec()
+    }
+
+    #[cfg(windows)]
+    {
+        use std::os::windows::ffi::OsStrExt;
+
+        let mut out = vec![];
+
+        for c in char::decode_utf16(path.as_os_str().encode_wide()) {
+            match c {
+                Ok(c) => out.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes()),
+                Err(err) => {
+                    let surrogate = err.unpaired_surrogate();
+                    out.extend_from_slice(&[
+                        0xE0 | (surrogate >> 12) as u8,
+                        0x80 | ((surrogate >> 6) & 0x3F) as u8,
+                        0x80 | (surrogate & 0x3F) as u8,
+                    ]);
+                }
+            }
+        }
+
+        out
+    }
+
+    #[cfg(not(any(unix, windows)))]
+    {
+        path.to_string_lossy().into_owned().into_bytes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+
+    /// Format an item as JSON and parse it back
+    fn parsed_item(path: &Path, prefix: &str, with_metadata: bool, checksum: Option<(&str, Option<&str>)>) -> Value {
+        let tmp = tempfile::NamedTempFile::new().unwrap();
+        let metadata = tmp.as_file().metadata().unwrap();
+
+        serde_json::from_str(&json_item(path, (prefix, ""), &metadata, with_metadata, checksum)).unwrap()
+    }
+
+    #[test]
+    fn json_item_fields() {
+        assert_eq!(
+            parsed_item(Path::new("dir/file"), "", false, None),
+            json!({ "path": "dir/file", "type": "file" })
+        );
+
+        assert_eq!(
+            parsed_item(Path::new("file"), "backup/", false, Some(("sha256", Some("abc")))),
+            json!({ "path": "backup/file", "type": "file", "sha256": "abc" })
+        );
+
+        let item = parsed_item(Path::new("file"), "", true, None);
+        assert_eq!(item["size"], json!(0));
+        assert!(item["mtime"].as_f64().unwrap() > 0.0);
+    }
+
+    #[test]
+    fn json_item_control_characters() {
+        let path = Path::new("dir/new\nline\t\"quoted\" back\\slash \u{1}\u{7f} é");
+        let item = parsed_item(path, "", false, None);
+
+        assert_eq!(item["path"], json!(path.to_str().unwrap()));
+        assert!(item.get("path_bytes").is_none());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn json_item_non_utf8_path() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+
+        let path = Path::new(OsStr::from_bytes(b"dir/invalid\xff\xfe\nname"));
+        let item = parsed_item(path, "prefix/", false, None);
+
+        assert_eq!(item["path"], json!("prefix/dir/invalid\u{fffd}\u{fffd}\nname"));
+
+        let bytes: Vec<u8> = serde_json::from_value(item["path_bytes"].clone()).unwrap();
+        assert_eq!(bytes, b"prefix/dir/invalid\xff\xfe\nname");
+    }
+
+    #[test]
+    fn normalize_verbatim_paths() {
+        assert_eq!(normalize_path_str(r"\\?\C:\x", true, false), r"C:\x");
+        assert_eq!(normalize_path_str(r"\\?\UNC\srv\share\x", true, false), r"\\srv\share\x");
+        assert_eq!(normalize_path_str(r"\\?\UNC\srv\share", true, false), r"\\srv\share");
+
+        // Volume paths have no equivalent without their prefix
+        let volume = r"\\?\Volume{0a1b2c3d-0000-0000-0000-100000000000}\x";
+        assert!(matches!(normalize_path_str(volume, true, false), Cow::Borrowed(path) if path == volume));
+
+        assert!(matches!(normalize_path_str(r"\\?\C:\x", false, false), Cow::Borrowed(r"\\?\C:\x")));
+        assert!(matches!(normalize_path_str(r"C:\x", true, false), Cow::Borrowed(r"C:\x")));
+    }
+
+    #[test]
+    fn normalize_path_separators() {
+        assert_eq!(normalize_path_str(r"C:\dir\file", false, true), "C:/dir/file");
+        assert_eq!(normalize_path_str(r"\\?\C:\dir\file", true, true), "C:/dir/file");
+        assert_eq!(normalize_path_str(r"\\?\UNC\srv\share\file", true, true), "//srv/share/file");
+        assert_eq!(normalize_path_str(r"\\?\C:\dir", false, true), "//?/C:/dir");
+        assert!(matches!(normalize_path_str("dir/file", false, true), Cow::Borrowed("dir/file")));
+    }
+}
@@ -0,0 +1,294 @@
+//! Parsers for the command-line arguments
+
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Parse a human-readable size (e.g. `500`, `10K`, `1.5G`, `2MiB`)
+///
+/// Units are powers of 1024 and case-insensitive, and may be followed by `B` or `iB` (a lone `B` means bytes).
+pub fn parse_size(input: &str) -> Result<u64, String> {
+    let input = input.trim();
+    let lowercase = input.to_ascii_lowercase();
+
+    let number_len = lowercase.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(lowercase.len());
+    let (number, unit) = lowercase.split_at(number_len);
+
+    let exponent = match unit.trim_start() {
+        "" | "b" => 0,
+        "k" | "kb" | "kib" => 1,
+        "m" | "mb" | "mib" => 2,
+        "g" | "gb" | "gib" => 3,
+        "t" | "tb" | "tib" => 4,
+        _ => return Err(format!("invalid size unit in '{}' (expected one of: B, K, M, G, T)", input)),
+    };
+
+    if number.is_empty() {
+        return Err(format!("missing number in size '{}'", input));
+    }
+
+    let number: f64 = number.parse().map_err(|_| format!("invalid number in size '{}'", input))?;
+    let size = (number * 1024f64.powi(exponent)).round();
+
+    if size > u64::MAX as f64 {
+        return Err(format!("size '{}' is too large", input));
+    }
+
+    Ok(size as u64)
+}
+
+/// Parse a point in time, either:
+///
+/// * An RFC 3339 timestamp (e.g. `2021-03-01T12:00:00Z`, `2021-03-01 12:00:00+02:00`)
+/// * A duration relative to now (e.g. `30s`, `15m`, `36h`, `7d`, `2w`)
+/// * A path prefixed by `@` to use the modification time of a file (e.g. `@/path/to/last-list`)
+pub fn parse_time(input: &str) -> Result<SystemTime, String> {
+    let input = input.trim();
+
+    if let Some(path) = input.strip_prefix('@') {
+        return fs::metadata(path)
+            .and_then(|metadata| metadata.modified())
+            .map_err(|err| format!("failed to get modification time of '{}': {}", path, err));
+    }
+
+    if let Some(duration) = parse_duration(input) {
+        return SystemTime::now()
+            .checked_sub(duration)
+            .ok_or_else(|| format!("duration '{}' is too large", input));
+    }
+
+    parse_rfc3339(input).ok_or_else(|| {
+        format!(
+            "invalid time '{}' (expected an RFC 3339 timestamp, a duration like '7d' or a file path prefixed by '@')",
+            input
+        )
+    })
+}
+
+/// Parse a timeout, either as a number of seconds (e.g. `5`, `0.5`) or as a duration with a unit (e.g. `30s`, `2m`)
+pub fn parse_timeout(input: &str) -> Result<Duration, String> {
+    let input = input.trim();
+
+    parse_secs_or_duration(input).ok_or_else(|| format!("invalid timeout '{}' (expected a number of seconds or a duration like '30s', '2m')", input))
+}
+
+/// Parse a modification time window, as a number of seconds (e.g. `2`, `0.001`) or as a duration with a unit (e.g. `2s`)
+pub fn parse_mtime_window(input: &str) -> Result<Duration, String> {
+    let input = input.trim();
+
+    parse_secs_or_duration(input).ok_or_else(|| {
+        format!(
+            "invalid modification time window '{}' (expected a number of seconds or a duration like '2s')",
+            input
+        )
+    })
+}
+
+/// Parse an age, as a duration with a unit (e.g. `12h`, `30d`, `2w`)
+pub fn parse_age(input: &str) -> Result<Duration, String> {
+    let input = input.trim();
+
+    parse_duration(input).ok_or_else(|| format!("invalid age '{}' (expected a duration like '12h', '30d' or '2w')", input))
+}
+
+/// Parse a list of source directories (see the `--sources-from` option), separated by line breaks or by NUL characters
+///
+/// Blank entries and entries starting with `#` are skipped.
+pub fn parse_sources_list(content: &[u8], null_separated: bool) -> Vec<PathBuf> {
+    let separator = if null_separated { b'\0' } else { b'\n' };
+
+    content
+        .split(|byte| *byte == separator)
+        .map(|entry| if null_separated { entry } else { entry.strip_suffix(b"\r").unwrap_or(entry) })
+        .filter(|entry| !entry.iter().all(u8::is_ascii_whitespace) && !entry.starts_with(b"#"))
+        .map(path_from_bytes)
+        .collect()
+}
+
+/// Convert raw bytes to a path (lossily on platforms where paths are not made of bytes)
+pub fn path_from_bytes(bytes: &[u8]) -> PathBuf {
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStrExt;
+        PathBuf::from(std::ffi::OsStr::from_bytes(bytes))
+    }
+
+    #[cfg(not(unix))]
+    {
+        PathBuf::from(String::from_utf8_lossy(bytes).into_owned())
+    }
+}
+
+/// (Internal) Parse a number of seconds, or a duration with a unit
+fn parse_secs_or_duration(input: &str) -> Option<Duration> {
+    match input.parse::<f64>() {
+        Ok(secs) if secs.is_finite() && secs >= 0.0 && secs <= u64::MAX as f64 => Some(Duration::from_secs_f64(secs)),
+        Ok(_) => None,
+        Err(_) => parse_duration(input),
+    }
+}
+
+/// (Internal) Parse a duration made of a number and a unit (`s`, `m`, `h`, `d` or `w`)
+fn parse_duration(input: &str) -> Option<Duration> {
+    let number_len = input.find(|c: char| !c.is_ascii_digit() && c != '.')?;
+    let (number, unit) = input.split_at(number_len);
+
+    let unit_secs = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 3600,
+        "d" => 86400,
+        "w" => 604800,
+        _ => return None,
+    };
+
+    let number: f64 = number.parse().ok()?;
+    let secs = number * unit_secs as f64;
+
+    if secs.is_finite() && secs <= u64::MAX as f64 {
+        Some(Duration::from_secs_f64(secs))
+    } else {
+        None
+    }
+}
+
+/// (Internal) Parse an RFC 3339 timestamp (`YYYY-MM-DDTHH:MM:SS[.fraction](Z|+HH:MM|-HH:MM)`)
+fn parse_rfc3339(input: &str) -> Option<SystemTime> {
+    let bytes = input.as_bytes();
+
+    if bytes.len() < 20 || bytes[4] != b'-' || bytes[7] != b'-' || bytes[13] != b':' || bytes[16] != b':' {
+        return None;
+    }
+
+    if !matches!(bytes[10], b'T' | b't' | b' ') {
+        return None;
+    }
+
+    let field = |range: std::ops::Range<usize>| -> Option<i64> {
+        let digits = input.get(range)?;
+
+        if digits.bytes().all(|c| c.is_ascii_digit()) {
+            digits.parse().ok()
+        } else {
+            None
+        }
+    };
+
+    let (year, month, day) = (field(0..4)?, field(5..7)?, field(8..10)?);
+    let (hours, minutes, seconds) = (field(11..13)?, field(14..16)?, field(17..19)?);
+
+    if !(1..=12).contains(&month) || day < 1 || day > days_in_month(year, month) || hours > 23 || minutes > 59 || seconds > 60 {
+        return None;
+    }
+
+    let mut rest = &input[19..];
+    let mut nanos = 0;
+
+    if let Some(fraction) = rest.strip_prefix('.') {
+        let digits_len = fraction.find(|c: char| !c.is_ascii_digit()).unwrap_or(fraction.len());
+
+        if digits_len == 0 {
+            return None;
+        }
+
+        let digits = &fraction[..digits_len.min(9)];
+        nanos = digits.parse::<u32>().ok()? * 10u32.pow(9 - digits.len() as u32);
+        rest = &fraction[digits_len..];
+    }
+
+    let offset_secs = match rest.as_bytes() {
+        [b'Z'] | [b'z'] => 0,
+        [sign @ (b'+' | b'-'), _, _, b':', _, _] => {
+            let offset_hours = field(input.len() - 5..input.len() - 3)?;
+            let offset_minutes = field(input.len() - 2..input.len())?;
+
+            if offset_hours > 23 || offset_minutes > 59 {
+                return None;
+            }
+
+            let offset = offset_hours * 3600 + offset_minutes * 60;
+
+            if *sign == b'+' {
+                offset
+            } else {
+                -offset
+            }
+        }
+        _ => return None,
+    };
+
+    let timestamp = days_from_civil(year, month, day) * 86400 + hours * 3600 + minutes * 60 + seconds - offset_secs;
+
+    let time = if timestamp >= 0 {
+        UNIX_EPOCH.checked_add(Duration::from_secs(timestamp as u64))?
+    } else {
+        UNIX_EPOCH.checked_sub(Duration::from_secs(timestamp.unsigned_abs()))?
+    };
+
+    time.checked_add(Duration::from_nanos(nanos.into()))
+}
+
+/// (Internal) Get the number of days in a month
+fn days_in_month(year: i64, month: i64) -> i64 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// (Internal) Get the number of days between the Unix epoch and a date of the proleptic Gregorian calendar
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+
+    era * 146097 + day_of_era - 719468
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_valid_sizes() {
+        assert_eq!(parse_size("500"), Ok(500));
+        assert_eq!(parse_size("10K"), Ok(10 * 1024));
+        assert_eq!(parse_size("1.5g"), Ok(3 * 512 * 1024 * 1024));
+        assert_eq!(parse_size("2MiB"), Ok(2 * 1024 * 1024));
+        assert_eq!(parse_size(" 3 kb "), Ok(3 * 1024));
+    }
+
+    #[test]
+    fn parse_invalid_sizes() {
+        assert_eq!(parse_size(""), Err("missing number in size ''".to_string()));
+        assert_eq!(parse_size("K"), Err("missing number in size 'K'".to_string()));
+        assert!(parse_size("10X").unwrap_err().starts_with("invalid size unit"));
+        assert!(parse_size("10 KB extra").unwrap_err().starts_with("invalid size unit"));
+        assert!(parse_size("1.2.3M").unwrap_err().starts_with("invalid number"));
+        assert_eq!(parse_size("99999999999T"), Err("size '99999999999T' is too large".to_string()));
+        assert!(parse_size("-5").is_err());
+        assert!(parse_size("-5K").is_err());
+    }
+
+    #[test]
+    fn parse_sources_lists() {
+        let sources = |content: &[u8], null_separated| parse_sources_list(content, null_separated);
+
+        assert_eq!(
+            sources(b"# Sources\n/home/user\n\n  \n/mnt/data\r\n#/mnt/old\r\n/srv", false),
+            vec![PathBuf::from("/home/user"), PathBuf::from("/mnt/data"), PathBuf::from("/srv")]
+        );
+
+        assert_eq!(
+            sources(b"/home/user\0/mnt/line\nbreak\r\0\0# comment\0", true),
+            vec![PathBuf::from("/home/user"), PathBuf::from("/mnt/line\nbreak\r")]
+        );
+
+        assert!(sources(b"", false).is_empty());
+        assert!(sources(b"\n\r\n# Only comments\n", false).is_empty());
+    }
+}
@@ -0,0 +1,90 @@
+//! Previous files lists and snapshots the new files list is compared to (see the `--diff-against` option)
+
+use crate::output::{normalize_path, raw_path_bytes};
+use crate::Opts;
+use rebackup::{err, Snapshot};
+use std::collections::HashSet;
+use std::io;
+use std::path::Path;
+
+/// Beginning of the snapshots' header line
+const SNAPSHOT_MAGIC: &[u8] = b"rebackup-snapshot ";
+
+/// Previous output of the program
+pub enum Previous {
+    /// A snapshot (see the `--save-snapshot` option)
+    Snapshot(Snapshot),
+
+    /// A files list, with each entry as it was written
+    List(HashSet<Vec<u8>>),
+}
+
+impl Previous {
+    /// Read a snapshot or a files list (separated by NUL characters if the list contains any, by line breaks otherwise)
+    pub fn parse(content: &[u8]) -> io::Result<Self> {
+        if content.starts_with(SNAPSHOT_MAGIC) {
+            return Snapshot::read_from(content).map(Self::Snapshot);
+        }
+
+        let entries = if content.contains(&b'\0') {
+            content.split(|&byte| byte == b'\0').collect::<Vec<_>>()
+        } else {
+            content
+                .split(|&byte| byte == b'\n')
+                .map(|line| line.strip_suffix(b"\r").unwrap_or(line))
+                .collect()
+        };
+
+        Ok(Self::List(entries.into_iter().filter(|entry| !entry.is_empty()).map(<[u8]>::to_vec).collect()))
+    }
+}
+
+/// Get an item as it is written in the files list by the current invocation, from its source directory
+pub fn list_entry(opts: &Opts, source: &Path, path: &Path) -> Vec<u8> {
+    let path = &*normalize_path(opts, path);
+
+    let path = match path.to_str() {
+        Some(str) => str.as_bytes().to_vec(),
+        None if opts.raw_paths => raw_path_bytes(path),
+        None => path.display().to_string().into_bytes(),
+    };
+
+    [opts.prefix_of(source).as_bytes(), &path, opts.suffix().as_bytes()].concat()
+}
+
+/// Warn if the entries of a previous files list obviously don't use the paths style of the current invocation
+pub fn check_style<'a>(opts: &Opts, entries: &'a HashSet<Vec<u8>>) {
+    let prefixes: Vec<_> = match opts.source_prefixes.is_empty() {
+        true => vec![opts.prefix.first().map_or("", String::as_str)],
+        false => opts.source_prefixes.values().map(String::as_str).collect(),
+    };
+
+    let strip_prefix = |entry: &'a Vec<u8>| {
+        prefixes
+            .iter()
+            .find_map(|prefix| entry.strip_prefix(prefix.as_bytes()))
+            .and_then(|entry| entry.strip_suffix(opts.suffix().as_bytes()))
+    };
+
+    if entries.is_empty() {
+        return;
+    }
+
+    if !entries.iter().any(|entry| strip_prefix(entry).is_some()) {
+        err!("None of the previous files list's entries have the provided prefixes and suffix, all items will be listed as new");
+        return;
+    }
+
+    let same_style = entries
+        .iter()
+        .filter_map(strip_prefix)
+        .any(|path| Path::new(&*String::from_utf8_lossy(path)).is_absolute() == opts.absolute);
+
+    if !same_style {
+        err!(
+            "The previous files list seems to contain {} paths, while {} paths are listed",
+            if opts.absolute { "relative" } else { "absolute" },
+            if opts.absolute { "absolute" } else { "relative" }
+        );
+    }
+}
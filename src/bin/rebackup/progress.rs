@@ -0,0 +1,58 @@
+//! Progress indicator of the walk, displayed to STDERR (see the `--progress` option)
+
+use rebackup::{set_status_line, WalkProgress, WalkerProgressFn};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Minimum interval between two updates of the progress indicator
+const UPDATE_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Default width of the terminal, when it isn't provided by the `COLUMNS` environment variable
+const DEFAULT_WIDTH: usize = 80;
+
+/// Create a progress callback for the walker, which displays the progress as a single line
+pub fn callback() -> WalkerProgressFn {
+    let last_update = Mutex::new(None::<Instant>);
+
+    let width = std::env::var("COLUMNS").ok().and_then(|columns| columns.parse().ok()).unwrap_or(DEFAULT_WIDTH);
+
+    Box::new(move |progress: &WalkProgress| {
+        let mut last_update = last_update.lock().unwrap_or_else(|err| err.into_inner());
+
+        if last_update.is_some_and(|last_update| last_update.elapsed() < UPDATE_INTERVAL) {
+            return;
+        }
+
+        *last_update = Some(Instant::now());
+
+        let counts = format!("{} directories scanned, {} items found: ", progress.dirs_entered, progress.files_included);
+        let path = progress.current_path.display().to_string();
+
+        set_status_line(Some(&format!(
+            "{}{}",
+            counts,
+            truncate_start(&path, width.saturating_sub(counts.chars().count() + 1))
+        )));
+    })
+}
+
+/// Clear the progress indicator
+pub fn finish() {
+    set_status_line(None);
+}
+
+/// (Internal) Keep the end of a string so it fits in the provided number of characters
+fn truncate_start(str: &str, width: usize) -> String {
+    let len = str.chars().count();
+
+    if len <= width {
+        return str.to_string();
+    }
+
+    if width <= 3 {
+        return String::new();
+    }
+
+    let kept: String = str.chars().skip(len - (width - 3)).collect();
+    format!("...{}", kept)
+}
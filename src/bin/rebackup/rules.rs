@@ -1,23 +1,185 @@
 mod glob_patterns;
+mod regex_patterns;
 mod shell_filters;
 
+use crate::output::raw_path_bytes;
+use crate::parsers::parse_age;
 use clap::Clap;
-use rebackup::WalkerRule;
+use rebackup::{fail, rules, RootsItems, WalkerItemType, WalkerItemTypes, WalkerRule};
+use std::time::Duration;
 
 #[derive(Clap)]
 pub struct RulesOpts {
+    #[clap(long = "rule", about = "Enable a built-in rule by name", possible_values = rules::NAMES)]
+    builtin_rules: Vec<String>,
+
+    #[clap(long, about = "Exclude items ignored by Git in Git repositories (doesn't require Git to be installed)")]
+    respect_gitignore: bool,
+
+    #[clap(long, about = "Exclude items based on ignore files with the provided name (e.g. '.rebackupignore')")]
+    ignore_file: Option<String>,
+
+    #[clap(long, about = "Exclude the metadata directories of version control systems (.git, .hg, .svn, .bzr, CVS)")]
+    exclude_vcs: bool,
+
+    #[clap(
+        long,
+        about = "Exclude items ignored by version control systems (currently only Git, same as '--respect-gitignore')"
+    )]
+    exclude_vcs_ignores: bool,
+
+    #[clap(long, about = "Exclude cache directories (containing a valid 'CACHEDIR.TAG' file)")]
+    exclude_caches: bool,
+
+    #[clap(
+        long,
+        about = "Exclude the build artifacts, caches and dependencies of common development ecosystems (Python, Node.js, Go, Cargo...) next to their project files"
+    )]
+    exclude_dev_artifacts: bool,
+
+    #[clap(
+        long,
+        min_values = 0,
+        require_equals = true,
+        default_missing_value = "all",
+        possible_values = &["dirs", "files", "all"],
+        about = "Exclude hidden items (whose name starts with a dot, or with the hidden attribute on Windows), optionally only directories or files (e.g. '--exclude-hidden=dirs')"
+    )]
+    exclude_hidden: Option<String>,
+
+    #[clap(
+        long = "exclude-ext",
+        about = "Exclude files with one of the provided extensions, case-insensitive (comma-separated, e.g. 'iso,mkv,tmp')"
+    )]
+    exclude_extensions: Vec<String>,
+
+    #[clap(
+        long = "only-ext",
+        about = "Only include files with one of the provided extensions, case-insensitive (comma-separated, e.g. 'jpg,png,raw')"
+    )]
+    only_extensions: Vec<String>,
+
+    #[clap(
+        long,
+        parse(try_from_str = parse_age),
+        about = "Exclude files not modified for longer than the provided duration (e.g. '12h', '30d', '2w'), directories are still traversed"
+    )]
+    exclude_older_than: Option<Duration>,
+
+    #[clap(
+        long,
+        about = "Exclude directories containing a file with the provided name, optionally starting with a content ('NAME:CONTENT_PREFIX')"
+    )]
+    exclude_if_present: Vec<String>,
+
     #[clap(flatten)]
     shell_cmd_filters: shell_filters::ShellCmdFiltersOpts,
 
     #[clap(flatten)]
     glob_patterns: glob_patterns::GlobPatternsOpts,
+
+    #[clap(flatten)]
+    regex_patterns: regex_patterns::RegexPatternsOpts,
 }
 
 pub fn make_rules(opts: &RulesOpts) -> Vec<WalkerRule> {
     let mut rules = vec![];
 
+    for name in &opts.builtin_rules {
+        rules.push(rules::by_name(name).unwrap_or_else(|| fail!(exit 10, "Unknown built-in rule: {}", name)));
+    }
+
+    if opts.exclude_vcs {
+        rules.extend(rules::vcs_dirs());
+    }
+
+    if opts.respect_gitignore || opts.exclude_vcs_ignores {
+        rules.push(rules::respect_gitignore());
+    }
+
+    if let Some(file_name) = &opts.ignore_file {
+        rules.push(rules::ignore_file(file_name));
+    }
+
+    if opts.exclude_caches {
+        rules.push(rules::cachedir_tag());
+    }
+
+    if opts.exclude_dev_artifacts {
+        rules.extend(rules::dev_artifacts());
+    }
+
+    if let Some(granularity) = &opts.exclude_hidden {
+        let only_for = match granularity.as_str() {
+            "dirs" => WalkerItemTypes::only(WalkerItemType::Directory),
+            "files" => WalkerItemTypes::only(WalkerItemType::File),
+            _ => WalkerItemTypes::all(),
+        };
+
+        rules.push(WalkerRule { only_for, ..rules::hidden() });
+    }
+
+    if !opts.exclude_extensions.is_empty() {
+        rules.push(rules::exclude_extensions(&split_extensions(&opts.exclude_extensions)));
+    }
+
+    if !opts.only_extensions.is_empty() {
+        rules.push(rules::include_only_extensions(&split_extensions(&opts.only_extensions)));
+    }
+
+    if let Some(max_age) = opts.exclude_older_than {
+        rules.push(rules::exclude_older_than(max_age));
+    }
+
+    for marker in &opts.exclude_if_present {
+        rules.push(match marker.split_once(':') {
+            Some((name, content_prefix)) => rules::exclude_if_present(name, Some(content_prefix.as_bytes())),
+            None => rules::exclude_if_present(marker, None),
+        });
+    }
+
     shell_filters::make_shell_cmd_filters(&opts.shell_cmd_filters, &mut rules);
     glob_patterns::make_pattern_filters(&opts.glob_patterns, &mut rules);
+    regex_patterns::make_regex_filters(&opts.regex_patterns, &mut rules);
 
     rules
 }
+
+/// Split comma-separated lists of extensions provided to '--exclude-ext' or '--only-ext'
+fn split_extensions(lists: &[String]) -> Vec<&str> {
+    lists
+        .iter()
+        .flat_map(|list| list.split(','))
+        .map(str::trim)
+        .filter(|ext| !ext.is_empty())
+        .collect()
+}
+
+/// Check if filters must be applied to the files list after the walk (see [`apply_post_walk_filters`])
+pub fn has_post_walk_filters(opts: &RulesOpts) -> bool {
+    let filters = &opts.shell_cmd_filters;
+    filters.filter_jobs.is_some() || !filters.filter_with_batch.is_empty()
+}
+
+/// Apply the filters which process the files list after the walk (parallel and batch shell filters)
+pub fn apply_post_walk_filters(opts: &RulesOpts, items: &mut RootsItems) {
+    let filters = &opts.shell_cmd_filters;
+
+    if filters.filter_jobs.is_some() {
+        shell_filters::run_parallel_filters(filters, items).unwrap_or_else(|err| fail!(exit 3, "Shell filter failed: {}", err));
+    }
+
+    for filter in &filters.filter_with_batch {
+        let paths = items
+            .iter()
+            .flat_map(|(source, items)| items.iter().map(move |path| source.join(path)))
+            .collect();
+
+        let kept = shell_filters::run_batch_filter(filters, filter, paths)
+            .unwrap_or_else(|err| fail!(exit 3, "Batch filter failed: {} (command: {})", err, filter));
+
+        for (source, items) in items.iter_mut() {
+            items.retain(|path| kept.contains(&raw_path_bytes(&source.join(path))));
+        }
+    }
+}
@@ -1,6 +1,9 @@
 use clap::Clap;
-use glob::Pattern;
-use rebackup::{fail, WalkerRule, WalkerRuleResult};
+use rebackup::rules::{glob_rules, read_patterns, GlobPatterns};
+use rebackup::{fail, WalkerRule};
+use std::fs::File;
+use std::io::{self, BufReader};
+use std::path::{Path, PathBuf};
 
 #[derive(Clap)]
 pub struct GlobPatternsOpts {
@@ -12,30 +15,73 @@ pub struct GlobPatternsOpts {
 
     #[clap(short, long, about = "Exclude items with a glob pattern")]
     pub exclude: Vec<String>,
+
+    #[clap(long, about = "Only include items with the glob patterns of a file, one per line ('-' to read from STDIN)")]
+    pub include_from: Vec<PathBuf>,
+
+    #[clap(long, about = "Exclude items with the glob patterns of a file, one per line ('-' to read from STDIN)")]
+    pub exclude_from: Vec<PathBuf>,
+
+    #[clap(long, about = "Include directories matching a glob pattern, but not their content")]
+    pub prune: Vec<String>,
+
+    #[clap(
+        long,
+        about = "Exclude items with a glob pattern, or re-include them if it starts with a '!' (evaluated in order, the last matching pattern applies)"
+    )]
+    pub pattern: Vec<String>,
+
+    #[clap(long, about = "Match glob patterns against absolute paths (default is relative to the source directory)")]
+    pub absolute_patterns: bool,
+
+    #[clap(
+        long,
+        about = "Match all glob patterns against full paths (by default, patterns without a '/' match items' name at any depth)"
+    )]
+    pub full_path_patterns: bool,
 }
 
 pub fn make_pattern_filters(opts: &GlobPatternsOpts, out: &mut Vec<WalkerRule>) {
-    fn make_pattern_filter(rule_name: &'static str, action: WalkerRuleResult, pattern: &str, out: &mut Vec<WalkerRule>) {
-        let pattern = Pattern::new(pattern).unwrap_or_else(|err| fail!(exit 10, "Invalid pattern provided: {}", err));
-
-        out.push(WalkerRule {
-            name: rule_name,
-            description: Some(format!("Pattern: {}", pattern)),
-            only_for: None,
-            matches: Box::new(move |path, _, source| pattern.matches_path(path.strip_prefix(source).unwrap())),
-            action: Box::new(move |_, _, _| Ok(action.clone())),
-        });
-    }
+    let mut include = opts.include_only.clone();
+    let mut exclude = opts.exclude.clone();
 
-    for pattern in &opts.include_absolute {
-        make_pattern_filter("include-pattern-absolute", WalkerRuleResult::IncludeItemAbsolute, pattern, out);
+    for path in &opts.include_from {
+        include.extend(read_patterns_file(path));
     }
 
-    for pattern in &opts.include_only {
-        make_pattern_filter("include-pattern", WalkerRuleResult::IncludeItem, pattern, out);
+    for path in &opts.exclude_from {
+        exclude.extend(read_patterns_file(path));
     }
 
-    for pattern in &opts.exclude {
-        make_pattern_filter("exclude-pattern", WalkerRuleResult::ExcludeItem, pattern, out);
+    let patterns = GlobPatterns {
+        include_absolute: opts.include_absolute.clone(),
+        include,
+        exclude,
+        prune: opts.prune.clone(),
+        ordered: opts.pattern.clone(),
+        absolute: opts.absolute_patterns,
+        full_path: opts.full_path_patterns,
+    };
+
+    if patterns.include_absolute.is_empty()
+        && patterns.include.is_empty()
+        && patterns.exclude.is_empty()
+        && patterns.prune.is_empty()
+        && patterns.ordered.is_empty()
+    {
+        return;
     }
+
+    out.push(glob_rules(&patterns).unwrap_or_else(|err| fail!(exit 10, "Invalid pattern provided: {}", err)));
+}
+
+fn read_patterns_file(path: &Path) -> Vec<String> {
+    let patterns = if path == Path::new("-") {
+        read_patterns(io::stdin().lock())
+    } else {
+        let file = File::open(path).unwrap_or_else(|err| fail!(exit 10, "Failed to open patterns file '{}': {}", path.display(), err));
+        read_patterns(BufReader::new(file))
+    };
+
+    patterns.unwrap_or_else(|err| fail!(exit 10, "Invalid patterns file '{}': {}", path.display(), err))
 }
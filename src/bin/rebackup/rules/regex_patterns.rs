@@ -0,0 +1,27 @@
+use clap::Clap;
+use rebackup::{fail, rules, WalkerRule};
+use regex::{Regex, RegexSet};
+
+#[derive(Clap)]
+pub struct RegexPatternsOpts {
+    #[clap(long, about = "Exclude items whose path (relative to the source directory) matches a regular expression")]
+    pub exclude_regex: Vec<String>,
+
+    #[clap(
+        long,
+        about = "Only include files whose path (relative to the source directory) matches any of the provided regular expressions"
+    )]
+    pub include_only_regex: Vec<String>,
+}
+
+pub fn make_regex_filters(opts: &RegexPatternsOpts, out: &mut Vec<WalkerRule>) {
+    for re in &opts.exclude_regex {
+        let re = Regex::new(re).unwrap_or_else(|err| fail!(exit 10, "Invalid regular expression provided:\n{}", err));
+        out.push(rules::exclude_regex(re));
+    }
+
+    if !opts.include_only_regex.is_empty() {
+        let set = RegexSet::new(&opts.include_only_regex).unwrap_or_else(|err| fail!(exit 10, "Invalid regular expression provided:\n{}", err));
+        out.push(rules::include_only_regex(set));
+    }
+}
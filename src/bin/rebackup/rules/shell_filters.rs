@@ -1,13 +1,67 @@
+use crate::output::{item_type_name, raw_path_bytes};
+use crate::parsers::parse_timeout;
 use clap::Clap;
-use rebackup::{WalkerRule, WalkerRuleResult};
-use std::process::{Command, Stdio};
+use globset::{Glob, GlobBuilder, GlobMatcher};
+use rebackup::{fail, RootsItems, WalkerItemType, WalkerItemTypes, WalkerRule, WalkerRuleResult};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io::{self, BufWriter, IsTerminal, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
 
 #[derive(Clap)]
 pub struct ShellCmdFiltersOpts {
-    #[clap(short, long, about = "Exclude items when provided commands fail (use REBACKUP_ITEM variable)")]
+    #[clap(
+        short,
+        long,
+        about = "Exclude items when provided commands exit with code 1, include them with code 0 (variables: REBACKUP_ITEM, REBACKUP_ITEM_TYPE, REBACKUP_ITEM_RELATIVE, REBACKUP_ITEM_SIZE, REBACKUP_SOURCE)"
+    )]
     pub filter_with: Vec<String>,
 
-    #[clap(long, about = "The binary shell to use for filtering")]
+    #[clap(
+        long,
+        requires = "filter-with",
+        about = "Run the commands of --filter-with after the walk on this number of threads, instead of one by one during the walk (0 to use the number of CPU cores)"
+    )]
+    pub filter_jobs: Option<usize>,
+
+    #[clap(
+        long,
+        requires = "filter-with",
+        about = "Only run the commands of --filter-with on items matching a glob pattern, other items are kept (one pattern per command in the same order, or a single one for all commands)"
+    )]
+    pub filter_match: Vec<String>,
+
+    #[clap(long, requires = "filter-with", about = "Only run the commands of --filter-with on files (other items are kept)")]
+    pub filter_only_files: bool,
+
+    #[clap(
+        long,
+        requires = "filter-with",
+        about = "Only run the commands of --filter-with on directories (other items are kept)"
+    )]
+    pub filter_only_dirs: bool,
+
+    #[clap(
+        long,
+        about = "Run a command once with all items' paths on its STDIN, and only keep the items whose path it writes back to its STDOUT"
+    )]
+    pub filter_with_batch: Vec<String>,
+
+    #[clap(
+        long,
+        requires = "filter-with-batch",
+        about = "Separate paths with NUL characters instead of line breaks when communicating with batch filters"
+    )]
+    pub filter_batch_print0: bool,
+
+    #[clap(
+        long,
+        about = "The binary shell to use for filtering (default: sh, or cmd.exe on Windows), arguments are set automatically for sh, bash, zsh, fish, cmd and powershell"
+    )]
     pub shell: Option<String>,
 
     #[clap(long, about = "Shell arguments provided before commands", requires = "shell")]
@@ -16,41 +70,47 @@ pub struct ShellCmdFiltersOpts {
     #[clap(long, about = "Shell arguments provided after commands", requires = "shell")]
     pub shell_tail_args: Vec<String>,
 
+    #[clap(
+        long,
+        about = "Exclude items when commands of --filter-with fail with any exit code (by default, only code 1 excludes the item and other ones are errors)"
+    )]
+    pub filter_lenient: bool,
+
     #[clap(long, about = "Print commands' STDOUT and STDERR")]
     pub display_shell_output: bool,
+
+    #[clap(
+        long,
+        parse(try_from_str = parse_timeout),
+        about = "Kill filtering commands running for longer than the provided number of seconds, and fail"
+    )]
+    pub shell_timeout: Option<Duration>,
 }
 
 pub fn make_shell_cmd_filters(opts: &ShellCmdFiltersOpts, out: &mut Vec<WalkerRule>) {
-    let (shell_path, shell_head_args, shell_tail_args) = if let Some(shell_path) = &opts.shell {
-        (shell_path.clone(), opts.shell_head_args.clone(), opts.shell_tail_args.clone())
-    } else if cfg!(windows) {
-        ("cmd.exe".to_string(), vec!["-C".to_string()], vec![])
-    } else {
-        ("sh".to_string(), vec!["-c".to_string()], vec![])
-    };
+    // Filters are run after the walk when running them in parallel (see `run_parallel_filters`)
+    if opts.filter_jobs.is_some() {
+        return;
+    }
 
-    let display_shell_output = opts.display_shell_output;
+    let shell = Shell::new(opts);
 
-    for filter in &opts.filter_with {
-        let (shell_path, shell_head_args, shell_tail_args) = (shell_path.clone(), shell_head_args.clone(), shell_tail_args.clone());
-        let filter = filter.clone();
+    for filter in item_filters(opts) {
+        let shell = shell.clone();
+        let matcher = filter.clone();
 
         out.push(WalkerRule {
             name: "shell-filter",
-            description: Some(format!("Command: {}", filter)),
-            only_for: None,
-            matches: Box::new(|_, _, _| true),
-            action: Box::new(move |path, _, _| {
-                let output = Command::new(shell_path.clone())
-                    .args(&shell_head_args)
-                    .arg(&filter)
-                    .args(&shell_tail_args)
-                    .env("REBACKUP_ITEM", path)
-                    .stdout(if display_shell_output { Stdio::inherit() } else { Stdio::null() })
-                    .stderr(if display_shell_output { Stdio::inherit() } else { Stdio::null() })
-                    .output()?;
-
-                Ok(if output.status.success() {
+            description: Some(format!("Command: {}", filter.command)),
+            priority: 0,
+            only_for: filter.types,
+            min_depth: None,
+            max_depth: None,
+            cacheable: false,
+            timeout: shell.timeout,
+            matches: Box::new(move |ctx| Ok(matcher.applies_to(ctx.path.strip_prefix(ctx.source).unwrap_or(ctx.path), ctx.item_type))),
+            action: Box::new(move |ctx| {
+                Ok(if shell.run_filter(&filter.command, ctx.path, ctx.metadata, ctx.source)? {
                     WalkerRuleResult::IncludeItem
                 } else {
                     WalkerRuleResult::ExcludeItem
@@ -59,3 +119,466 @@ pub fn make_shell_cmd_filters(opts: &ShellCmdFiltersOpts, out: &mut Vec<WalkerRu
         });
     }
 }
+
+/// Run the per-item filters (see `--filter-with`) after the walk on multiple threads (see `--filter-jobs`)
+///
+/// As excluding a directory during the walk excludes its whole content, items are only kept if the directories
+/// containing them (inside their source directory) pass the filters as well. Items' order is preserved.
+pub fn run_parallel_filters(opts: &ShellCmdFiltersOpts, items: &mut RootsItems) -> io::Result<()> {
+    let shell = Shell::new(opts);
+    let filters = item_filters(opts);
+
+    // Items and the directories containing them, with the source directory they belong to
+    let mut candidates = vec![];
+    let mut indexes = HashMap::new();
+
+    for (source, items) in items.iter() {
+        for path in items {
+            let path = source.join(path);
+
+            for ancestor in path.ancestors().take_while(|ancestor| *ancestor != source && ancestor.starts_with(source)) {
+                if !indexes.contains_key(ancestor) {
+                    indexes.insert(ancestor.to_path_buf(), candidates.len());
+                    candidates.push((source.as_path(), ancestor.to_path_buf()));
+                }
+            }
+        }
+    }
+
+    let jobs = match opts.filter_jobs {
+        Some(0) | None => thread::available_parallelism().map(|jobs| jobs.get()).unwrap_or(1),
+        Some(jobs) => jobs,
+    };
+
+    let next = AtomicUsize::new(0);
+    let done = AtomicUsize::new(0);
+    let progress = io::stderr().is_terminal();
+
+    let mut results: Vec<_> = thread::scope(|scope| {
+        let workers: Vec<_> = (0..jobs.min(candidates.len()))
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut results = vec![];
+
+                    loop {
+                        let index = next.fetch_add(1, Ordering::SeqCst);
+
+                        let (source, path) = match candidates.get(index) {
+                            Some(candidate) => candidate,
+                            None => break Ok(results),
+                        };
+
+                        let metadata = fs::symlink_metadata(path)?;
+                        let item_type = WalkerItemType::from_file_type(metadata.file_type());
+                        let relative = path.strip_prefix(source).unwrap_or(path);
+                        let mut keep = true;
+
+                        for filter in filters.iter().filter(|filter| filter.applies_to(relative, item_type)) {
+                            let keep_item = shell.run_filter(&filter.command, path, &metadata, source).map_err(|err| {
+                                io::Error::new(err.kind(), format!("{} (command: {}, on item: {})", err, filter.command, path.display()))
+                            })?;
+
+                            if !keep_item {
+                                keep = false;
+                                break;
+                            }
+                        }
+
+                        results.push((index, keep));
+
+                        let done = done.fetch_add(1, Ordering::SeqCst) + 1;
+
+                        if progress {
+                            eprint!("\rFiltering items: {}/{}", done, candidates.len());
+                        }
+                    }
+                })
+            })
+            .collect();
+
+        workers
+            .into_iter()
+            .map(|worker| worker.join().unwrap_or_else(|err| std::panic::resume_unwind(err)))
+            .collect::<io::Result<Vec<Vec<_>>>>()
+    })?
+    .into_iter()
+    .flatten()
+    .collect();
+
+    if progress && !candidates.is_empty() {
+        eprintln!();
+    }
+
+    results.sort_by_key(|(index, _)| *index);
+
+    for (source, items) in items.iter_mut() {
+        items.retain(|path| {
+            let path = source.join(path);
+
+            path.ancestors()
+                .take_while(|ancestor| *ancestor != source && ancestor.starts_with(&source))
+                .all(|ancestor| results[indexes[ancestor]].1)
+        });
+    }
+
+    Ok(())
+}
+
+/// (Internal) Per-item filter (see `--filter-with`)
+#[derive(Clone)]
+struct ItemFilter {
+    command: String,
+
+    /// Pattern the items' path must match (see `--filter-match`)
+    pattern: Option<FilterPattern>,
+
+    /// Types of items to run the command on (see `--filter-only-files` and `--filter-only-dirs`)
+    types: WalkerItemTypes,
+}
+
+/// (Internal) Glob pattern of a per-item filter, following the convention of the other glob patterns
+#[derive(Clone)]
+enum FilterPattern {
+    /// Pattern without a '/', matching items' name at any depth
+    Name(GlobMatcher),
+
+    /// Pattern matching items' path, relative to the source directory
+    Path(GlobMatcher),
+}
+
+impl ItemFilter {
+    /// Check if the command must be run on an item
+    fn applies_to(&self, relative_path: &Path, item_type: WalkerItemType) -> bool {
+        self.types.contains(item_type)
+            && match &self.pattern {
+                None => true,
+                Some(FilterPattern::Name(glob)) => relative_path.file_name().is_some_and(|name| glob.is_match(name)),
+                Some(FilterPattern::Path(glob)) => glob.is_match(relative_path),
+            }
+    }
+}
+
+/// (Internal) Build the per-item filters, pairing the commands with their pattern
+fn item_filters(opts: &ShellCmdFiltersOpts) -> Vec<ItemFilter> {
+    let patterns = &opts.filter_match;
+
+    if patterns.len() > 1 && patterns.len() != opts.filter_with.len() {
+        fail!(
+            exit 10,
+            "Expected a single --filter-match pattern or one per --filter-with command, got {} patterns for {} commands",
+            patterns.len(),
+            opts.filter_with.len()
+        );
+    }
+
+    let types = if opts.filter_only_files || opts.filter_only_dirs {
+        WalkerItemTypes::of(
+            [
+                (opts.filter_only_files, WalkerItemType::File),
+                (opts.filter_only_dirs, WalkerItemType::Directory),
+            ]
+            .iter()
+            .filter(|(enabled, _)| *enabled)
+            .map(|(_, item_type)| *item_type),
+        )
+    } else {
+        WalkerItemTypes::all()
+    };
+
+    opts.filter_with
+        .iter()
+        .enumerate()
+        .map(|(i, command)| {
+            let pattern = patterns.get(if patterns.len() == 1 { 0 } else { i }).map(|pattern| {
+                let glob = match pattern.strip_prefix('/').unwrap_or(pattern) {
+                    glob if pattern.contains('/') => GlobBuilder::new(glob).literal_separator(true).build(),
+                    glob => Glob::new(glob),
+                };
+
+                match glob {
+                    Ok(glob) if pattern.contains('/') => FilterPattern::Path(glob.compile_matcher()),
+                    Ok(glob) => FilterPattern::Name(glob.compile_matcher()),
+                    Err(err) => fail!(exit 10, "Invalid pattern provided: {}", err),
+                }
+            });
+
+            ItemFilter {
+                command: command.clone(),
+                pattern,
+                types,
+            }
+        })
+        .collect()
+}
+
+/// Run a batch filter (see `--filter-with-batch`) on items' full paths, and get the ones to keep
+///
+/// Paths are written to the command's STDIN while its STDOUT is read, so it can process them as they come.
+/// The command may exit before reading all paths, but it must succeed.
+pub fn run_batch_filter(opts: &ShellCmdFiltersOpts, filter: &str, paths: Vec<PathBuf>) -> io::Result<HashSet<Vec<u8>>> {
+    let separator = if opts.filter_batch_print0 { b'\0' } else { b'\n' };
+
+    let mut child = Shell::new(opts)
+        .command(filter)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(if opts.display_shell_output { Stdio::inherit() } else { Stdio::null() })
+        .spawn()?;
+
+    let stdin = child.stdin.take().unwrap();
+    let mut stdout = child.stdout.take().unwrap();
+
+    let writer = thread::spawn(move || -> io::Result<()> {
+        let mut stdin = BufWriter::new(stdin);
+
+        for path in paths {
+            let bytes = raw_path_bytes(&path);
+
+            if bytes.contains(&separator) {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("path contains a line break (use --filter-batch-print0): {}", path.display()),
+                ));
+            }
+
+            stdin.write_all(&bytes)?;
+            stdin.write_all(&[separator])?;
+        }
+
+        stdin.flush()
+    });
+
+    let mut output = vec![];
+    let read = stdout.read_to_end(&mut output);
+
+    // The command closing its STDIN early is not an error by itself, its exit status tells if it failed
+    let written = match writer.join().expect("Batch filter's writer thread panicked") {
+        Err(err) if err.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+        result => result,
+    };
+
+    if let Err(err) = read.and(written) {
+        let _ = child.kill();
+        let _ = child.wait();
+        return Err(err);
+    }
+
+    let status = child.wait()?;
+
+    if !status.success() {
+        return Err(io::Error::other(format!("command failed ({})", status)));
+    }
+
+    Ok(output
+        .split(|byte| *byte == separator)
+        .map(|line| if separator == b'\n' { line.strip_suffix(b"\r").unwrap_or(line) } else { line })
+        .filter(|line| !line.is_empty())
+        .map(|line| line.to_vec())
+        .collect())
+}
+
+/// (Internal) Shell to run filters with
+#[derive(Clone)]
+struct Shell {
+    path: String,
+    head_args: Vec<String>,
+    tail_args: Vec<String>,
+    display_output: bool,
+    lenient: bool,
+    timeout: Option<Duration>,
+}
+
+impl Shell {
+    fn new(opts: &ShellCmdFiltersOpts) -> Self {
+        let (path, head_args, tail_args) = shell_invocation(opts.shell.as_deref(), &opts.shell_head_args, &opts.shell_tail_args);
+
+        Self {
+            path,
+            head_args,
+            tail_args,
+            display_output: opts.display_shell_output,
+            lenient: opts.filter_lenient,
+            timeout: opts.shell_timeout,
+        }
+    }
+
+    /// Build the command running a filter
+    fn command(&self, filter: &str) -> Command {
+        let mut command = Command::new(&self.path);
+        command.args(&self.head_args);
+
+        // cmd.exe doesn't follow the usual quoting rules of arguments, so commands must be provided to it as they are
+        #[cfg(windows)]
+        {
+            if shell_name(&self.path) == "cmd" {
+                std::os::windows::process::CommandExt::raw_arg(&mut command, filter);
+            } else {
+                command.arg(filter);
+            }
+        }
+
+        #[cfg(not(windows))]
+        command.arg(filter);
+
+        command.args(&self.tail_args);
+        command
+    }
+
+    /// Run a per-item filter, and check if the item must be kept
+    ///
+    /// Exit code 0 keeps the item and 1 excludes it. Other codes and termination by a signal are errors,
+    /// whose message contains the command's STDERR (unless lenient, in which case they exclude the item).
+    fn run_filter(&self, filter: &str, path: &Path, metadata: &fs::Metadata, source: &Path) -> io::Result<bool> {
+        let mut command = self.command(filter);
+
+        // Only files have a meaningful size
+        if metadata.is_file() {
+            command.env("REBACKUP_ITEM_SIZE", metadata.len().to_string());
+        }
+
+        let mut child = command
+            .env("REBACKUP_ITEM", path)
+            .env("REBACKUP_ITEM_TYPE", item_type_name(metadata))
+            .env("REBACKUP_ITEM_RELATIVE", path.strip_prefix(source).unwrap_or(path))
+            .env("REBACKUP_SOURCE", source)
+            .stdout(if self.display_output { Stdio::inherit() } else { Stdio::null() })
+            .stderr(if self.display_output {
+                Stdio::inherit()
+            } else if self.lenient {
+                Stdio::null()
+            } else {
+                Stdio::piped()
+            })
+            .spawn()?;
+
+        // STDERR is read from another thread, so the command can't be blocked by a full pipe
+        let stderr = child.stderr.take().map(|mut stderr| {
+            thread::spawn(move || {
+                let mut out = vec![];
+                let _ = stderr.read_to_end(&mut out);
+                out
+            })
+        });
+
+        let status = match self.timeout {
+            Some(timeout) => wait_with_timeout(&mut child, timeout)?,
+            None => child.wait()?,
+        };
+
+        if status.success() {
+            return Ok(true);
+        }
+
+        if self.lenient || status.code() == Some(1) {
+            return Ok(false);
+        }
+
+        let reason = match status.code() {
+            Some(code) => format!("command exited with code {}", code),
+            None => format!("command was terminated ({})", status),
+        };
+
+        let stderr = stderr.and_then(|reader| reader.join().ok()).unwrap_or_default();
+        let stderr = String::from_utf8_lossy(&stderr);
+
+        Err(io::Error::other(match stderr.trim() {
+            "" => reason,
+            stderr => format!("{}: {}", reason, stderr),
+        }))
+    }
+}
+
+/// Get the shell's path, and the arguments to provide before and after commands
+///
+/// When no argument is provided, known shells get the ones required to run a command (e.g. `-c` for `sh`, `/C` for `cmd`).
+fn shell_invocation(shell: Option<&str>, head_args: &[String], tail_args: &[String]) -> (String, Vec<String>, Vec<String>) {
+    let shell = shell.unwrap_or(if cfg!(windows) { "cmd.exe" } else { "sh" });
+
+    if !head_args.is_empty() || !tail_args.is_empty() {
+        return (shell.to_string(), head_args.to_vec(), tail_args.to_vec());
+    }
+
+    let head_args: &[&str] = match shell_name(shell).as_str() {
+        "sh" | "bash" | "dash" | "zsh" | "ksh" | "fish" => &["-c"],
+        "cmd" => &["/C"],
+        "powershell" | "pwsh" => &["-NoProfile", "-Command"],
+        _ => &[],
+    };
+
+    (shell.to_string(), head_args.iter().map(|arg| arg.to_string()).collect(), vec![])
+}
+
+/// Get the name of a shell from its path, without extension (e.g. `cmd` for `C:\Windows\System32\cmd.exe`)
+///
+/// Both kinds of separators are accepted, so Windows paths are recognized on every platform.
+fn shell_name(shell: &str) -> String {
+    let file_name = shell.rsplit(['/', '\\']).next().unwrap_or(shell);
+
+    Path::new(file_name)
+        .file_stem()
+        .map_or_else(|| file_name.to_string(), |stem| stem.to_string_lossy().to_ascii_lowercase())
+}
+
+/// Wait for a command to complete, killing it if it runs for longer than the provided timeout
+fn wait_with_timeout(child: &mut Child, timeout: Duration) -> io::Result<ExitStatus> {
+    let deadline = Instant::now() + timeout;
+
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(status);
+        }
+
+        if Instant::now() >= deadline {
+            child.kill()?;
+            child.wait()?;
+
+            return Err(io::Error::new(io::ErrorKind::TimedOut, format!("command timed out after {:?}", timeout)));
+        }
+
+        thread::sleep(Duration::from_millis(10));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn invocation(shell: Option<&str>, head_args: &[&str], tail_args: &[&str]) -> (String, Vec<String>, Vec<String>) {
+        let to_vec = |args: &[&str]| args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>();
+        shell_invocation(shell, &to_vec(head_args), &to_vec(tail_args))
+    }
+
+    #[test]
+    fn default_shell_arguments() {
+        if cfg!(windows) {
+            assert_eq!(invocation(None, &[], &[]), ("cmd.exe".to_string(), vec!["/C".to_string()], vec![]));
+        } else {
+            assert_eq!(invocation(None, &[], &[]), ("sh".to_string(), vec!["-c".to_string()], vec![]));
+        }
+
+        for shell in ["powershell", "pwsh", "/usr/bin/pwsh", "PowerShell.exe"] {
+            assert_eq!(invocation(Some(shell), &[], &[]).1, vec!["-NoProfile", "-Command"]);
+        }
+
+        assert_eq!(invocation(Some("/bin/bash"), &[], &[]).1, vec!["-c"]);
+        assert_eq!(invocation(Some(r"C:\Windows\System32\CMD.EXE"), &[], &[]).1, vec!["/C"]);
+        assert!(invocation(Some("python3"), &[], &[]).1.is_empty());
+    }
+
+    #[test]
+    fn explicit_shell_arguments() {
+        assert_eq!(
+            invocation(Some("bash"), &["-e", "-c"], &[]),
+            ("bash".to_string(), vec!["-e".to_string(), "-c".to_string()], vec![])
+        );
+
+        assert_eq!(invocation(Some("cmd"), &[], &["extra"]), ("cmd".to_string(), vec![], vec!["extra".to_string()]));
+    }
+
+    #[test]
+    fn shell_names() {
+        assert_eq!(shell_name(r"C:\Windows\System32\CMD.EXE"), "cmd");
+        assert_eq!(shell_name("/usr/local/bin/zsh"), "zsh");
+        assert_eq!(shell_name("pwsh"), "pwsh");
+    }
+}
@@ -0,0 +1,187 @@
+//! Tar archive output (see the `--format tar` option, requires the `tar-output` feature)
+//!
+//! Archives are written with the `tar` crate, using GNU extensions for paths and link targets that don't fit in a header.
+
+use rebackup::err;
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::Path;
+use tar::{Builder, Header};
+
+/// Append an item to an archive under the provided name (returns `false` if the item was skipped)
+///
+/// Items that disappeared or cannot be read anymore are skipped with a warning. Files that are modified while they
+/// are archived keep the size they had when their header was written.
+pub fn append_item(archive: &mut Builder<impl Write>, name: &Path, path: &Path) -> io::Result<bool> {
+    let metadata = match path.symlink_metadata() {
+        Ok(metadata) => metadata,
+        Err(err) => {
+            err!("Failed to archive item, skipping it: {} ({})", path.display(), err);
+            return Ok(false);
+        }
+    };
+
+    let file_type = metadata.file_type();
+
+    let mut header = Header::new_gnu();
+    header.set_metadata(&metadata);
+
+    if file_type.is_dir() {
+        archive.append_data(&mut header, name, io::empty())?;
+    } else if file_type.is_symlink() {
+        let target = match fs::read_link(path) {
+            Ok(target) => target,
+            Err(err) => {
+                err!("Failed to read symbolic link's target, skipping it: {} ({})", path.display(), err);
+                return Ok(false);
+            }
+        };
+
+        archive.append_link(&mut header, name, target)?;
+    } else if file_type.is_file() {
+        let mut file = match File::open(path) {
+            Ok(file) => file,
+            Err(err) => {
+                err!("Failed to open file, skipping it: {} ({})", path.display(), err);
+                return Ok(false);
+            }
+        };
+
+        let content = FileContent {
+            file: &mut file,
+            path,
+            remaining: metadata.len(),
+            truncated: false,
+        };
+
+        archive.append_data(&mut header, name, content)?;
+    } else {
+        err!("Special files cannot be archived, skipping it: {}", path.display());
+        return Ok(false);
+    }
+
+    Ok(true)
+}
+
+/// (Internal) Content of a file, with exactly the size written in its header
+///
+/// Reading errors are reported but don't fail, and missing content is filled with zeroes so the archive stays valid.
+struct FileContent<'a> {
+    file: &'a mut File,
+    path: &'a Path,
+    remaining: u64,
+    truncated: bool,
+}
+
+impl Read for FileContent<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = self.remaining.min(buf.len() as u64) as usize;
+
+        if len == 0 {
+            return Ok(0);
+        }
+
+        if !self.truncated {
+            match self.file.read(&mut buf[..len]) {
+                Ok(0) => {
+                    err!("File was truncated while being archived, padding it with zeroes: {}", self.path.display());
+                    self.truncated = true;
+                }
+                Ok(read) => {
+                    self.remaining -= read as u64;
+                    return Ok(read);
+                }
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => return Err(err),
+                Err(err) => {
+                    err!(
+                        "Failed to read file, its content in the archive is incomplete: {} ({})",
+                        self.path.display(),
+                        err
+                    );
+                    self.truncated = true;
+                }
+            }
+        }
+
+        buf[..len].fill(0);
+        self.remaining -= len as u64;
+        Ok(len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use tar::{Archive, EntryType};
+
+    #[test]
+    fn written_archive_can_be_listed_back() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+
+        let long_name = format!("{}/file", "long-directory-name".repeat(8));
+
+        fs::create_dir_all(root.join("dir")).unwrap();
+        fs::create_dir_all(root.join(&long_name).parent().unwrap()).unwrap();
+        fs::write(root.join("dir/file"), "content").unwrap();
+        fs::write(root.join(&long_name), "long").unwrap();
+
+        #[cfg(unix)]
+        std::os::unix::fs::symlink("dir/file", root.join("link")).unwrap();
+
+        let mut archive = Builder::new(vec![]);
+
+        for name in ["dir", "dir/file", long_name.as_str(), "link", "missing"] {
+            let appended = append_item(&mut archive, &Path::new("prefix").join(name), &root.join(name)).unwrap();
+            assert_eq!(appended, name != "missing" && (cfg!(unix) || name != "link"));
+        }
+
+        let bytes = archive.into_inner().unwrap();
+
+        let mut entries = vec![];
+
+        for entry in Archive::new(bytes.as_slice()).entries().unwrap() {
+            let mut entry = entry.unwrap();
+
+            let path = entry.path().unwrap().into_owned();
+            let link_name = entry.link_name().unwrap().map(|target| target.into_owned());
+            let mut content = String::new();
+            entry.read_to_string(&mut content).unwrap();
+
+            entries.push((path, entry.header().entry_type(), content, link_name));
+        }
+
+        let mut expected = vec![
+            (PathBuf::from("prefix/dir"), EntryType::Directory, String::new(), None),
+            (PathBuf::from("prefix/dir/file"), EntryType::Regular, "content".to_string(), None),
+            (Path::new("prefix").join(&long_name), EntryType::Regular, "long".to_string(), None),
+        ];
+
+        if cfg!(unix) {
+            expected.push((PathBuf::from("prefix/link"), EntryType::Symlink, String::new(), Some(PathBuf::from("dir/file"))));
+        }
+
+        assert_eq!(entries, expected);
+    }
+
+    #[test]
+    fn truncated_files_are_padded_with_zeroes() {
+        let mut file = tempfile::tempfile().unwrap();
+        file.write_all(b"abc").unwrap();
+        io::Seek::rewind(&mut file).unwrap();
+
+        let mut content = vec![];
+
+        FileContent {
+            file: &mut file,
+            path: Path::new("file"),
+            remaining: 8,
+            truncated: false,
+        }
+        .read_to_end(&mut content)
+        .unwrap();
+
+        assert_eq!(content, b"abc\0\0\0\0\0");
+    }
+}
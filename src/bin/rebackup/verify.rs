@@ -0,0 +1,89 @@
+//! Verification of a previous files list or snapshot against the source directory (see the `--verify` option)
+
+use crate::output::raw_path_bytes;
+use crate::parsers::path_from_bytes;
+use crate::previous::Previous;
+use crate::Opts;
+use rebackup::{fail, WalkerItemType};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Check that all entries of a files list or snapshot still exist with the same type, print the ones that don't,
+/// and exit (with code 12 if any entry is missing or changed type)
+pub fn run(opts: &Opts, list: &Path, source: &Path) -> ! {
+    let previous = fs::read(list)
+        .and_then(|content| Previous::parse(&content))
+        .unwrap_or_else(|err| fail!(exit 9, "Failed to read files list or snapshot to verify: {}", err));
+
+    // Entries are compared as raw bytes, alongside their expected type if it is known
+    let mut entries: Vec<(Vec<u8>, Option<WalkerItemType>)> = match previous {
+        Previous::Snapshot(snapshot) => snapshot
+            .entries
+            .iter()
+            .map(|(path, entry)| (raw_path_bytes(path), Some(entry.item_type)))
+            .collect(),
+
+        // Directories are only known in lists written with '--mark-dirs'
+        Previous::List(entries) => entries
+            .into_iter()
+            .map(|entry| match entry.strip_suffix(b"/") {
+                Some(dir) if !dir.is_empty() => (dir.to_vec(), Some(WalkerItemType::Directory)),
+                _ => (entry, None),
+            })
+            .collect(),
+    };
+
+    entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+    let strip_prefix = opts.strip_prefix.as_deref().unwrap_or("").as_bytes();
+    let mut failed = 0;
+
+    for (entry, expected) in entries {
+        let path = match entry.strip_prefix(strip_prefix) {
+            Some(path) => path_from_bytes(path),
+            None => fail!(
+                exit 12,
+                "Entry doesn't start with the prefix to strip: {}",
+                String::from_utf8_lossy(&entry)
+            ),
+        };
+
+        let full_path: PathBuf = if opts.absolute { path.clone() } else { source.join(&path) };
+
+        let actual = match full_path.symlink_metadata() {
+            Ok(metadata) => WalkerItemType::from_file_type(metadata.file_type()),
+            Err(_) => {
+                println!("MISSING {}", path.display());
+                failed += 1;
+                continue;
+            }
+        };
+
+        // Entries without a trailing slash are not directories when the list was written with '--mark-dirs'
+        let expected = match expected {
+            None if opts.mark_dirs && actual == WalkerItemType::Directory => Some(WalkerItemType::File),
+            expected => expected,
+        };
+
+        if let Some(expected) = expected.filter(|expected| *expected != actual) {
+            println!("CHANGED {} (was {}, now {})", path.display(), type_name(expected), type_name(actual));
+            failed += 1;
+        }
+    }
+
+    if failed > 0 {
+        fail!(exit 12, "{} entry(ies) are missing or changed type", failed);
+    }
+
+    std::process::exit(0)
+}
+
+/// (Internal) Get the name of an item type, as displayed in the reports
+fn type_name(item_type: WalkerItemType) -> &'static str {
+    match item_type {
+        WalkerItemType::Directory => "dir",
+        WalkerItemType::File => "file",
+        WalkerItemType::Symlink => "symlink",
+        WalkerItemType::Other => "other",
+    }
+}
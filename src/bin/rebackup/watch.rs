@@ -0,0 +1,143 @@
+//! Watch mode, which keeps the files list up to date (see the `--watch` option)
+//!
+//! Source directories are watched for changes with `notify`, and the files list is rebuilt once changes settled down
+//! (see the `--watch-debounce` option). Changes to items excluded by the rules (and to their descendants) are ignored,
+//! so a busy excluded directory (e.g. a build directory) doesn't trigger rebuilds. The excluded items are determined
+//! again after each rebuild, so items excluded depending on their metadata (e.g. with `--exclude-older-than`) are only
+//! listed again once another change triggered a rebuild.
+//!
+//! The files list is rebuilt by running the program again without watching, so failures of a single walk (e.g. an
+//! item vanishing during the walk) don't stop the watch. The output file is only replaced when the files list changed,
+//! by writing it to a temporary file which is then renamed, so it is never partially written.
+
+use crate::Opts;
+use notify::{Event, EventKind, RecursiveMode, Watcher};
+use rebackup::{debug, err, fail, info, walk_explain, WalkerRuleResult};
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::time::Duration;
+
+/// Default delay without changes before the files list is rebuilt
+const DEFAULT_DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// Rebuild the files list each time the source directories change, until the program is interrupted
+pub fn run(opts: &Opts, sources: &[PathBuf]) -> ! {
+    let output = opts.output.as_ref().expect("Internal error: no output file in watch mode");
+    let debounce = opts.watch_debounce.unwrap_or(DEFAULT_DEBOUNCE);
+
+    let exe = std::env::current_exe().unwrap_or_else(|err| fail!(exit 2, "Failed to get the path of the program: {}", err));
+    let args: Vec<OsString> = std::env::args_os().skip(1).collect();
+
+    let mut tmp_name = output.file_name().map(OsString::from).unwrap_or_default();
+    tmp_name.push(".rebackup-tmp");
+    let tmp = output.with_file_name(tmp_name);
+
+    let (sender, receiver) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(sender).unwrap_or_else(|err| fail!(exit 2, "Failed to watch source directories: {}", err));
+
+    for source in sources {
+        watcher
+            .watch(source, RecursiveMode::Recursive)
+            .unwrap_or_else(|err| fail!(exit 2, "Failed to watch source directory {}: {}", source.display(), err));
+    }
+
+    // Excluded items are only looked for, the rebuilds already explain and time the rules if asked to
+    let mut config = crate::walker_config(opts, false);
+    config.explain = false;
+    config.time_rules = false;
+
+    loop {
+        match rebuild(&exe, &args, output, &tmp) {
+            Ok(true) => info!("Files list was updated"),
+            Ok(false) => debug!("Files list didn't change"),
+            Err(err) => err!("Failed to rebuild the files list: {}", err),
+        }
+
+        // The output file may be located inside a source directory
+        let mut ignored = vec![output.clone(), tmp.clone()];
+
+        for source in sources {
+            match walk_explain(source, &config) {
+                Ok((_, exclusions)) => ignored.extend(
+                    exclusions
+                        .into_iter()
+                        .filter(|(_, reason)| matches!(reason.result, WalkerRuleResult::ExcludeItem))
+                        .map(|(path, _)| path),
+                ),
+                Err(err) => debug!("Failed to find the excluded items of {}: {}", source.display(), err),
+            }
+        }
+
+        info!("Watching source directories for changes...");
+
+        // Wait for a change, then for changes to settle down
+        wait_for_change(&receiver, &ignored, None);
+        while wait_for_change(&receiver, &ignored, Some(debounce)) {}
+    }
+}
+
+/// (Internal) Wait for a change which isn't ignored, returning `false` if none happened before the timeout
+fn wait_for_change(receiver: &Receiver<notify::Result<Event>>, ignored: &[PathBuf], timeout: Option<Duration>) -> bool {
+    loop {
+        let event = match timeout {
+            Some(timeout) => match receiver.recv_timeout(timeout) {
+                Ok(event) => event,
+                Err(RecvTimeoutError::Timeout) => return false,
+                Err(RecvTimeoutError::Disconnected) => fail!(exit 2, "Source directories are not watched anymore"),
+            },
+            None => receiver.recv().unwrap_or_else(|_| fail!(exit 2, "Source directories are not watched anymore")),
+        };
+
+        let event = match event {
+            Ok(event) => event,
+            Err(err) => {
+                err!("Failed to watch source directories: {}", err);
+                continue;
+            }
+        };
+
+        // Items are read while the files list is built, which must not trigger another rebuild
+        if matches!(event.kind, EventKind::Access(_)) {
+            continue;
+        }
+
+        if let Some(path) = event.paths.iter().find(|path| !is_ignored(path, ignored)) {
+            debug!("Change detected: {}", path.display());
+            return true;
+        }
+    }
+}
+
+/// (Internal) Check if a path is ignored, or located inside an ignored directory
+fn is_ignored(path: &Path, ignored: &[PathBuf]) -> bool {
+    ignored.iter().any(|ignored| path.starts_with(ignored))
+}
+
+/// (Internal) Build the files list to a temporary file, and replace the output file with it if the list changed
+fn rebuild(exe: &Path, args: &[OsString], output: &Path, tmp: &PathBuf) -> Result<bool, String> {
+    let status = Command::new(exe)
+        .args(args)
+        .arg("--watch-output")
+        .arg(tmp)
+        .status()
+        .map_err(|err| format!("failed to run the program: {}", err))?;
+
+    // Partial lists (exit code 6) are still written
+    if !status.success() && status.code() != Some(6) {
+        let _ = fs::remove_file(tmp);
+        return Err(format!("program failed ({})", status));
+    }
+
+    let list = fs::read(tmp).map_err(|err| format!("failed to read the temporary files list: {}", err))?;
+
+    if fs::read(output).is_ok_and(|current| current == list) {
+        let _ = fs::remove_file(tmp);
+        return Ok(false);
+    }
+
+    fs::rename(tmp, output).map_err(|err| format!("failed to replace the output file: {}", err))?;
+    Ok(true)
+}
@@ -3,33 +3,911 @@
 //! The walker can be configured through [`WalkerConfig`].
 //! Rules can be defined using [`WalkerRule`].
 
+use crate::filesystem::{FileSystem, RealFs};
+use crate::walker::WalkProgress;
+use std::ffi::OsString;
+use std::fs;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
 
 /// Configuration for ReBackup's walker
 pub struct WalkerConfig {
     /// List of rules to apply on items
     pub rules: Vec<WalkerRule>,
 
-    /// Should the walker follow symbolic links?
-    pub follow_symlinks: bool,
+    /// How the paths of the files list are returned
+    ///
+    /// This only affects the returned items: rules and errors always use absolute paths.
+    pub output_paths: PathStyle,
+
+    /// What to do with symbolic links
+    pub symlink_mode: SymlinkMode,
+
+    /// What to do with followed symbolic links pointing outside of the source directory
+    pub symlink_escape: SymlinkEscapePolicy,
 
     /// Drop empty directoryes
     pub drop_empty_dirs: bool,
+
+    /// Which directories to add to the files list
+    ///
+    /// Directories without any entry are never added when [`WalkerConfig::drop_empty_dirs`] is set.
+    pub emit_directories: DirEmitPolicy,
+
+    /// Types of items to add to the files list (all types by default)
+    ///
+    /// Items of other types are still walked through (directories are traversed and rules apply to them as usual),
+    /// but are not returned.
+    ///
+    /// ```
+    /// use rebackup::{walk, SymlinkMode, WalkerConfig, WalkerItemType, WalkerItemTypes};
+    /// use std::fs;
+    ///
+    /// let dir = std::env::temp_dir().join("rebackup-doctest-output-types");
+    /// # let _ = fs::remove_dir_all(&dir);
+    /// fs::create_dir_all(dir.join("empty")).unwrap();
+    /// fs::create_dir_all(dir.join("sub")).unwrap();
+    /// fs::write(dir.join("sub/file"), "").unwrap();
+    /// # #[cfg(unix)]
+    /// std::os::unix::fs::symlink("sub/file", dir.join("link")).unwrap();
+    ///
+    /// let config = |output_types| {
+    ///     WalkerConfig::builder()
+    ///         .symlink_mode(SymlinkMode::ListAsEntry)
+    ///         .output_types(output_types)
+    ///         .build()
+    /// };
+    ///
+    /// let files = walk(&dir, &config(WalkerItemTypes::only(WalkerItemType::File))).unwrap();
+    /// assert_eq!(files, vec![dir.join("sub/file")]);
+    ///
+    /// let dirs = walk(&dir, &config(WalkerItemTypes::only(WalkerItemType::Directory))).unwrap();
+    /// assert_eq!(dirs, vec![dir.join("empty")]);
+    ///
+    /// # #[cfg(unix)]
+    /// # {
+    /// let mut items = walk(&dir, &config(WalkerItemTypes::of([WalkerItemType::File, WalkerItemType::Symlink]))).unwrap();
+    /// items.sort();
+    /// assert_eq!(items, vec![dir.join("link"), dir.join("sub/file")]);
+    /// # }
+    /// # fs::remove_dir_all(&dir).unwrap();
+    /// ```
+    pub output_types: WalkerItemTypes,
+
+    /// Consider directories whose entries were all excluded (by rules or filters) as empty
+    ///
+    /// By default, a directory is considered empty only if it doesn't have any entry on disk. When this option is enabled,
+    /// it is considered empty if none of its descendants made it to the files list, which makes it subject to
+    /// [`WalkerConfig::drop_empty_dirs`] like any other empty directory.
+    ///
+    /// With the [parallel walker](crate::walk_parallel), directories containing sub-directories are never considered empty.
+    ///
+    /// ```
+    /// use rebackup::{walk, WalkerConfig, WalkerRule};
+    /// use std::fs;
+    ///
+    /// let dir = std::env::temp_dir().join("rebackup-doctest-treat-filtered-as-empty");
+    /// # let _ = fs::remove_dir_all(&dir);
+    /// fs::create_dir_all(dir.join("project/node_modules")).unwrap();
+    /// fs::write(dir.join("project/node_modules/package.json"), "").unwrap();
+    ///
+    /// let dir = fs::canonicalize(&dir).unwrap();
+    /// let config = |treat_filtered_as_empty| {
+    ///     WalkerConfig::builder()
+    ///         .rule(WalkerRule::exclude_dir_named("node_modules"))
+    ///         .treat_filtered_as_empty(treat_filtered_as_empty)
+    ///         .build()
+    /// };
+    ///
+    /// assert!(walk(&dir, &config(false)).unwrap().is_empty());
+    /// assert_eq!(walk(&dir, &config(true)).unwrap(), vec![dir.join("project")]);
+    ///
+    /// let mut config = config(true);
+    /// config.drop_empty_dirs = true;
+    /// assert!(walk(&dir, &config).unwrap().is_empty());
+    /// # fs::remove_dir_all(&dir).unwrap();
+    /// ```
+    pub treat_filtered_as_empty: bool,
+
+    /// Maximum depth to traverse, relative to the source directory (direct children of the source directory have a depth of 0)
+    ///
+    /// Directories located at the maximum depth are not traversed but are added to the files list as entries
+    /// (even if [`WalkerConfig::drop_empty_dirs`] is set, as they are not known to be empty).
+    pub max_depth: Option<usize>,
+
+    /// Maximum number of items in the files list (including mapped items and empty directories)
+    ///
+    /// When the limit is exceeded, the walk stops with a [`WalkerErr::TooManyItems`](crate::WalkerErr::TooManyItems) error.
+    pub max_items: Option<usize>,
+
+    /// Fail with a [`WalkerErr::EmptyResult`](crate::WalkerErr::EmptyResult) error at the end of the walk if no item was found
+    ///
+    /// Mapped items count as found items, but the source directory itself (listed when it is empty) doesn't.
+    ///
+    /// ```
+    /// use rebackup::{walk, WalkerConfig, WalkerErr, WalkerRule};
+    /// use std::fs;
+    ///
+    /// let dir = std::env::temp_dir().join("rebackup-doctest-fail-if-empty");
+    /// # let _ = fs::remove_dir_all(&dir);
+    /// fs::create_dir_all(dir.join("node_modules")).unwrap();
+    ///
+    /// let config = WalkerConfig::builder()
+    ///     .rule(WalkerRule::exclude_dir_named("node_modules"))
+    ///     .fail_if_empty(true)
+    ///     .build();
+    ///
+    /// assert!(matches!(walk(&dir, &config), Err(WalkerErr::EmptyResult { excluded: 1 })));
+    /// # fs::remove_dir_all(&dir).unwrap();
+    /// ```
+    pub fail_if_empty: bool,
+
+    /// Include special files (named pipes, sockets, devices, ...) in the files list
+    ///
+    /// When enabled, rules are run on these items like on any other one.
+    pub include_special_files: bool,
+
+    /// Exclude regular files smaller than this size (in bytes)
+    pub min_file_size: Option<u64>,
+
+    /// Exclude regular files larger than this size (in bytes)
+    pub max_file_size: Option<u64>,
+
+    /// Exclude regular files last modified before this time
+    ///
+    /// Directories are still traversed regardless of their own modification time, so recently modified files inside
+    /// them are still found. Files whose modification time cannot be read are included.
+    ///
+    /// ```
+    /// use rebackup::{walk, WalkerConfig};
+    /// use std::fs::{self, File};
+    /// use std::time::{Duration, SystemTime};
+    ///
+    /// let dir = std::env::temp_dir().join("rebackup-doctest-modified-after");
+    /// # let _ = fs::remove_dir_all(&dir);
+    /// fs::create_dir_all(dir.join("old_dir")).unwrap();
+    /// fs::write(dir.join("old_file"), "").unwrap();
+    /// fs::write(dir.join("old_dir/new_file"), "").unwrap();
+    ///
+    /// let last_run = SystemTime::now() - Duration::from_secs(3600);
+    /// let long_ago = last_run - Duration::from_secs(3600);
+    /// File::open(dir.join("old_file")).unwrap().set_modified(long_ago).unwrap();
+    /// File::open(dir.join("old_dir")).unwrap().set_modified(long_ago).unwrap();
+    ///
+    /// let dir = fs::canonicalize(&dir).unwrap();
+    /// let config = WalkerConfig::builder().modified_after(Some(last_run)).build();
+    /// assert_eq!(walk(&dir, &config).unwrap(), vec![dir.join("old_dir/new_file")]);
+    /// # fs::remove_dir_all(&dir).unwrap();
+    /// ```
+    pub modified_after: Option<SystemTime>,
+
+    /// Exclude regular files last modified after this time
+    ///
+    /// As with [`WalkerConfig::modified_after`], directories are still traversed and files whose modification time
+    /// cannot be read are included.
+    pub modified_before: Option<SystemTime>,
+
+    /// Skip items that cannot be read because of insufficient permissions instead of failing
+    ///
+    /// Permission errors when reading a directory's entries, an item's metadata or a symbolic link's target are logged,
+    /// and the related item is skipped. Other I/O errors are still returned.
+    /// The number of skipped items is available in [`WalkStats::skipped_unreadable`](crate::WalkStats::skipped_unreadable).
+    ///
+    /// ```
+    /// # #[cfg(unix)] {
+    /// use rebackup::{walk, WalkerConfig};
+    /// use std::fs;
+    /// use std::os::unix::fs::PermissionsExt;
+    ///
+    /// let dir = std::env::temp_dir().join("rebackup-doctest-skip-unreadable");
+    /// # let _ = fs::remove_dir_all(&dir);
+    /// fs::create_dir_all(dir.join("locked")).unwrap();
+    /// fs::write(dir.join("locked/file"), "").unwrap();
+    /// fs::write(dir.join("file"), "").unwrap();
+    /// fs::set_permissions(dir.join("locked"), fs::Permissions::from_mode(0o000)).unwrap();
+    ///
+    /// // Permissions are not enforced for privileged users
+    /// if fs::read_dir(dir.join("locked")).is_err() {
+    ///     let dir = fs::canonicalize(&dir).unwrap();
+    ///     assert!(walk(&dir, &WalkerConfig::default()).is_err());
+    ///
+    ///     let config = WalkerConfig::builder().skip_unreadable(true).build();
+    ///     assert_eq!(walk(&dir, &config).unwrap(), vec![dir.join("file")]);
+    /// }
+    ///
+    /// # fs::set_permissions(dir.join("locked"), fs::Permissions::from_mode(0o755)).unwrap();
+    /// # fs::remove_dir_all(&dir).unwrap();
+    /// # }
+    /// ```
+    pub skip_unreadable: bool,
+
+    /// Only include the first path found for files with multiple hard links (only supported on Unix platforms)
+    pub dedup_hard_links: bool,
+
+    /// Sort each directory's entries by file name before traversing them, making the traversal order deterministic
+    pub sort_entries: bool,
+
+    /// Order in which directories are traversed
+    pub strategy: TraversalStrategy,
+
+    /// What to do when an error occurs on a specific item
+    pub on_error: ErrorPolicy,
+
+    /// What to do when a rule maps an item to a list containing items that don't exist (see [`WalkerRuleResult::MapAsList`])
+    pub mapping_validation: MappingValidation,
+
+    /// Optional cancellation flag: when it is set to `true`, the walker stops as soon as possible
+    /// and returns a [`WalkerErr::Cancelled`](crate::WalkerErr::Cancelled) error
+    pub cancel: Option<Arc<AtomicBool>>,
+
+    /// Optional callback to report the walk's progress
+    ///
+    /// It is called each time a directory is entered, as well as regularly while items are treated.
+    pub progress: Option<WalkerProgressFn>,
+
+    /// Measure the time spent in each rule's callbacks (see [`WalkStats`](crate::WalkStats))
+    pub time_rules: bool,
+
+    /// Record the reason why each item was excluded or mapped by a rule (see [`walk_explain`](crate::walk_explain))
+    pub explain: bool,
+
+    /// Filesystem the walker reads items from (the real one by default, see [`FileSystem`])
+    pub filesystem: Arc<dyn FileSystem>,
+
+    /// Check that rules' callbacks don't change the current directory (useful to debug rules, see [`WalkerRule`])
+    ///
+    /// When a callback changed it, the walk fails with a [`WalkerErr::RuleFailedToRun`](crate::WalkerErr::RuleFailedToRun) error.
+    ///
+    /// ```
+    /// use rebackup::config::*;
+    /// use rebackup::{walk, WalkerErr};
+    /// use std::{env, fs};
+    ///
+    /// let dir = std::env::temp_dir().join("rebackup-doctest-strict-rules");
+    /// # let _ = fs::remove_dir_all(&dir);
+    /// fs::create_dir_all(dir.join("sub")).unwrap();
+    ///
+    /// let config = WalkerConfig::builder()
+    ///     .rule(WalkerRule {
+    ///         name: "misbehaving",
+    ///         description: None,
+    ///         priority: 0,
+    ///         only_for: WalkerItemTypes::all(),
+    ///         min_depth: None,
+    ///         max_depth: None,
+    ///         cacheable: false,
+    ///         timeout: None,
+    ///         matches: Box::new(|_| Ok(true)),
+    ///         action: Box::new(|ctx| {
+    ///             env::set_current_dir(ctx.path)?;
+    ///             Ok(WalkerRuleResult::IncludeItem)
+    ///         }),
+    ///     })
+    ///     .strict_rules(true)
+    ///     .build();
+    ///
+    /// assert!(matches!(walk(&dir, &config), Err(WalkerErr::RuleFailedToRun { .. })));
+    /// # fs::remove_dir_all(&dir).unwrap();
+    /// ```
+    pub strict_rules: bool,
 }
 
-/// Create a default configuration from rules
 impl WalkerConfig {
-    pub fn new(rules: Vec<WalkerRule>) -> Self {
+    /// Create a default configuration from rules (either a list of rules or a [`RuleSet`])
+    pub fn new(rules: impl Into<Vec<WalkerRule>>) -> Self {
         Self {
-            rules,
-            follow_symlinks: false,
+            rules: rules.into(),
+            output_paths: PathStyle::Absolute,
+            symlink_mode: SymlinkMode::Skip,
+            symlink_escape: SymlinkEscapePolicy::Allow,
             drop_empty_dirs: false,
+            emit_directories: DirEmitPolicy::OnlyEmpty,
+            output_types: WalkerItemTypes::all(),
+            treat_filtered_as_empty: false,
+            max_depth: None,
+            max_items: None,
+            fail_if_empty: false,
+            include_special_files: false,
+            min_file_size: None,
+            max_file_size: None,
+            modified_after: None,
+            modified_before: None,
+            skip_unreadable: false,
+            dedup_hard_links: false,
+            sort_entries: false,
+            strategy: TraversalStrategy::DepthFirst,
+            on_error: ErrorPolicy::Abort,
+            mapping_validation: MappingValidation::Fail,
+            cancel: None,
+            progress: None,
+            time_rules: false,
+            explain: false,
+            filesystem: Arc::new(RealFs),
+            strict_rules: false,
         }
     }
+
+    /// Create a configuration builder (starting from the default configuration)
+    ///
+    /// ```
+    /// use rebackup::config::*;
+    ///
+    /// let config = WalkerConfig::builder()
+    ///     .rule(WalkerRule {
+    ///         name: "nomedia",
+    ///         description: None,
+    ///         priority: 0,
+    ///         only_for: WalkerItemTypes::only(WalkerItemType::Directory),
+    ///         min_depth: None,
+    ///         max_depth: None,
+    ///         cacheable: false,
+    ///         timeout: None,
+    ///         matches: Box::new(|ctx| Ok(ctx.path.join(".nomedia").is_file())),
+    ///         action: Box::new(|_| Ok(WalkerRuleResult::ExcludeItem)),
+    ///     })
+    ///     .symlink_mode(SymlinkMode::Follow)
+    ///     .drop_empty_dirs(true)
+    ///     .build();
+    ///
+    /// assert_eq!(config.rules.len(), 1);
+    /// assert_eq!(config.symlink_mode, SymlinkMode::Follow);
+    /// assert!(config.drop_empty_dirs);
+    /// assert_eq!(config.max_depth, None);
+    /// ```
+    pub fn builder() -> WalkerConfigBuilder {
+        WalkerConfigBuilder { config: Self::default() }
+    }
+}
+
+/// Create a default configuration without any rule
+///
+/// ```
+/// use rebackup::config::*;
+///
+/// let config = WalkerConfig::default();
+///
+/// assert!(config.rules.is_empty());
+/// assert_eq!(config.symlink_mode, SymlinkMode::Skip);
+/// assert_eq!(config.on_error, ErrorPolicy::Abort);
+/// ```
+impl Default for WalkerConfig {
+    fn default() -> Self {
+        Self::new(vec![])
+    }
+}
+
+/// Builder for [`WalkerConfig`] (see [`WalkerConfig::builder`])
+///
+/// ```
+/// use rebackup::config::*;
+///
+/// let config = WalkerConfig::builder()
+///     .rules(vec![])
+///     .max_depth(Some(2))
+///     .sort_entries(true)
+///     .on_error(ErrorPolicy::Collect)
+///     .build();
+///
+/// assert_eq!(config.max_depth, Some(2));
+/// assert!(config.sort_entries);
+/// assert_eq!(config.on_error, ErrorPolicy::Collect);
+/// ```
+pub struct WalkerConfigBuilder {
+    config: WalkerConfig,
+}
+
+impl WalkerConfigBuilder {
+    /// Add a rule (applied after the previously added ones)
+    pub fn rule(mut self, rule: WalkerRule) -> Self {
+        self.config.rules.push(rule);
+        self
+    }
+
+    /// Add multiple rules (applied after the previously added ones)
+    pub fn rules(mut self, rules: impl IntoIterator<Item = WalkerRule>) -> Self {
+        self.config.rules.extend(rules);
+        self
+    }
+
+    /// Set [`WalkerConfig::output_paths`]
+    pub fn output_paths(mut self, output_paths: PathStyle) -> Self {
+        self.config.output_paths = output_paths;
+        self
+    }
+
+    /// Set [`WalkerConfig::symlink_mode`]
+    pub fn symlink_mode(mut self, symlink_mode: SymlinkMode) -> Self {
+        self.config.symlink_mode = symlink_mode;
+        self
+    }
+
+    /// Set [`WalkerConfig::drop_empty_dirs`]
+    pub fn drop_empty_dirs(mut self, drop_empty_dirs: bool) -> Self {
+        self.config.drop_empty_dirs = drop_empty_dirs;
+        self
+    }
+
+    /// Set [`WalkerConfig::max_depth`]
+    pub fn max_depth(mut self, max_depth: Option<usize>) -> Self {
+        self.config.max_depth = max_depth;
+        self
+    }
+
+    /// Set [`WalkerConfig::max_items`]
+    pub fn max_items(mut self, max_items: Option<usize>) -> Self {
+        self.config.max_items = max_items;
+        self
+    }
+
+    /// Set [`WalkerConfig::fail_if_empty`]
+    pub fn fail_if_empty(mut self, fail_if_empty: bool) -> Self {
+        self.config.fail_if_empty = fail_if_empty;
+        self
+    }
+
+    /// Set [`WalkerConfig::min_file_size`]
+    pub fn min_file_size(mut self, min_file_size: Option<u64>) -> Self {
+        self.config.min_file_size = min_file_size;
+        self
+    }
+
+    /// Set [`WalkerConfig::emit_directories`]
+    pub fn emit_directories(mut self, emit_directories: DirEmitPolicy) -> Self {
+        self.config.emit_directories = emit_directories;
+        self
+    }
+
+    /// Set [`WalkerConfig::output_types`]
+    pub fn output_types(mut self, output_types: WalkerItemTypes) -> Self {
+        self.config.output_types = output_types;
+        self
+    }
+
+    /// Set [`WalkerConfig::treat_filtered_as_empty`]
+    pub fn treat_filtered_as_empty(mut self, treat_filtered_as_empty: bool) -> Self {
+        self.config.treat_filtered_as_empty = treat_filtered_as_empty;
+        self
+    }
+
+    /// Set [`WalkerConfig::max_file_size`]
+    pub fn max_file_size(mut self, max_file_size: Option<u64>) -> Self {
+        self.config.max_file_size = max_file_size;
+        self
+    }
+
+    /// Set [`WalkerConfig::modified_after`]
+    pub fn modified_after(mut self, modified_after: Option<SystemTime>) -> Self {
+        self.config.modified_after = modified_after;
+        self
+    }
+
+    /// Set [`WalkerConfig::modified_before`]
+    pub fn modified_before(mut self, modified_before: Option<SystemTime>) -> Self {
+        self.config.modified_before = modified_before;
+        self
+    }
+
+    /// Set [`WalkerConfig::skip_unreadable`]
+    pub fn skip_unreadable(mut self, skip_unreadable: bool) -> Self {
+        self.config.skip_unreadable = skip_unreadable;
+        self
+    }
+
+    /// Set [`WalkerConfig::include_special_files`]
+    pub fn include_special_files(mut self, include_special_files: bool) -> Self {
+        self.config.include_special_files = include_special_files;
+        self
+    }
+
+    /// Set [`WalkerConfig::dedup_hard_links`]
+    pub fn dedup_hard_links(mut self, dedup_hard_links: bool) -> Self {
+        self.config.dedup_hard_links = dedup_hard_links;
+        self
+    }
+
+    /// Set [`WalkerConfig::sort_entries`]
+    pub fn sort_entries(mut self, sort_entries: bool) -> Self {
+        self.config.sort_entries = sort_entries;
+        self
+    }
+
+    /// Set [`WalkerConfig::strategy`]
+    pub fn strategy(mut self, strategy: TraversalStrategy) -> Self {
+        self.config.strategy = strategy;
+        self
+    }
+
+    /// Set [`WalkerConfig::symlink_escape`]
+    pub fn symlink_escape(mut self, symlink_escape: SymlinkEscapePolicy) -> Self {
+        self.config.symlink_escape = symlink_escape;
+        self
+    }
+
+    /// Set [`WalkerConfig::on_error`]
+    pub fn on_error(mut self, on_error: ErrorPolicy) -> Self {
+        self.config.on_error = on_error;
+        self
+    }
+
+    /// Set [`WalkerConfig::mapping_validation`]
+    pub fn mapping_validation(mut self, mapping_validation: MappingValidation) -> Self {
+        self.config.mapping_validation = mapping_validation;
+        self
+    }
+
+    /// Set [`WalkerConfig::cancel`]
+    pub fn cancel(mut self, cancel: Arc<AtomicBool>) -> Self {
+        self.config.cancel = Some(cancel);
+        self
+    }
+
+    /// Set [`WalkerConfig::strict_rules`]
+    pub fn strict_rules(mut self, strict_rules: bool) -> Self {
+        self.config.strict_rules = strict_rules;
+        self
+    }
+
+    /// Set [`WalkerConfig::filesystem`]
+    pub fn filesystem(mut self, filesystem: impl FileSystem + 'static) -> Self {
+        self.config.filesystem = Arc::new(filesystem);
+        self
+    }
+
+    /// Set [`WalkerConfig::progress`]
+    pub fn progress(mut self, progress: impl Fn(&WalkProgress) + Send + Sync + 'static) -> Self {
+        self.config.progress = Some(Box::new(progress));
+        self
+    }
+
+    /// Set [`WalkerConfig::time_rules`]
+    pub fn time_rules(mut self, time_rules: bool) -> Self {
+        self.config.time_rules = time_rules;
+        self
+    }
+
+    /// Set [`WalkerConfig::explain`]
+    pub fn explain(mut self, explain: bool) -> Self {
+        self.config.explain = explain;
+        self
+    }
+
+    /// Build the configuration
+    pub fn build(self) -> WalkerConfig {
+        self.config
+    }
+}
+
+/// Behavior of the walker when an error occurs on a specific item
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorPolicy {
+    /// Stop the walk and return the error
+    Abort,
+
+    /// Skip the faulty item and go on with the walk (only for recoverable errors, see [`WalkerErr::is_recoverable`](crate::WalkerErr::is_recoverable))
+    Collect,
+}
+
+/// Behavior of the walker when a rule maps an item to a list containing items that don't exist
+/// (see [`WalkerConfig::mapping_validation`])
+///
+/// Mapped items located outside of the item they were mapped from always make the walk fail.
+///
+/// ```
+/// use rebackup::config::*;
+/// use rebackup::{walk, WalkerErr};
+/// use std::fs;
+///
+/// let dir = std::env::temp_dir().join("rebackup-doctest-mapping-validation");
+/// # let _ = fs::remove_dir_all(&dir);
+/// fs::create_dir_all(dir.join("tmp")).unwrap();
+///
+/// let dir = fs::canonicalize(&dir).unwrap();
+/// let mut config = WalkerConfig::new(vec![WalkerRule {
+///     name: "listing",
+///     description: None,
+///     priority: 0,
+///     only_for: WalkerItemTypes::only(WalkerItemType::Directory),
+///     min_depth: None,
+///     max_depth: None,
+///     cacheable: false,
+///     timeout: None,
+///     matches: Box::new(|_| Ok(true)),
+///     action: Box::new(|ctx| {
+///         fs::write(ctx.path.join("kept"), "")?;
+///         fs::write(ctx.path.join("removed"), "")?;
+///         let listing = vec![ctx.path.join("kept"), ctx.path.join("removed")];
+///
+///         // The file disappears before the mapping is validated
+///         fs::remove_file(ctx.path.join("removed"))?;
+///         Ok(WalkerRuleResult::MapAsList(listing, true))
+///     }),
+/// }]);
+///
+/// assert!(matches!(walk(&dir, &config), Err(WalkerErr::RuleMappingContainsNonExistingItem { .. })));
+///
+/// config.mapping_validation = MappingValidation::SkipMissing;
+/// assert_eq!(walk(&dir, &config).unwrap(), vec![dir.join("tmp/kept")]);
+///
+/// config.mapping_validation = MappingValidation::KeepUnchecked;
+/// assert_eq!(walk(&dir, &config).unwrap(), vec![dir.join("tmp/kept"), dir.join("tmp/removed")]);
+/// # fs::remove_dir_all(&dir).unwrap();
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MappingValidation {
+    /// Stop the walk with a [`WalkerErr::RuleMappingContainsNonExistingItem`](crate::WalkerErr::RuleMappingContainsNonExistingItem) error
+    Fail,
+
+    /// Log an error and drop the missing items from the mapping
+    SkipMissing,
+
+    /// Don't check that mapped items exist, and list them as they are (for tools that handle missing files)
+    ///
+    /// Missing items are reported with the type and metadata of the directory they were mapped relatively to
+    /// (see [`walk_detailed`](crate::walk_detailed)).
+    KeepUnchecked,
 }
 
+/// Behavior of the walker with symbolic links (see [`WalkerConfig::symlink_mode`])
+///
+/// ```
+/// # #[cfg(unix)] {
+/// use rebackup::{walk, SymlinkMode, WalkerConfig};
+/// use std::fs;
+///
+/// let dir = std::env::temp_dir().join("rebackup-doctest-symlink-mode");
+/// # let _ = fs::remove_dir_all(&dir);
+/// fs::create_dir_all(dir.join("dir")).unwrap();
+/// fs::write(dir.join("dir/file"), "").unwrap();
+/// std::os::unix::fs::symlink("dir", dir.join("dir_link")).unwrap();
+/// std::os::unix::fs::symlink("dir/file", dir.join("file_link")).unwrap();
+/// std::os::unix::fs::symlink("missing", dir.join("broken_link")).unwrap();
+///
+/// let dir = fs::canonicalize(&dir).unwrap();
+/// let list = |symlink_mode| {
+///     let mut config = WalkerConfig::builder().symlink_mode(symlink_mode).sort_entries(true).build();
+///     config.on_error = rebackup::ErrorPolicy::Collect;
+///
+///     let mut items: Vec<_> = walk(&dir, &config).unwrap().into_iter()
+///         .map(|path| path.strip_prefix(&dir).unwrap().to_string_lossy().into_owned())
+///         .collect();
+///
+///     items.sort();
+///     items
+/// };
+///
+/// assert_eq!(list(SymlinkMode::Skip), vec!["dir/file"]);
+/// assert_eq!(list(SymlinkMode::ListAsEntry), vec!["broken_link", "dir/file", "dir_link", "file_link"]);
+///
+/// // Items are only listed once (even through symbolic links), and the broken link can't be followed
+/// assert_eq!(list(SymlinkMode::Follow), vec!["dir/file"]);
+/// # fs::remove_dir_all(&dir).unwrap();
+/// # }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymlinkMode {
+    /// Ignore symbolic links
+    Skip,
+
+    /// Add symbolic links to the files list as entries, without following them (rules are still run on them)
+    ListAsEntry,
+
+    /// Follow symbolic links, traversing the directories they point to
+    Follow,
+}
+
+/// Order in which directories are traversed (see [`WalkerConfig::strategy`])
+///
+/// Rules and filters behave identically with both strategies, only the order of the files list changes.
+///
+/// ```
+/// use rebackup::{walk, TraversalStrategy, WalkerConfig};
+/// use std::fs;
+///
+/// let dir = std::env::temp_dir().join("rebackup-doctest-strategy");
+/// # let _ = fs::remove_dir_all(&dir);
+/// fs::create_dir_all(dir.join("a/sub")).unwrap();
+/// fs::create_dir_all(dir.join("b")).unwrap();
+/// fs::write(dir.join("a/file"), "").unwrap();
+/// fs::write(dir.join("a/sub/file"), "").unwrap();
+/// fs::write(dir.join("b/file"), "").unwrap();
+///
+/// let dir = fs::canonicalize(&dir).unwrap();
+/// let list = |strategy| {
+///     let config = WalkerConfig::builder().strategy(strategy).sort_entries(true).build();
+///
+///     walk(&dir, &config).unwrap().into_iter()
+///         .map(|path| path.strip_prefix(&dir).unwrap().to_string_lossy().into_owned())
+///         .collect::<Vec<_>>()
+/// };
+///
+/// assert_eq!(list(TraversalStrategy::DepthFirst), vec!["a/file", "a/sub/file", "b/file"]);
+/// assert_eq!(list(TraversalStrategy::BreadthFirst), vec!["a/file", "b/file", "a/sub/file"]);
+/// # fs::remove_dir_all(&dir).unwrap();
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TraversalStrategy {
+    /// Traverse each directory entirely before going on with its next sibling
+    DepthFirst,
+
+    /// Traverse all the directories of a given depth before the ones below them, which gives an early coverage
+    /// of the whole tree (with [`walk_iter`](crate::walk_iter))
+    ///
+    /// Directories containing sub-directories are never considered empty
+    /// with [`WalkerConfig::treat_filtered_as_empty`].
+    BreadthFirst,
+}
+
+/// Style of the paths of the files list (see [`WalkerConfig::output_paths`])
+///
+/// ```
+/// use rebackup::{walk, PathStyle, WalkerConfig};
+/// use std::fs;
+/// use std::path::PathBuf;
+///
+/// let dir = std::env::temp_dir().join("rebackup-doctest-output-paths");
+/// # let _ = fs::remove_dir_all(&dir);
+/// fs::create_dir_all(dir.join("dir")).unwrap();
+/// fs::write(dir.join("dir/file"), "").unwrap();
+///
+/// let config = WalkerConfig::builder().output_paths(PathStyle::RelativeToSource).build();
+/// assert_eq!(walk(&dir, &config).unwrap(), vec![PathBuf::from("dir/file")]);
+/// # fs::remove_dir_all(&dir).unwrap();
+/// ```
+///
+/// Items mapped by rules are always reported inside the source directory, even when they are provided through
+/// their canonical path while being reached through a symbolic link:
+///
+/// ```
+/// # #[cfg(unix)] {
+/// use rebackup::config::*;
+/// use rebackup::walk;
+/// use std::fs;
+/// use std::path::PathBuf;
+///
+/// let dir = std::env::temp_dir().join("rebackup-doctest-output-paths-mapped");
+/// # let _ = fs::remove_dir_all(&dir);
+/// fs::create_dir_all(dir.join("outside/sub")).unwrap();
+/// fs::create_dir_all(dir.join("source")).unwrap();
+/// fs::write(dir.join("outside/sub/file"), "").unwrap();
+/// std::os::unix::fs::symlink(dir.join("outside"), dir.join("source/link")).unwrap();
+///
+/// let config = WalkerConfig::builder()
+///     .rule(WalkerRule {
+///         name: "map-canonical",
+///         description: None,
+///         priority: 0,
+///         only_for: WalkerItemTypes::only(WalkerItemType::Symlink),
+///         min_depth: None,
+///         max_depth: None,
+///         cacheable: false,
+///         timeout: None,
+///         matches: Box::new(|_| Ok(true)),
+///         action: Box::new(|ctx| Ok(WalkerRuleResult::MapAsList(vec![fs::canonicalize(ctx.path)?.join("sub/file")], true))),
+///     })
+///     .symlink_mode(SymlinkMode::Follow)
+///     .output_paths(PathStyle::RelativeToSource)
+///     .build();
+///
+/// assert_eq!(walk(&dir.join("source"), &config).unwrap(), vec![PathBuf::from("link/sub/file")]);
+/// # fs::remove_dir_all(&dir).unwrap();
+/// # }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PathStyle {
+    /// Absolute paths (the source directory being canonicalized)
+    Absolute,
+
+    /// Paths relative to the source directory
+    ///
+    /// Items which are not located inside the source directory (e.g. items renamed to an absolute path elsewhere)
+    /// make the walk fail with a [`WalkerErr::ItemOutsideSource`](crate::WalkerErr::ItemOutsideSource) error.
+    RelativeToSource,
+}
+
+/// Directories to add to the files list (see [`WalkerConfig::emit_directories`])
+///
+/// ```
+/// use rebackup::{walk, DirEmitPolicy, WalkerConfig};
+/// use std::fs;
+///
+/// let dir = std::env::temp_dir().join("rebackup-doctest-emit-directories");
+/// # let _ = fs::remove_dir_all(&dir);
+/// fs::create_dir_all(dir.join("a/b/empty")).unwrap();
+/// fs::write(dir.join("a/file"), "").unwrap();
+/// fs::write(dir.join("a/b/file"), "").unwrap();
+///
+/// let dir = fs::canonicalize(&dir).unwrap();
+/// let list = |emit_directories| {
+///     let config = WalkerConfig::builder().emit_directories(emit_directories).build();
+///
+///     walk(&dir, &config).unwrap().into_iter()
+///         .map(|path| path.strip_prefix(&dir).unwrap().to_string_lossy().into_owned())
+///         .collect::<Vec<_>>()
+/// };
+///
+/// let mut only_empty = list(DirEmitPolicy::OnlyEmpty);
+/// only_empty.sort();
+/// assert_eq!(only_empty, vec!["a/b/empty", "a/b/file", "a/file"]);
+///
+/// // Directories are listed before their children
+/// let all = list(DirEmitPolicy::All);
+/// let position = |path| all.iter().position(|item| item == path).unwrap();
+/// assert_eq!(all.len(), 5);
+/// assert!(position("a") < position("a/file"));
+/// assert!(position("a") < position("a/b"));
+/// assert!(position("a/b") < position("a/b/empty"));
+/// assert!(position("a/b") < position("a/b/file"));
+///
+/// let mut none = list(DirEmitPolicy::None);
+/// none.sort();
+/// assert_eq!(none, vec!["a/b/file", "a/file"]);
+/// # fs::remove_dir_all(&dir).unwrap();
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DirEmitPolicy {
+    /// Only add directories without any entry (which would be lost otherwise)
+    OnlyEmpty,
+
+    /// Add every traversed directory (except the source directory), before its entries
+    All,
+
+    /// Don't add any directory
+    None,
+}
+
+/// Behavior of the walker when a followed symbolic link points outside of the source directory
+/// (see [`WalkerConfig::symlink_escape`])
+///
+/// ```
+/// # #[cfg(unix)] {
+/// use rebackup::{walk, SymlinkEscapePolicy, SymlinkMode, WalkerConfig, WalkerErr};
+/// use std::fs;
+///
+/// let dir = std::env::temp_dir().join("rebackup-doctest-symlink-escape");
+/// # let _ = fs::remove_dir_all(&dir);
+/// fs::create_dir_all(dir.join("source")).unwrap();
+/// fs::create_dir_all(dir.join("outside")).unwrap();
+/// fs::write(dir.join("outside/file"), "").unwrap();
+/// std::os::unix::fs::symlink(dir.join("outside"), dir.join("source/link")).unwrap();
+///
+/// let dir = fs::canonicalize(&dir).unwrap();
+/// let source = dir.join("source");
+///
+/// let mut config = WalkerConfig::builder().symlink_mode(SymlinkMode::Follow).build();
+/// assert_eq!(walk(&source, &config).unwrap(), vec![source.join("link/file")]);
+///
+/// config.symlink_escape = SymlinkEscapePolicy::Skip;
+/// assert!(walk(&source, &config).unwrap().is_empty());
+///
+/// config.symlink_escape = SymlinkEscapePolicy::Error;
+/// assert!(matches!(walk(&source, &config), Err(WalkerErr::SymlinkEscapesSource(_, _))));
+/// # fs::remove_dir_all(&dir).unwrap();
+/// # }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymlinkEscapePolicy {
+    /// Follow the symbolic link as any other one
+    Allow,
+
+    /// Log and ignore the symbolic link
+    Skip,
+
+    /// Stop the walk with a [`WalkerErr::SymlinkEscapesSource`](crate::WalkerErr::SymlinkEscapesSource) error
+    Error,
+}
+
+/// Progress callback of the walker (see [`WalkerConfig::progress`])
+pub type WalkerProgressFn = Box<dyn Fn(&WalkProgress) + Send + Sync>;
+
 /// Walker rule (run on individual items)
 ///
+/// Rules may be run concurrently (e.g. by [`walk_parallel`](crate::walk_parallel)) and alongside other walks, so their
+/// callbacks must not change the process' global state, like the current directory or the environment variables
+/// (e.g. use [`Command::current_dir`](std::process::Command::current_dir) instead of [`std::env::set_current_dir`]).
+/// Changes of the current directory can be detected with [`WalkerConfig::strict_rules`].
+///
 /// ```
 /// use rebackup::config::*;
 ///
@@ -40,15 +918,23 @@ impl WalkerConfig {
 ///     // Optional description of the rule
 ///     description: None,
 ///
-///     // The type of items the rule applies to (`None` for all)
-///     only_for: Some(WalkerItemType::Directory),
+///     // Priority of the rule (rules with a higher priority are run first)
+///     priority: 0,
+///
+///     // The types of items the rule applies to (`WalkerItemTypes::all()` for all)
+///     only_for: WalkerItemTypes::only(WalkerItemType::Directory),
+///     min_depth: None,
+///     max_depth: None,
+///     cacheable: false,
+///     timeout: None,
 ///
 ///     // Check if the rule would match a specific item
-///     matches: Box::new(|path, _, _| path.join(".nomedia").is_file()),
+///     matches: Box::new(|ctx| Ok(ctx.path.join(".nomedia").is_file())),
 ///
 ///     // Apply the rule to determine what to do
-///     action: Box::new(|_, _, _| Ok(WalkerRuleResult::ExcludeItem)),
+///     action: Box::new(|_| Ok(WalkerRuleResult::ExcludeItem)),
 /// };
+/// ```
 pub struct WalkerRule {
     /// Rule's name
     pub name: &'static str,
@@ -56,19 +942,452 @@ pub struct WalkerRule {
     /// Rule's optional description
     pub description: Option<String>,
 
-    /// Indicate if the rule should only be applied on a specific type of filesystem items
-    pub only_for: Option<WalkerItemType>,
+    /// Rule's priority (`0` by default)
+    ///
+    /// Rules are run by decreasing priority, and rules with the same priority are run in the order they were provided in.
+    /// As [`WalkerRuleResult::IncludeItemAbsolute`] (and other results that ignore all following rules) prevents the rules
+    /// run after it from being evaluated at all, giving a higher priority to such a rule makes it win over other rules,
+    /// like an exclusion rule with a lower priority.
+    pub priority: i32,
+
+    /// Types of filesystem items the rule should be applied on
+    pub only_for: WalkerItemTypes,
+
+    /// Minimum depth of the items the rule should be applied on (see [`RuleCtx::depth`])
+    pub min_depth: Option<usize>,
+
+    /// Maximum depth of the items the rule should be applied on (see [`RuleCtx::depth`])
+    ///
+    /// ```
+    /// use rebackup::config::*;
+    /// use rebackup::walk;
+    /// use std::fs;
+    ///
+    /// let dir = std::env::temp_dir().join("rebackup-doctest-rule-depth");
+    /// # let _ = fs::remove_dir_all(&dir);
+    /// fs::create_dir_all(dir.join("tmp")).unwrap();
+    /// fs::create_dir_all(dir.join("sub/tmp")).unwrap();
+    /// fs::write(dir.join("tmp/file"), "").unwrap();
+    /// fs::write(dir.join("sub/tmp/file"), "").unwrap();
+    ///
+    /// // Only exclude the "tmp" directory located directly inside the source directory
+    /// let config = WalkerConfig::new(vec![WalkerRule {
+    ///     max_depth: Some(0),
+    ///     ..WalkerRule::exclude_dir_named("tmp")
+    /// }]);
+    ///
+    /// let dir = fs::canonicalize(&dir).unwrap();
+    /// assert_eq!(walk(&dir, &config).unwrap(), vec![dir.join("sub/tmp/file")]);
+    /// # fs::remove_dir_all(&dir).unwrap();
+    /// ```
+    pub max_depth: Option<usize>,
+
+    /// Indicate the result of the predicate ([`WalkerRule::matches`]) only depends on the item's parent directory
+    ///
+    /// When enabled, the predicate is only run on the first item of each directory (among the ones the rule applies on),
+    /// and its result is reused for all the other items of the same directory. This avoids hitting the disk repeatedly for
+    /// predicates checking e.g. the presence of a marker file in the parent directory.
+    ///
+    /// **This must only be enabled for predicates which don't depend on the item itself** (its name, type or metadata),
+    /// otherwise the rule will be run on the wrong items.
+    ///
+    /// ```
+    /// use rebackup::config::*;
+    /// use rebackup::walk;
+    /// use std::fs;
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    /// use std::sync::Arc;
+    ///
+    /// let dir = std::env::temp_dir().join("rebackup-doctest-cacheable");
+    /// # let _ = fs::remove_dir_all(&dir);
+    /// fs::create_dir_all(dir.join("sub")).unwrap();
+    ///
+    /// for name in &["a", "b", "c", "sub/a", "sub/b"] {
+    ///     fs::write(dir.join(name), "").unwrap();
+    /// }
+    ///
+    /// let calls = Arc::new(AtomicUsize::new(0));
+    /// let counter = Arc::clone(&calls);
+    ///
+    /// let config = WalkerConfig::new(vec![WalkerRule {
+    ///     name: "counter",
+    ///     description: None,
+    ///     priority: 0,
+    ///     only_for: WalkerItemTypes::only(WalkerItemType::File),
+    ///     min_depth: None,
+    ///     max_depth: None,
+    ///     cacheable: true,
+    ///     timeout: None,
+    ///     matches: Box::new(move |ctx| {
+    ///         counter.fetch_add(1, Ordering::SeqCst);
+    ///         Ok(ctx.path.parent().unwrap().join("sub").is_dir())
+    ///     }),
+    ///     action: Box::new(|_| Ok(WalkerRuleResult::ExcludeItem)),
+    /// }]);
+    ///
+    /// let dir = fs::canonicalize(&dir).unwrap();
+    /// let mut items = walk(&dir, &config).unwrap();
+    /// items.sort();
+    ///
+    /// assert_eq!(items, vec![dir.join("sub/a"), dir.join("sub/b")]);
+    /// assert_eq!(calls.load(Ordering::SeqCst), 2);
+    /// # fs::remove_dir_all(&dir).unwrap();
+    /// ```
+    pub cacheable: bool,
+
+    /// Maximum duration of each of the rule's callbacks (none by default)
+    ///
+    /// When a callback takes longer than this, the walk fails with a [`WalkerRuleErr::TimedOut`](crate::WalkerRuleErr::TimedOut)
+    /// error, even if the callback succeeded. The same error is reported when a callback fails with an I/O error of kind
+    /// [`TimedOut`](std::io::ErrorKind::TimedOut).
+    ///
+    /// As callbacks can't be interrupted, the walker only checks their duration once they returned: callbacks which may hang
+    /// (e.g. running an external command) must enforce the timeout themselves.
+    ///
+    /// ```
+    /// use rebackup::config::*;
+    /// use rebackup::{walk, WalkerErr, WalkerRuleErr};
+    /// use std::fs;
+    /// use std::thread;
+    /// use std::time::Duration;
+    ///
+    /// let dir = std::env::temp_dir().join("rebackup-doctest-rule-timeout");
+    /// # let _ = fs::remove_dir_all(&dir);
+    /// fs::create_dir_all(&dir).unwrap();
+    /// fs::write(dir.join("file"), "").unwrap();
+    ///
+    /// let config = WalkerConfig::new(vec![WalkerRule {
+    ///     name: "slow",
+    ///     description: None,
+    ///     priority: 0,
+    ///     only_for: WalkerItemTypes::all(),
+    ///     min_depth: None,
+    ///     max_depth: None,
+    ///     cacheable: false,
+    ///     timeout: Some(Duration::from_millis(50)),
+    ///     matches: Box::new(|_| Ok(true)),
+    ///     action: Box::new(|_| {
+    ///         thread::sleep(Duration::from_millis(200));
+    ///         Ok(WalkerRuleResult::IncludeItem)
+    ///     }),
+    /// }]);
+    ///
+    /// assert!(matches!(
+    ///     walk(&dir, &config),
+    ///     Err(WalkerErr::RuleFailedToRun { err: WalkerRuleErr::TimedOut(_), .. })
+    /// ));
+    /// # fs::remove_dir_all(&dir).unwrap();
+    /// ```
+    pub timeout: Option<Duration>,
 
     /// Predicate to indicate if the rule should be run on a specific item.
     /// The checking should be as fast as possible, the goal of this callback being to not having as much overhad as `action`.
     ///
-    /// Arguments are the item's absolute path, the walker's configuration, as well as the source directory (absolute, canonicalized)
-    pub matches: Box<dyn Fn(&Path, &WalkerConfig, &Path) -> bool>,
+    /// The argument is the [context](RuleCtx) of the item the rule may be run on.
+    /// If the predicate fails, the walker fails with a [`WalkerErr::RuleFailedToRun`](crate::WalkerErr::RuleFailedToRun) error.
+    pub matches: WalkerRuleMatcher,
 
     /// Action to perform when the rule is applies on a specific item
     ///
-    /// Arguments are the item's absolute path, the walker's configuration, as well as the source directory (absolute, canonicalized)
-    pub action: Box<dyn Fn(&Path, &WalkerConfig, &Path) -> Result<WalkerRuleResult, std::io::Error>>,
+    /// The argument is the [context](RuleCtx) of the item the rule is run on
+    pub action: WalkerRuleAction,
+}
+
+impl WalkerRule {
+    /// Exclude directories with a specific name
+    ///
+    /// ```
+    /// use rebackup::{walk, WalkerConfig, WalkerRule};
+    /// use std::fs;
+    ///
+    /// let dir = std::env::temp_dir().join("rebackup-doctest-rule-constructors");
+    /// # let _ = fs::remove_dir_all(&dir);
+    /// fs::create_dir_all(dir.join("node_modules")).unwrap();
+    /// fs::create_dir_all(dir.join("photos")).unwrap();
+    /// fs::create_dir_all(dir.join("private")).unwrap();
+    /// fs::write(dir.join("node_modules/package.json"), "").unwrap();
+    /// fs::write(dir.join("photos/holidays.jpg"), "").unwrap();
+    /// fs::write(dir.join("photos/notes.txt"), "").unwrap();
+    /// fs::write(dir.join("private/.nomedia"), "").unwrap();
+    /// fs::write(dir.join("private/secret.png"), "").unwrap();
+    ///
+    /// let config = WalkerConfig::new(vec![
+    ///     WalkerRule::exclude_dir_named("node_modules"),
+    ///     WalkerRule::exclude_if_contains(".nomedia"),
+    ///     WalkerRule::include_only_extensions(&["jpg", "png"]),
+    /// ]);
+    ///
+    /// let dir = fs::canonicalize(&dir).unwrap();
+    /// assert_eq!(walk(&dir, &config).unwrap(), vec![dir.join("photos/holidays.jpg")]);
+    /// # fs::remove_dir_all(&dir).unwrap();
+    /// ```
+    pub fn exclude_dir_named(name: &str) -> Self {
+        let name = OsString::from(name);
+
+        Self {
+            name: "exclude_dir_named",
+            description: Some(format!("Directory name: {}", name.to_string_lossy())),
+            priority: 0,
+            only_for: WalkerItemTypes::only(WalkerItemType::Directory),
+            min_depth: None,
+            max_depth: None,
+            cacheable: false,
+            timeout: None,
+            matches: Box::new(move |ctx| Ok(ctx.path.file_name() == Some(name.as_os_str()))),
+            action: Box::new(|_| Ok(WalkerRuleResult::ExcludeItem)),
+        }
+    }
+
+    /// Exclude items matching a glob pattern, relative to the source directory (requires the `glob` feature)
+    ///
+    /// ```
+    /// use rebackup::{walk, WalkerConfig, WalkerRule};
+    /// use std::fs;
+    ///
+    /// let dir = std::env::temp_dir().join("rebackup-doctest-exclude-glob");
+    /// # let _ = fs::remove_dir_all(&dir);
+    /// fs::create_dir_all(dir.join("build/out")).unwrap();
+    /// fs::create_dir_all(dir.join("src")).unwrap();
+    /// fs::write(dir.join("build/out/app"), "").unwrap();
+    /// fs::write(dir.join("notes.tmp"), "").unwrap();
+    /// fs::write(dir.join("src/main.rs"), "").unwrap();
+    /// fs::write(dir.join("src/main.rs.tmp"), "").unwrap();
+    ///
+    /// let config = WalkerConfig::new(vec![
+    ///     WalkerRule::exclude_glob("**/*.tmp").unwrap(),
+    ///     WalkerRule::exclude_glob("build").unwrap(),
+    /// ]);
+    ///
+    /// let dir = fs::canonicalize(&dir).unwrap();
+    /// assert_eq!(walk(&dir, &config).unwrap(), vec![dir.join("src/main.rs")]);
+    ///
+    /// assert!(WalkerRule::exclude_glob("[").is_err());
+    /// # fs::remove_dir_all(&dir).unwrap();
+    /// ```
+    #[cfg(feature = "glob")]
+    pub fn exclude_glob(pattern: &str) -> Result<Self, glob::PatternError> {
+        let pattern = glob::Pattern::new(pattern)?;
+
+        Ok(Self {
+            name: "exclude_glob",
+            description: Some(format!("Pattern: {}", pattern)),
+            priority: 0,
+            only_for: WalkerItemTypes::all(),
+            min_depth: None,
+            max_depth: None,
+            cacheable: false,
+            timeout: None,
+            matches: Box::new(move |ctx| Ok(ctx.path.strip_prefix(ctx.source).is_ok_and(|path| pattern.matches_path(path)))),
+            action: Box::new(|_| Ok(WalkerRuleResult::ExcludeItem)),
+        })
+    }
+
+    /// Exclude directories containing an item with a specific name (e.g. `.nomedia`)
+    pub fn exclude_if_contains(name: &str) -> Self {
+        let name = name.to_string();
+
+        Self {
+            name: "exclude_if_contains",
+            description: Some(format!("Contains: {}", name)),
+            priority: 0,
+            only_for: WalkerItemTypes::only(WalkerItemType::Directory),
+            min_depth: None,
+            max_depth: None,
+            cacheable: false,
+            timeout: None,
+            matches: Box::new(move |ctx| Ok(ctx.path.join(&name).symlink_metadata().is_ok())),
+            action: Box::new(|_| Ok(WalkerRuleResult::ExcludeItem)),
+        }
+    }
+
+    /// Only include files with one of the provided extensions (compared case-insensitively);
+    /// other types of items are not affected
+    pub fn include_only_extensions(extensions: &[&str]) -> Self {
+        let extensions: Vec<String> = extensions.iter().map(|ext| ext.to_lowercase()).collect();
+
+        Self {
+            name: "include_only_extensions",
+            description: Some(format!("Extensions: {}", extensions.join(", "))),
+            priority: 0,
+            only_for: WalkerItemTypes::only(WalkerItemType::File),
+            min_depth: None,
+            max_depth: None,
+            cacheable: false,
+            timeout: None,
+            matches: Box::new(move |ctx| {
+                let ext = ctx.path.extension().map(|ext| ext.to_string_lossy().to_lowercase());
+                Ok(!matches!(ext, Some(ext) if extensions.contains(&ext)))
+            }),
+            action: Box::new(|_| Ok(WalkerRuleResult::ExcludeItem)),
+        }
+    }
+}
+
+/// Set of [rules](WalkerRule) organized in named groups, which can be enabled or disabled individually
+///
+/// Groups are enabled when they are created. Only the rules of enabled groups are kept when the set
+/// is converted to a list of rules (see [`RuleSet::into_rules`]).
+///
+/// ```
+/// use rebackup::config::*;
+///
+/// let mut rules = RuleSet::new();
+///
+/// rules.add("media", WalkerRule {
+///     name: "nomedia",
+///     description: None,
+///     priority: 0,
+///     only_for: WalkerItemTypes::only(WalkerItemType::Directory),
+///     min_depth: None,
+///     max_depth: None,
+///     cacheable: false,
+///     timeout: None,
+///     matches: Box::new(|ctx| Ok(ctx.path.join(".nomedia").is_file())),
+///     action: Box::new(|_| Ok(WalkerRuleResult::ExcludeItem)),
+/// });
+///
+/// assert!(rules.is_enabled("media"));
+///
+/// rules.disable("media");
+///
+/// let config = WalkerConfig::new(rules);
+/// assert!(config.rules.is_empty());
+/// ```
+#[derive(Default)]
+pub struct RuleSet {
+    /// Groups of rules, in the order they were created in
+    groups: Vec<RuleGroup>,
+}
+
+/// (Internal) Named group of rules in a [`RuleSet`]
+struct RuleGroup {
+    name: String,
+    enabled: bool,
+    rules: Vec<WalkerRule>,
+}
+
+impl RuleSet {
+    /// Create an empty set of rules
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a rule to a group (the group is created if it doesn't exist yet)
+    pub fn add(&mut self, group: &str, rule: WalkerRule) -> &mut Self {
+        match self.groups.iter_mut().find(|candidate| candidate.name == group) {
+            Some(group) => group.rules.push(rule),
+            None => self.groups.push(RuleGroup {
+                name: group.to_string(),
+                enabled: true,
+                rules: vec![rule],
+            }),
+        }
+
+        self
+    }
+
+    /// Enable a group of rules (no effect if the group doesn't exist)
+    pub fn enable(&mut self, group: &str) -> &mut Self {
+        self.set_enabled(group, true)
+    }
+
+    /// Disable a group of rules (no effect if the group doesn't exist)
+    pub fn disable(&mut self, group: &str) -> &mut Self {
+        self.set_enabled(group, false)
+    }
+
+    /// Check if a group exists and is enabled
+    pub fn is_enabled(&self, group: &str) -> bool {
+        self.groups.iter().any(|candidate| candidate.name == group && candidate.enabled)
+    }
+
+    /// Get the name of all groups, in the order they were created in
+    pub fn groups(&self) -> impl Iterator<Item = &str> {
+        self.groups.iter().map(|group| group.name.as_str())
+    }
+
+    /// Merge another set into this one
+    ///
+    /// The other set's rules are added after this set's ones. When both sets have a group with the same name,
+    /// the rules are added to the existing group, which keeps its current state.
+    pub fn merge(&mut self, other: RuleSet) -> &mut Self {
+        for group in other.groups {
+            match self.groups.iter_mut().find(|candidate| candidate.name == group.name) {
+                Some(existing) => existing.rules.extend(group.rules),
+                None => self.groups.push(group),
+            }
+        }
+
+        self
+    }
+
+    /// Get the rules of all enabled groups
+    pub fn into_rules(self) -> Vec<WalkerRule> {
+        self.groups.into_iter().filter(|group| group.enabled).flat_map(|group| group.rules).collect()
+    }
+
+    /// (Internal) Enable or disable a group of rules
+    fn set_enabled(&mut self, group: &str, enabled: bool) -> &mut Self {
+        if let Some(group) = self.groups.iter_mut().find(|candidate| candidate.name == group) {
+            group.enabled = enabled;
+        }
+
+        self
+    }
+}
+
+impl From<RuleSet> for Vec<WalkerRule> {
+    fn from(rules: RuleSet) -> Self {
+        rules.into_rules()
+    }
+}
+
+impl IntoIterator for RuleSet {
+    type Item = WalkerRule;
+    type IntoIter = std::vec::IntoIter<WalkerRule>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.into_rules().into_iter()
+    }
+}
+
+/// Predicate of a [`WalkerRule`] (see [`WalkerRule::matches`])
+pub type WalkerRuleMatcher = Box<dyn Fn(&RuleCtx) -> Result<bool, std::io::Error> + Send + Sync>;
+
+/// Action of a [`WalkerRule`] (see [`WalkerRule::action`])
+pub type WalkerRuleAction = Box<dyn Fn(&RuleCtx) -> Result<WalkerRuleResult, std::io::Error> + Send + Sync>;
+
+// Ensure configurations can be built on a thread and used on another one, or shared between threads (see `walk_parallel`)
+const _: fn() = || {
+    fn assert_send_sync<T: Send + Sync>() {}
+
+    assert_send_sync::<WalkerConfig>();
+    assert_send_sync::<WalkerRule>();
+    assert_send_sync::<RuleSet>();
+};
+
+/// Context provided to a [rule](WalkerRule)'s callbacks
+pub struct RuleCtx<'a> {
+    /// Item's absolute path
+    pub path: &'a Path,
+
+    /// Item's metadata, as obtained by the walker (symbolic links are not followed)
+    pub metadata: &'a fs::Metadata,
+
+    /// Item's type
+    pub item_type: WalkerItemType,
+
+    /// Item's depth (`0` for the source directory's direct children)
+    pub depth: usize,
+
+    /// Item's parent directory (absolute)
+    pub parent: &'a Path,
+
+    /// Walker's configuration
+    pub config: &'a WalkerConfig,
+
+    /// Source directory (absolute, canonicalized)
+    pub source: &'a Path,
 }
 
 /// Walker's item type
@@ -77,6 +1396,94 @@ pub enum WalkerItemType {
     Directory,
     File,
     Symlink,
+
+    /// Special file (named pipe, socket, block or character device, ...)
+    Other,
+}
+
+impl WalkerItemType {
+    /// Get the type of an item from its file type (as provided by [`fs::Metadata::file_type`])
+    pub fn from_file_type(file_type: fs::FileType) -> Self {
+        if file_type.is_symlink() {
+            Self::Symlink
+        } else if file_type.is_file() {
+            Self::File
+        } else if file_type.is_dir() {
+            Self::Directory
+        } else {
+            Self::Other
+        }
+    }
+}
+
+/// Set of [item types](WalkerItemType) (see [`WalkerRule::only_for`])
+///
+/// ```
+/// use rebackup::config::*;
+///
+/// let types = WalkerItemTypes::of([WalkerItemType::File, WalkerItemType::Symlink]);
+///
+/// assert!(types.contains(WalkerItemType::File));
+/// assert!(types.contains(WalkerItemType::Symlink));
+/// assert!(!types.contains(WalkerItemType::Directory));
+///
+/// assert_eq!(WalkerItemTypes::from(None), WalkerItemTypes::all());
+/// assert_eq!(WalkerItemTypes::from(Some(WalkerItemType::File)), WalkerItemTypes::only(WalkerItemType::File));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WalkerItemTypes(u8);
+
+impl WalkerItemTypes {
+    /// Set containing all item types
+    pub fn all() -> Self {
+        Self::of([WalkerItemType::Directory, WalkerItemType::File, WalkerItemType::Symlink, WalkerItemType::Other])
+    }
+
+    /// Set containing a single item type
+    pub fn only(item_type: WalkerItemType) -> Self {
+        Self(Self::bit(item_type))
+    }
+
+    /// Set containing the provided item types
+    pub fn of(item_types: impl IntoIterator<Item = WalkerItemType>) -> Self {
+        Self(item_types.into_iter().fold(0, |bits, item_type| bits | Self::bit(item_type)))
+    }
+
+    /// Check if the set contains an item type
+    pub fn contains(&self, item_type: WalkerItemType) -> bool {
+        self.0 & Self::bit(item_type) != 0
+    }
+
+    /// (Internal) Get the bit representing an item type
+    fn bit(item_type: WalkerItemType) -> u8 {
+        match item_type {
+            WalkerItemType::Directory => 1 << 0,
+            WalkerItemType::File => 1 << 1,
+            WalkerItemType::Symlink => 1 << 2,
+            WalkerItemType::Other => 1 << 3,
+        }
+    }
+}
+
+impl Default for WalkerItemTypes {
+    fn default() -> Self {
+        Self::all()
+    }
+}
+
+impl From<WalkerItemType> for WalkerItemTypes {
+    fn from(item_type: WalkerItemType) -> Self {
+        Self::only(item_type)
+    }
+}
+
+impl From<Option<WalkerItemType>> for WalkerItemTypes {
+    fn from(item_type: Option<WalkerItemType>) -> Self {
+        match item_type {
+            None => Self::all(),
+            Some(item_type) => Self::only(item_type),
+        }
+    }
 }
 
 /// Walker rule's result
@@ -103,6 +1510,48 @@ pub enum WalkerRuleResult {
     /// Paths may either be absolute or relative to the item itself, but they must always be children items
     /// of the base path.
     ///
+    /// Paths containing `..` components are normalized first. When symbolic links are followed (see [`SymlinkMode::Follow`]),
+    /// paths located inside the base path's target (e.g. canonicalized paths) are accepted as well, and are reported
+    /// through the base path.
+    ///
+    /// ```
+    /// # #[cfg(unix)] {
+    /// use rebackup::config::*;
+    /// use rebackup::walk;
+    /// use std::fs;
+    ///
+    /// let dir = std::env::temp_dir().join("rebackup-doctest-map-through-symlinks");
+    /// # let _ = fs::remove_dir_all(&dir);
+    /// fs::create_dir_all(dir.join("real/sub")).unwrap();
+    /// fs::write(dir.join("real/sub/file"), "").unwrap();
+    /// std::os::unix::fs::symlink(dir.join("real"), dir.join("link")).unwrap();
+    ///
+    /// let dir = fs::canonicalize(&dir).unwrap();
+    /// let target = dir.join("real/sub/file");
+    ///
+    /// let mut config = WalkerConfig::new(vec![WalkerRule {
+    ///     name: "map-link",
+    ///     description: None,
+    ///     priority: 0,
+    ///     only_for: WalkerItemTypes::all(),
+    ///     min_depth: None,
+    ///     max_depth: None,
+    ///     cacheable: false,
+    ///     timeout: None,
+    ///     matches: Box::new(|ctx| Ok(ctx.path.ends_with("link"))),
+    ///     // Canonicalized path of the mapped item, which doesn't textually start with the link's path
+    ///     action: Box::new(move |_| Ok(WalkerRuleResult::MapAsList(vec![target.clone()], true))),
+    /// }]);
+    ///
+    /// config.symlink_mode = SymlinkMode::Follow;
+    /// config.sort_entries = true;
+    ///
+    /// // The real directory is not traversed as it was already visited through the symbolic link
+    /// assert_eq!(walk(&dir, &config).unwrap(), vec![dir.join("link/sub/file")]);
+    /// # fs::remove_dir_all(&dir).unwrap();
+    /// # }
+    /// ```
+    ///
     /// The second operand indicates if the mapping is absolute, wich means if all following rules should be skipped.
     ///
     /// **NOTE:** This return value is only valid on directories and symbolic links, if will generate an error if used on files.
@@ -110,4 +1559,87 @@ pub enum WalkerRuleResult {
     /// **NOTE:** If the return value includes a path that has already been visited, an error will be emitted but the process won't fail.
     ///           It will simply skip the said path and go on to the next item to treat.
     MapAsList(Vec<PathBuf>, bool),
+
+    /// Same as [`WalkerRuleResult::MapAsList`], but relative paths are relative to the source directory instead of the item,
+    /// and paths can point to any item of the source directory (not only to the item's descendants)
+    ///
+    /// This is useful for rules getting lists of items from external tools (see `examples/rules.rs`).
+    ///
+    /// ```
+    /// use rebackup::config::*;
+    /// use rebackup::walk;
+    /// use std::fs;
+    /// use std::path::PathBuf;
+    ///
+    /// let dir = std::env::temp_dir().join("rebackup-doctest-map-from-source");
+    /// # let _ = fs::remove_dir_all(&dir);
+    /// fs::create_dir_all(dir.join("project/src")).unwrap();
+    /// fs::write(dir.join("project/listing"), "project/src/main.rs\n").unwrap();
+    /// fs::write(dir.join("project/src/main.rs"), "").unwrap();
+    /// fs::write(dir.join("project/src/generated.rs"), "").unwrap();
+    ///
+    /// let config = WalkerConfig::new(vec![WalkerRule {
+    ///     name: "listing",
+    ///     description: None,
+    ///     priority: 0,
+    ///     only_for: WalkerItemTypes::only(WalkerItemType::Directory),
+    ///     min_depth: None,
+    ///     max_depth: None,
+    ///     cacheable: false,
+    ///     timeout: None,
+    ///     matches: Box::new(|ctx| Ok(ctx.path.join("listing").is_file())),
+    ///     action: Box::new(|ctx| {
+    ///         let listing = fs::read_to_string(ctx.path.join("listing"))?;
+    ///         Ok(WalkerRuleResult::MapAsListFromSource(listing.lines().map(PathBuf::from).collect(), false))
+    ///     }),
+    /// }]);
+    ///
+    /// let dir = fs::canonicalize(&dir).unwrap();
+    /// assert_eq!(walk(&dir, &config).unwrap(), vec![dir.join("project/src/main.rs")]);
+    /// # fs::remove_dir_all(&dir).unwrap();
+    /// ```
+    MapAsListFromSource(Vec<PathBuf>, bool),
+
+    /// Include the directory the rule was ran on, but don't traverse it (none of its descendants will be included).
+    /// All following rules are ignored.
+    ///
+    /// **NOTE:** This return value has no effect on items that are not directories (or symbolic links to directories),
+    ///           which are included as with [`WalkerRuleResult::IncludeItem`].
+    IncludeWithoutDescendants,
+
+    /// Report the item the rule was ran on under another path in the files list (the item is still read from its real path).
+    /// For directories, all descendants are reported under the new path as well.
+    ///
+    /// Relative paths are relative to the item's parent directory (e.g. `"new_name"` replaces the item's name),
+    /// while absolute paths are used as is.
+    ///
+    /// **NOTE:** An error is emitted if multiple rules rename the same item, or if two items are renamed to the same path.
+    ///
+    /// ```
+    /// use rebackup::{walk, WalkerConfig, WalkerItemTypes, WalkerRule, WalkerRuleResult};
+    /// use std::fs;
+    ///
+    /// let dir = std::env::temp_dir().join("rebackup-doctest-rename-item");
+    /// # let _ = fs::remove_dir_all(&dir);
+    /// fs::create_dir_all(dir.join("current/sub")).unwrap();
+    /// fs::write(dir.join("current/sub/file"), "").unwrap();
+    ///
+    /// let config = WalkerConfig::new(vec![WalkerRule {
+    ///     name: "rename_current",
+    ///     description: None,
+    ///     priority: 0,
+    ///     only_for: WalkerItemTypes::all(),
+    ///     min_depth: None,
+    ///     max_depth: None,
+    ///     cacheable: false,
+    ///     timeout: None,
+    ///     matches: Box::new(|ctx| Ok(ctx.path.file_name().is_some_and(|name| name == "current"))),
+    ///     action: Box::new(|_| Ok(WalkerRuleResult::RenameItem("2021-04-01".into()))),
+    /// }]);
+    ///
+    /// let dir = fs::canonicalize(&dir).unwrap();
+    /// assert_eq!(walk(&dir, &config).unwrap(), vec![dir.join("2021-04-01/sub/file")]);
+    /// # fs::remove_dir_all(&dir).unwrap();
+    /// ```
+    RenameItem(PathBuf),
 }
@@ -0,0 +1,241 @@
+//! # The filesystem module
+//!
+//! This module contains the [`FileSystem`] trait, which the [walker](crate::walker::walk) uses to access the filesystem
+//! (see [`WalkerConfig::filesystem`](crate::WalkerConfig::filesystem)), and its default implementation [`RealFs`].
+//!
+//! With the `test-util` feature, [`MockFs`] allows to simulate filesystem failures on specific items and to count
+//! the operations performed by the walker.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+#[cfg(feature = "test-util")]
+use std::collections::HashMap;
+#[cfg(feature = "test-util")]
+use std::sync::{Arc, Mutex};
+
+/// Entries of a directory (see [`FileSystem::read_dir`])
+pub type DirEntriesIter = Box<dyn Iterator<Item = io::Result<PathBuf>> + Send>;
+
+/// Filesystem the walker reads items from
+///
+/// Rules' callbacks are not affected and keep accessing the filesystem on their own.
+pub trait FileSystem: Send + Sync {
+    /// Get the paths of a directory's entries (see [`std::fs::read_dir`])
+    fn read_dir(&self, path: &Path) -> io::Result<DirEntriesIter>;
+
+    /// Get an item's metadata, without following symbolic links (see [`std::fs::symlink_metadata`])
+    fn symlink_metadata(&self, path: &Path) -> io::Result<fs::Metadata>;
+
+    /// Get an item's metadata, following symbolic links (see [`std::fs::metadata`])
+    fn metadata(&self, path: &Path) -> io::Result<fs::Metadata>;
+
+    /// Get the target of a symbolic link (see [`std::fs::read_link`])
+    fn read_link(&self, path: &Path) -> io::Result<PathBuf>;
+
+    /// Get the canonical path of an item (see [`std::fs::canonicalize`])
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf>;
+}
+
+/// The real filesystem (used by default)
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RealFs;
+
+impl FileSystem for RealFs {
+    fn read_dir(&self, path: &Path) -> io::Result<DirEntriesIter> {
+        let entries = fs::read_dir(path)?;
+        Ok(Box::new(entries.map(|entry| entry.map(|entry| entry.path()))))
+    }
+
+    fn symlink_metadata(&self, path: &Path) -> io::Result<fs::Metadata> {
+        fs::symlink_metadata(path)
+    }
+
+    fn metadata(&self, path: &Path) -> io::Result<fs::Metadata> {
+        fs::metadata(path)
+    }
+
+    fn read_link(&self, path: &Path) -> io::Result<PathBuf> {
+        fs::read_link(path)
+    }
+
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        fs::canonicalize(path)
+    }
+}
+
+/// Filesystem operation (see [`MockFs::fail`])
+#[cfg(feature = "test-util")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FsOperation {
+    ReadDir,
+    SymlinkMetadata,
+    Metadata,
+    ReadLink,
+    Canonicalize,
+}
+
+/// Real filesystem with simulated failures on specific items (requires the `test-util` feature)
+///
+/// As [metadata](std::fs::Metadata) can't be built by hand, items are still read from the real filesystem,
+/// but operations can be made to fail on specific paths. Paths must be provided as they are seen by the walker,
+/// which means they must be located inside the canonicalized source directory.
+///
+/// The number of times each operation was performed is counted (see [`MockFs::calls`]). Clones share their counters,
+/// so a clone can be kept to inspect them after the filesystem was moved to the walker's configuration.
+///
+/// ```
+/// use rebackup::filesystem::{FsOperation, MockFs};
+/// use rebackup::{walk, walk_with_stats, WalkerConfig, WalkerErr};
+/// use std::{fs, io};
+///
+/// let dir = std::env::temp_dir().join("rebackup-doctest-mock-fs");
+/// # let _ = fs::remove_dir_all(&dir);
+/// fs::create_dir_all(dir.join("locked")).unwrap();
+/// fs::write(dir.join("locked/file"), "").unwrap();
+///
+/// let dir = fs::canonicalize(&dir).unwrap();
+///
+/// let config = WalkerConfig::builder()
+///     .filesystem(MockFs::new().fail(FsOperation::ReadDir, dir.join("locked"), io::ErrorKind::PermissionDenied))
+///     .build();
+///
+/// match walk(&dir, &config) {
+///     Err(WalkerErr::FailedToWalkDir(path, err)) => {
+///         assert_eq!(path, dir.join("locked"));
+///         assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+///     }
+///     result => panic!("Unexpected result: {:?}", result),
+/// }
+///
+/// // Failures can also be injected while iterating over a directory's entries
+/// let config = WalkerConfig::builder()
+///     .filesystem(MockFs::new().fail_entry(dir.join("locked"), 0, io::ErrorKind::Other))
+///     .build();
+///
+/// match walk(&dir, &config) {
+///     Err(WalkerErr::FailedToReadDirEntry(path, _)) => assert_eq!(path, dir.join("locked")),
+///     result => panic!("Unexpected result: {:?}", result),
+/// }
+///
+/// // Unreadable items can be skipped
+/// let config = WalkerConfig::builder()
+///     .filesystem(MockFs::new().fail(FsOperation::SymlinkMetadata, dir.join("locked/file"), io::ErrorKind::PermissionDenied))
+///     .skip_unreadable(true)
+///     .build();
+///
+/// let (items, stats) = walk_with_stats(&dir, &config).unwrap();
+///
+/// assert!(items.is_empty());
+/// assert_eq!(stats.skipped_unreadable, 1);
+/// # fs::remove_dir_all(&dir).unwrap();
+/// ```
+#[cfg(feature = "test-util")]
+#[derive(Debug, Clone, Default)]
+pub struct MockFs {
+    /// Operations to fail, with the kind of error to return
+    failures: HashMap<(FsOperation, PathBuf), io::ErrorKind>,
+
+    /// Directories whose entries' iteration must fail, with the number of entries to yield first
+    entry_failures: HashMap<PathBuf, (usize, io::ErrorKind)>,
+
+    /// Number of times each operation was performed (shared between clones)
+    calls: Arc<Mutex<HashMap<FsOperation, usize>>>,
+}
+
+#[cfg(feature = "test-util")]
+impl MockFs {
+    /// Create a filesystem without any failure
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Make an operation fail on a specific item
+    pub fn fail(mut self, operation: FsOperation, path: impl Into<PathBuf>, kind: io::ErrorKind) -> Self {
+        self.failures.insert((operation, path.into()), kind);
+        self
+    }
+
+    /// Make the iteration over a directory's entries fail after the provided number of entries
+    pub fn fail_entry(mut self, dir: impl Into<PathBuf>, after: usize, kind: io::ErrorKind) -> Self {
+        self.entry_failures.insert(dir.into(), (after, kind));
+        self
+    }
+
+    /// Get the number of times an operation was performed, including the ones which failed
+    ///
+    /// ```
+    /// use rebackup::filesystem::{FsOperation, MockFs};
+    /// use rebackup::{walk, WalkerConfig};
+    /// use std::fs;
+    ///
+    /// let dir = std::env::temp_dir().join("rebackup-doctest-mock-fs-calls");
+    /// # let _ = fs::remove_dir_all(&dir);
+    /// fs::create_dir_all(dir.join("sub")).unwrap();
+    /// fs::write(dir.join("sub/file"), "").unwrap();
+    ///
+    /// let mock_fs = MockFs::new();
+    /// walk(&dir, &WalkerConfig::builder().filesystem(mock_fs.clone()).build()).unwrap();
+    ///
+    /// // Only the source directory is canonicalized
+    /// assert_eq!(mock_fs.calls(FsOperation::Canonicalize), 1);
+    /// # fs::remove_dir_all(&dir).unwrap();
+    /// ```
+    pub fn calls(&self, operation: FsOperation) -> usize {
+        let calls = self.calls.lock().expect("Internal error: mock filesystem's lock is poisoned");
+        calls.get(&operation).copied().unwrap_or(0)
+    }
+
+    /// (Internal) Count an operation and get its simulated failure, if any
+    fn check(&self, operation: FsOperation, path: &Path) -> io::Result<()> {
+        *self
+            .calls
+            .lock()
+            .expect("Internal error: mock filesystem's lock is poisoned")
+            .entry(operation)
+            .or_insert(0) += 1;
+
+        match self.failures.get(&(operation, path.to_path_buf())) {
+            Some(kind) => Err(io::Error::new(*kind, format!("simulated failure of {:?} operation", operation))),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(feature = "test-util")]
+impl FileSystem for MockFs {
+    fn read_dir(&self, path: &Path) -> io::Result<DirEntriesIter> {
+        self.check(FsOperation::ReadDir, path)?;
+
+        let entries = RealFs.read_dir(path)?;
+
+        Ok(match self.entry_failures.get(path) {
+            Some(&(after, kind)) => {
+                let failure = io::Error::new(kind, "simulated failure of directory entry");
+                Box::new(entries.take(after).chain(std::iter::once(Err(failure))))
+            }
+            None => entries,
+        })
+    }
+
+    fn symlink_metadata(&self, path: &Path) -> io::Result<fs::Metadata> {
+        self.check(FsOperation::SymlinkMetadata, path)?;
+        RealFs.symlink_metadata(path)
+    }
+
+    fn metadata(&self, path: &Path) -> io::Result<fs::Metadata> {
+        self.check(FsOperation::Metadata, path)?;
+        RealFs.metadata(path)
+    }
+
+    fn read_link(&self, path: &Path) -> io::Result<PathBuf> {
+        self.check(FsOperation::ReadLink, path)?;
+        RealFs.read_link(path)
+    }
+
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        self.check(FsOperation::Canonicalize, path)?;
+        RealFs.canonicalize(path)
+    }
+}
@@ -0,0 +1,31 @@
+//! # The item module
+//!
+//! This module contains [`WalkerItem`], which describes an item found by the [walker](crate::walker::walk_detailed).
+
+use crate::config::WalkerItemType;
+use std::fs;
+use std::path::PathBuf;
+
+/// Item found by the walker, alongside its informations
+#[derive(Debug, Clone)]
+pub struct WalkerItem {
+    /// Item's absolute path (as reported in the files list, see [`WalkerItem::renamed_from`])
+    pub path: PathBuf,
+
+    /// Item's type
+    pub item_type: WalkerItemType,
+
+    /// Item's metadata (symbolic links are not followed)
+    pub metadata: fs::Metadata,
+
+    /// Target of the symbolic link, if the item is one
+    pub symlink_target: Option<PathBuf>,
+
+    /// Other hard links to the same file that were not included in the files list
+    /// (only when [`WalkerConfig::dedup_hard_links`](crate::WalkerConfig::dedup_hard_links) is enabled)
+    pub hard_links: Vec<PathBuf>,
+
+    /// Path the item was actually read from, if it was renamed by a rule
+    /// (see [`WalkerRuleResult::RenameItem`](crate::WalkerRuleResult::RenameItem))
+    pub renamed_from: Option<PathBuf>,
+}
@@ -7,36 +7,44 @@
 //!
 //! Its main features are:
 //!
-//! * Fast recursive directory traversing
-//! * Powerful rules system to include, exclude or remap items
+//! * Fast recursive directory traversing (optionally multi-threaded, see [`walk_parallel`](walker::walk_parallel))
+//! * Powerful rules system to include, exclude or remap items (with built-in rules, see [`rules`])
 //! * Handling of symbolic links (requires to enable an option for the walker)
 //! * Detection of already visited paths
+//! * Comparison of files lists to find what changed since a previous walk (see [`Snapshot`])
 //! * Command-line interface
 //!
 //! ReBackup can be used either:
 //!
-//! * As a library (see [`walk`](walker::walk))
+//! * As a library (see [`walk`](walker::walk), [`walk_iter`](walker::walk_iter) and [`walk_detailed`](walker::walk_detailed))
+//! * From asynchronous code with the `async-walker` feature (see `walk_async`)
+//! * With a simulated filesystem in tests with the `test-util` feature (see [`filesystem`])
 //! * As a standalone binary with the `cli` feature
 //!
 //! ## Library usage
 //!
-//! ReBackup only exports one single function which is the Walker: [`walk`](walker::walk).
+//! ReBackup's main function is the Walker: [`walk`](walker::walk).
+//!
+//! The most commonly used types and macros can be imported at once from the [`prelude`].
 //!
 //! It can be used like this:
 //!
 //! ```no_run
 //! use std::path::PathBuf;
-//! use rebackup::{fail, walk, WalkerConfig};
+//! use rebackup::{fail, walk, ErrorPolicy, SymlinkMode, WalkerConfig};
 //!
 //! let source = std::env::args().nth(1)
 //!     .unwrap_or_else(|| fail!(exit 1, "Please provide a source directory"));
 //!
 //! // NOTE: This can be shortened to `WalkerConfig::new(vec![])`
 //! //       (expanded here for explanations purpose)
+//! //       A builder is also available through `WalkerConfig::builder()`
 //! let config = WalkerConfig {
 //!     rules: vec![],
-//!     follow_symlinks: false,
+//!     symlink_mode: SymlinkMode::Skip,
 //!     drop_empty_dirs: false,
+//!     on_error: ErrorPolicy::Abort,
+//!     ..WalkerConfig::default()
 //! };
 //!
 //! let files_list = walk(&PathBuf::from(source), &config)
@@ -50,6 +58,22 @@
 //! println!("{}", files_list_str.join("\n"));
 //! ```
 //!
+//! For large trees, [`walk_iter`](walker::walk_iter) yields the items lazily instead of building the whole list in memory:
+//!
+//! ```no_run
+//! use std::path::Path;
+//! use rebackup::{walk_iter, WalkerConfig};
+//!
+//! let config = WalkerConfig::new(vec![]);
+//!
+//! for item in walk_iter(Path::new("/home"), &config) {
+//!     match item {
+//!         Ok(path) => println!("{}", path.display()),
+//!         Err(err) => eprintln!("Skipping item: {}", err),
+//!     }
+//! }
+//! ```
+//!
 //! ### Rules
 //!
 //! You can use powerful rules to configure how the walker behaves.
@@ -59,6 +83,9 @@
 //! * One to determine if the rule applies on a specific item
 //! * One to run the rule itself
 //!
+//! Both receive a [`RuleCtx`](config::RuleCtx) containing the item's path, type and metadata, as well as the walker's configuration
+//! and the source directory.
+//!
 //! Here is a basic rule excluding all directories containing `.nomedia` files:
 //!
 //! ```
@@ -71,47 +98,59 @@
 //!     // Optional description of the rule
 //!     description: None,
 //!
-//!     // The type of items the rule applies to (`None` for all)
-//!     only_for: Some(WalkerItemType::Directory),
+//!     // Priority of the rule (rules with a higher priority are run first)
+//!     priority: 0,
+//!
+//!     // The types of items the rule applies to (`WalkerItemTypes::all()` for all)
+//!     only_for: WalkerItemTypes::only(WalkerItemType::Directory),
+//!
+//!     // Optional range of depths the rule applies to (`0` for the source directory's direct children)
+//!     min_depth: None,
+//!     max_depth: None,
+//!
+//!     // Indicate if the predicate only depends on the item's parent directory (see `WalkerRule::cacheable`)
+//!     cacheable: false,
+//!
+//!     // Optional maximum duration of the rule's callbacks (see `WalkerRule::timeout`)
+//!     timeout: None,
 //!
 //!     // Check if the rule would match a specific item
-//!     matches: Box::new(|path, _, _| path.join(".nomedia").is_file()),
+//!     matches: Box::new(|ctx| Ok(ctx.path.join(".nomedia").is_file())),
 //!
 //!     // Apply the rule to determine what to do
-//!     action: Box::new(|_, _, _| Ok(WalkerRuleResult::ExcludeItem)),
+//!     action: Box::new(|_| Ok(WalkerRuleResult::ExcludeItem)),
 //! };
 //! ```
 //!
 //! You can also build more powerful rules, like excluding files ignored by Git:
 //!
 //! ```
-//! use std::env;
 //! use std::process::Command;
 //! use rebackup::config::*;
 //!
 //! let rule = WalkerRule {
 //!     name: "gitignore",
 //!     description: None,
-//!     only_for: None,
-//!     matches: Box::new(|path, _, _| path.ancestors().any(|path| path.join(".git").is_dir())),
-//!     action: Box::new(|dir, _, _| {
-//!         let cwd = env::current_dir()?;
-//!
-//!         if dir.is_dir() {
-//!             env::set_current_dir(dir)?;
-//!         } else if let Some(parent) = dir.parent() {
-//!             env::set_current_dir(parent)?;
-//!         }
+//!     priority: 0,
+//!     only_for: WalkerItemTypes::all(),
+//!     min_depth: None,
+//!     max_depth: None,
+//!     cacheable: false,
+//!     timeout: None,
+//!     matches: Box::new(|ctx| Ok(ctx.path.ancestors().any(|path| path.join(".git").is_dir()))),
+//!     action: Box::new(|ctx| {
+//!         let dir = ctx.path;
+//!
+//!         // Run the command from the item's directory (rules must not change the process' current directory)
+//!         let cwd = if dir.is_dir() { dir } else { dir.parent().unwrap_or(dir) };
 //!
 //!         let is_excluded = Command::new("git")
 //!             .arg("check-ignore")
 //!             .arg(dir.to_string_lossy().to_string())
-//!             .output();
-//!
-//!         // Restore the current directory before returning eventual error from the command
-//!         env::set_current_dir(cwd)?;
+//!             .current_dir(cwd)
+//!             .output()?;
 //!
-//!         if is_excluded?.status.success() {
+//!         if is_excluded.status.success() {
 //!             Ok(WalkerRuleResult::ExcludeItem)
 //!         } else {
 //!             Ok(WalkerRuleResult::IncludeItem)
@@ -122,6 +161,8 @@
 //!
 //! You can check more examples of rules in `examples/rules.rs`.
 //!
+//! Ready-to-use rules (including a faster version of the one above) are also available in the [`rules`] module.
+//!
 //! ## Command-line usage
 //!
 //! ```shell
@@ -155,10 +196,17 @@
 #[macro_use]
 pub mod logger;
 pub mod config;
+pub mod filesystem;
+pub mod item;
+pub mod prelude;
+pub mod rules;
+pub mod snapshot;
 pub mod walker;
 
 pub use config::*;
+pub use item::*;
 pub use logger::*;
+pub use snapshot::*;
 pub use walker::*;
 
 // Re-export used crates
@@ -171,3 +219,9 @@ pub use thiserror;
 pub use clap;
 #[cfg(feature = "cli")]
 pub use glob;
+#[cfg(feature = "globset")]
+pub use globset;
+#[cfg(feature = "gitignore")]
+pub use ignore;
+#[cfg(feature = "regex")]
+pub use regex;
@@ -2,15 +2,29 @@
 //!
 //! This module exports macros to display messages to STDOUT or STDERR, depending on the set logging level.
 //!
-//! The logging level is stored inside [`static@LOGGER_LEVEL`], which can be atomically read and updated.
+//! The logging level is stored inside [`static@LOGGER_LEVEL`], which can be atomically read and updated,
+//! or more simply through [`log_level`] and [`set_log_level`].
+//!
+//! Error messages can also be displayed as JSON objects instead of text, see [`set_log_format`].
+//!
+//! A status line (e.g. a progress indicator) can be kept below the messages displayed to STDERR, see [`set_status_line`].
 
-use atomic::Atomic;
+use atomic::{Atomic, Ordering};
 use lazy_static::lazy_static;
+use std::fmt::Write as _;
+use std::path::Path;
+use std::sync::{Mutex, MutexGuard};
 
 lazy_static! {
     /// The minimum logging level of messages to display.
     /// All messages with a lower logging level won't be displayed.
     pub static ref LOGGER_LEVEL: Atomic<LoggerLevel> = Atomic::<LoggerLevel>::new(LoggerLevel::Error);
+
+    /// The format of error and failure messages.
+    pub static ref LOGGER_FORMAT: Atomic<LoggerFormat> = Atomic::<LoggerFormat>::new(LoggerFormat::Text);
+
+    /// (Internal) The status line currently displayed at the bottom of STDERR, if any
+    static ref STATUS_LINE: Mutex<Option<String>> = Mutex::new(None);
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
@@ -21,16 +35,160 @@ pub enum LoggerLevel {
     Debug,
 }
 
+/// Format of error and failure messages (other messages are always displayed as text)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoggerFormat {
+    /// A line of text, prefixed by the message's level (e.g. `[ERROR] message`)
+    Text,
+
+    /// A JSON object on a single line (see [`json_message`])
+    Json,
+}
+
+/// Get the current logging level
+pub fn log_level() -> LoggerLevel {
+    LOGGER_LEVEL.load(Ordering::SeqCst)
+}
+
+/// Set the minimum logging level of messages to display
+///
+/// ```
+/// use rebackup::{debug, log_level, set_log_level, LoggerLevel};
+///
+/// set_log_level(LoggerLevel::Debug);
+/// assert_eq!(log_level(), LoggerLevel::Debug);
+///
+/// debug!("This message is now displayed");
+/// ```
+pub fn set_log_level(level: LoggerLevel) {
+    LOGGER_LEVEL.store(level, Ordering::SeqCst);
+}
+
+/// Get the current format of error and failure messages
+pub fn log_format() -> LoggerFormat {
+    LOGGER_FORMAT.load(Ordering::SeqCst)
+}
+
+/// Set the format of error and failure messages
+pub fn set_log_format(format: LoggerFormat) {
+    LOGGER_FORMAT.store(format, Ordering::SeqCst);
+}
+
+/// Display a status line at the bottom of STDERR, replacing the previous one (`None` to clear it)
+///
+/// The status line is cleared before displaying log messages, and displayed again after them, so they don't get mixed.
+/// It must fit in a single line of the terminal, and only be displayed if STDERR is a terminal.
+///
+/// ```no_run
+/// use rebackup::set_status_line;
+///
+/// for i in 0..100 {
+///     set_status_line(Some(&format!("Treating item {}/100", i + 1)));
+/// }
+///
+/// set_status_line(None);
+/// ```
+pub fn set_status_line(line: Option<&str>) {
+    let mut status_line = lock_status_line();
+
+    if status_line.is_some() || line.is_some() {
+        eprint!("\r\x1b[2K{}", line.unwrap_or(""));
+    }
+
+    *status_line = line.map(str::to_owned);
+}
+
+/// (Internal) Run a function displaying a message, with the status line cleared (see [`set_status_line`])
+#[doc(hidden)]
+pub fn with_status_line_cleared(f: impl FnOnce()) {
+    let status_line = lock_status_line();
+
+    match &*status_line {
+        Some(line) => {
+            eprint!("\r\x1b[2K");
+            f();
+            eprint!("{}", line);
+        }
+        None => f(),
+    }
+}
+
+/// (Internal) Lock the status line (a panic while it was locked doesn't prevent from displaying it)
+fn lock_status_line() -> MutexGuard<'static, Option<String>> {
+    STATUS_LINE.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+/// Format a message as a JSON object on a single line, with the machine-readable code and the path of the related error if any
+///
+/// ```
+/// use rebackup::{json_message, LoggerLevel};
+/// use std::path::Path;
+///
+/// assert_eq!(
+///     json_message(LoggerLevel::Error, Some("FailedToWalkDir"), Some(Path::new("/home")), "Failed to walk directory"),
+///     r#"{"level":"error","code":"FailedToWalkDir","path":"/home","message":"Failed to walk directory"}"#
+/// );
+/// ```
+pub fn json_message(level: LoggerLevel, code: Option<&str>, path: Option<&Path>, message: &str) -> String {
+    let level = match level {
+        LoggerLevel::Failure => "failure",
+        LoggerLevel::Error => "error",
+        LoggerLevel::Info => "info",
+        LoggerLevel::Debug => "debug",
+    };
+
+    let mut out = format!("{{\"level\":\"{}\"", level);
+
+    if let Some(code) = code {
+        out.push_str(",\"code\":");
+        push_json_str(&mut out, code);
+    }
+
+    if let Some(path) = path {
+        out.push_str(",\"path\":");
+        push_json_str(&mut out, &path.to_string_lossy());
+    }
+
+    out.push_str(",\"message\":");
+    push_json_str(&mut out, message);
+    out.push('}');
+    out
+}
+
+/// (Internal) Write a string as a JSON string literal
+fn push_json_str(out: &mut String, str: &str) {
+    out.push('"');
+
+    for c in str.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => write!(out, "\\u{:04x}", c as u32).unwrap(),
+            c => out.push(c),
+        }
+    }
+
+    out.push('"');
+}
+
 /// Log a message if the logging level is high enough
 #[macro_export]
 macro_rules! log {
     ($logger_level: ident, $is_err: expr, $msg_prefix: expr, $msg: expr$(, $args: expr)*) => {{
-        if $crate::logger::LOGGER_LEVEL.load(atomic::Ordering::SeqCst) >= $crate::logger::LoggerLevel::$logger_level {
-            if $is_err {
-                eprintln!(concat!($msg_prefix, $msg)$(, $args)*);
-            } else {
-                println!(concat!($msg_prefix, $msg)$(, $args)*);
-            }
+        if $crate::logger::log_level() >= $crate::logger::LoggerLevel::$logger_level {
+            $crate::logger::with_status_line_cleared(|| {
+                if $is_err && $crate::logger::log_format() == $crate::logger::LoggerFormat::Json {
+                    let message = format!($msg$(, $args)*);
+                    eprintln!("{}", $crate::logger::json_message($crate::logger::LoggerLevel::$logger_level, None, None, &message));
+                } else if $is_err {
+                    eprintln!(concat!($msg_prefix, $msg)$(, $args)*);
+                } else {
+                    println!(concat!($msg_prefix, $msg)$(, $args)*);
+                }
+            });
         }
     }}
 }
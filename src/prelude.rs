@@ -0,0 +1,44 @@
+//! # The prelude
+//!
+//! This module re-exports the most commonly used types, functions and macros, so they can be imported at once:
+//!
+//! ```
+//! use rebackup::prelude::*;
+//! use std::fs;
+//!
+//! let dir = std::env::temp_dir().join("rebackup-doctest-prelude");
+//! # let _ = fs::remove_dir_all(&dir);
+//! fs::create_dir_all(dir.join("target")).unwrap();
+//! fs::write(dir.join("main.rs"), "").unwrap();
+//!
+//! set_log_level(LoggerLevel::Info);
+//!
+//! let config = WalkerConfig::builder()
+//!     .rule(WalkerRule {
+//!         name: "target",
+//!         description: None,
+//!         priority: 0,
+//!         only_for: WalkerItemTypes::only(WalkerItemType::Directory),
+//!         min_depth: None,
+//!         max_depth: None,
+//!         cacheable: false,
+//!         timeout: None,
+//!         matches: Box::new(|ctx| Ok(ctx.path.ends_with("target"))),
+//!         action: Box::new(|_| Ok(WalkerRuleResult::ExcludeItem)),
+//!     })
+//!     .build();
+//!
+//! let items = walk(&dir, &config).unwrap_or_else(|err: WalkerErr| fail!(exit 1, "Walk failed: {}", err));
+//! info!("Found {} item(s)", items.len());
+//!
+//! assert_eq!(items, vec![dir.join("main.rs")]);
+//! # fs::remove_dir_all(&dir).unwrap();
+//! ```
+//!
+//! All items are still available from their original paths (e.g. [`rebackup::config::WalkerRule`](crate::config::WalkerRule)).
+
+pub use crate::config::{RuleCtx, WalkerConfig, WalkerItemType, WalkerItemTypes, WalkerRule, WalkerRuleResult};
+pub use crate::item::WalkerItem;
+pub use crate::logger::{set_log_format, set_log_level, LoggerFormat, LoggerLevel};
+pub use crate::walker::{walk, walk_detailed, walk_iter, WalkerErr};
+pub use crate::{debug, err, fail, info};
@@ -0,0 +1,151 @@
+//! # Built-in rules
+//!
+//! This module contains ready-to-use [rules](WalkerRule) for common needs, like excluding version control directories
+//! or build artifacts.
+//!
+//! Rules can be obtained either through their function (e.g. [`dotgit`]) or by name (see [`by_name`]).
+//!
+//! ```
+//! use rebackup::{rules, WalkerConfig};
+//!
+//! let mut rules = rules::default_dev_rules();
+//! rules.push(rules::by_name("nomedia").unwrap());
+//!
+//! let config = WalkerConfig::new(rules);
+//! assert_eq!(config.rules.len(), 4);
+//! ```
+
+mod attributes;
+mod dev_artifacts;
+mod dirs;
+mod gitignore;
+#[cfg(feature = "globset")]
+mod globs;
+#[cfg(feature = "gitignore")]
+mod ignore_files;
+#[cfg(feature = "regex")]
+mod regexes;
+
+pub use attributes::{exclude_extensions, exclude_older_than, hidden, include_only_extensions};
+pub use dev_artifacts::{
+    dev_artifacts, go_vendor, gradle_cache, nextjs_build, node_package_modules, python_build, python_mypy_cache, python_pycache, python_tox, python_venv,
+    terraform_dir,
+};
+pub use dirs::{cachedir_tag, dotgit, exclude_if_present, node_modules, nomedia, rust_cargo_build, vcs_dirs, CACHEDIR_TAG_SIGNATURE, VCS_DIR_NAMES};
+pub use gitignore::gitignore;
+#[cfg(feature = "globset")]
+pub use globs::{glob_rules, read_patterns, GlobPatterns, PatternsListErr};
+#[cfg(feature = "gitignore")]
+pub use ignore_files::{ignore_file, respect_gitignore};
+#[cfg(feature = "regex")]
+pub use regexes::{exclude_regex, include_only_regex};
+
+use crate::config::{RuleSet, WalkerRule};
+
+/// Names of all built-in rules (see [`by_name`])
+pub const NAMES: &[&str] = &[
+    "cachedir_tag",
+    "dotgit",
+    "gitignore",
+    "go_vendor",
+    "gradle_cache",
+    "hidden",
+    "nextjs_build",
+    "node_modules",
+    "node_package_modules",
+    "nomedia",
+    "python_build",
+    "python_mypy_cache",
+    "python_pycache",
+    "python_tox",
+    "python_venv",
+    "rust_cargo_build",
+    "terraform_dir",
+];
+
+/// Get a built-in rule from its name (see [`NAMES`])
+pub fn by_name(name: &str) -> Option<WalkerRule> {
+    match name {
+        "cachedir_tag" => Some(cachedir_tag()),
+        "dotgit" => Some(dotgit()),
+        "gitignore" => Some(gitignore()),
+        "go_vendor" => Some(go_vendor()),
+        "gradle_cache" => Some(gradle_cache()),
+        "hidden" => Some(hidden()),
+        "nextjs_build" => Some(nextjs_build()),
+        "node_modules" => Some(node_modules()),
+        "node_package_modules" => Some(node_package_modules()),
+        "nomedia" => Some(nomedia()),
+        "python_build" => Some(python_build()),
+        "python_mypy_cache" => Some(python_mypy_cache()),
+        "python_pycache" => Some(python_pycache()),
+        "python_tox" => Some(python_tox()),
+        "python_venv" => Some(python_venv()),
+        "rust_cargo_build" => Some(rust_cargo_build()),
+        "terraform_dir" => Some(terraform_dir()),
+        _ => None,
+    }
+}
+
+/// Get the usual set of rules for a developer's machine: [`dotgit`], [`node_modules`] and [`rust_cargo_build`]
+///
+/// [`gitignore`] is not included as it runs a Git command on every item inside repositories, which is a lot slower.
+pub fn default_dev_rules() -> Vec<WalkerRule> {
+    vec![dotgit(), node_modules(), rust_cargo_build()]
+}
+
+impl RuleSet {
+    /// Get the built-in rules for a developer's machine, organized in groups which can be toggled individually:
+    ///
+    /// * `vcs`: version control directories (see [`vcs_dirs`])
+    /// * `build-artifacts`: build outputs and installed dependencies of common toolchains (see [`dev_artifacts`])
+    /// * `caches`: directories tagged as caches (see [`cachedir_tag`])
+    ///
+    /// ```
+    /// use rebackup::{walk, RuleSet, WalkerConfig};
+    /// use std::fs;
+    ///
+    /// let dir = std::env::temp_dir().join("rebackup-doctest-dev-defaults");
+    /// # let _ = fs::remove_dir_all(&dir);
+    ///
+    /// for sub_dir in ["repo/.git", "repo/target/debug", "thumbnails"] {
+    ///     fs::create_dir_all(dir.join(sub_dir)).unwrap();
+    /// }
+    ///
+    /// fs::write(dir.join("repo/.git/HEAD"), "").unwrap();
+    /// fs::write(dir.join("repo/Cargo.toml"), "").unwrap();
+    /// fs::write(dir.join("repo/target/debug/app"), "").unwrap();
+    /// fs::write(dir.join("thumbnails/CACHEDIR.TAG"), "Signature: 8a477f597d28d172789f06886806bc55").unwrap();
+    ///
+    /// let dir = fs::canonicalize(&dir).unwrap();
+    ///
+    /// let mut items = walk(&dir, &WalkerConfig::new(RuleSet::dev_defaults())).unwrap();
+    /// items.sort();
+    ///
+    /// assert_eq!(items, vec![dir.join("repo/Cargo.toml")]);
+    ///
+    /// let mut rules = RuleSet::dev_defaults();
+    /// rules.disable("vcs");
+    ///
+    /// let mut items = walk(&dir, &WalkerConfig::new(rules)).unwrap();
+    /// items.sort();
+    ///
+    /// assert_eq!(items, vec![dir.join("repo/.git/HEAD"), dir.join("repo/Cargo.toml")]);
+    /// # fs::remove_dir_all(&dir).unwrap();
+    /// ```
+    pub fn dev_defaults() -> Self {
+        let mut set = Self::new();
+
+        for rule in vcs_dirs() {
+            set.add("vcs", rule);
+        }
+
+        for rule in dev_artifacts() {
+            set.add("build-artifacts", rule);
+        }
+
+        set.add("caches", cachedir_tag());
+
+        set
+    }
+}
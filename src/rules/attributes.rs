@@ -0,0 +1,213 @@
+//! Rules excluding items based on their name or metadata
+
+use crate::config::{WalkerItemType, WalkerItemTypes, WalkerRule, WalkerRuleResult};
+use std::time::{Duration, SystemTime};
+
+/// Exclude hidden items: items whose name starts with a dot (e.g. `.config`), as well as items with the hidden attribute on Windows
+///
+/// The source directory itself is never excluded, as rules are only run on its descendants. To only exclude some types of items,
+/// override the rule's [`only_for`](WalkerRule::only_for) field.
+///
+/// ```
+/// use rebackup::{rules, walk, WalkerConfig, WalkerItemType, WalkerItemTypes, WalkerRule};
+/// use std::fs;
+///
+/// let dir = std::env::temp_dir().join("rebackup-doctest-hidden");
+/// # let _ = fs::remove_dir_all(&dir);
+/// fs::create_dir_all(dir.join(".config")).unwrap();
+/// fs::create_dir_all(dir.join("..data")).unwrap();
+/// fs::create_dir_all(dir.join("docs")).unwrap();
+/// fs::write(dir.join(".config/settings"), "").unwrap();
+/// fs::write(dir.join("..data/file"), "").unwrap();
+/// fs::write(dir.join("docs/.draft"), "").unwrap();
+/// fs::write(dir.join("docs/file.txt"), "").unwrap();
+///
+/// // Relative components in the source's path don't make all items hidden
+/// let dir = fs::canonicalize(&dir).unwrap();
+/// let mut items = walk(&dir.join("docs/.."), &WalkerConfig::new(vec![rules::hidden()])).unwrap();
+/// items.sort();
+///
+/// assert_eq!(items, vec![dir.join("docs/file.txt")]);
+///
+/// // Only exclude hidden directories
+/// let dirs_only = WalkerRule {
+///     only_for: WalkerItemTypes::only(WalkerItemType::Directory),
+///     ..rules::hidden()
+/// };
+///
+/// let mut items = walk(&dir, &WalkerConfig::new(vec![dirs_only])).unwrap();
+/// items.sort();
+///
+/// assert_eq!(items, vec![dir.join("docs/.draft"), dir.join("docs/file.txt")]);
+/// # fs::remove_dir_all(&dir).unwrap();
+/// ```
+pub fn hidden() -> WalkerRule {
+    WalkerRule {
+        name: "hidden",
+        description: Some("Exclude hidden items".to_string()),
+        priority: 0,
+        only_for: WalkerItemTypes::all(),
+        min_depth: None,
+        max_depth: None,
+        cacheable: false,
+        timeout: None,
+        matches: Box::new(|ctx| {
+            // Names are never '.' or '..', as these can't be the last component of a path
+            let dot_name = ctx.path.file_name().is_some_and(|name| name.to_string_lossy().starts_with('.'));
+
+            #[cfg(windows)]
+            {
+                use std::os::windows::fs::MetadataExt;
+
+                /// Hidden attribute of files and directories on Windows
+                const FILE_ATTRIBUTE_HIDDEN: u32 = 0x2;
+
+                Ok(dot_name || ctx.metadata.file_attributes() & FILE_ATTRIBUTE_HIDDEN != 0)
+            }
+
+            #[cfg(not(windows))]
+            {
+                Ok(dot_name)
+            }
+        }),
+        action: Box::new(|_| Ok(WalkerRuleResult::ExcludeItem)),
+    }
+}
+
+/// Exclude files with one of the provided extensions (compared case-insensitively, with or without a leading dot);
+/// other types of items are not affected
+///
+/// ```
+/// use rebackup::{rules, walk, WalkerConfig};
+/// use std::fs;
+///
+/// let dir = std::env::temp_dir().join("rebackup-doctest-exclude-extensions");
+/// # let _ = fs::remove_dir_all(&dir);
+/// fs::create_dir_all(dir.join("videos.mkv")).unwrap();
+///
+/// for file in ["disk.iso", "DISK2.ISO", "movie.Mkv", "notes.txt", "README", "videos.mkv/file"] {
+///     fs::write(dir.join(file), "").unwrap();
+/// }
+///
+/// let dir = fs::canonicalize(&dir).unwrap();
+///
+/// let mut items = walk(&dir, &WalkerConfig::new(vec![rules::exclude_extensions(&["iso", ".MKV"])])).unwrap();
+/// items.sort();
+///
+/// assert_eq!(items, vec![dir.join("README"), dir.join("notes.txt"), dir.join("videos.mkv/file")]);
+/// # fs::remove_dir_all(&dir).unwrap();
+/// ```
+pub fn exclude_extensions(extensions: &[&str]) -> WalkerRule {
+    let extensions = normalize_extensions(extensions);
+
+    WalkerRule {
+        name: "exclude_extensions",
+        description: Some(format!("Exclude extensions: {}", extensions.join(", "))),
+        priority: 0,
+        only_for: WalkerItemTypes::only(WalkerItemType::File),
+        min_depth: None,
+        max_depth: None,
+        cacheable: false,
+        timeout: None,
+        matches: Box::new(move |ctx| {
+            let ext = ctx.path.extension().map(|ext| ext.to_string_lossy().to_lowercase());
+            Ok(matches!(ext, Some(ext) if extensions.contains(&ext)))
+        }),
+        action: Box::new(|_| Ok(WalkerRuleResult::ExcludeItem)),
+    }
+}
+
+/// Only include files with one of the provided extensions (compared case-insensitively, with or without a leading dot);
+/// other types of items are not affected, while files without an extension are excluded
+///
+/// ```
+/// use rebackup::{rules, walk, WalkerConfig};
+/// use std::fs;
+///
+/// let dir = std::env::temp_dir().join("rebackup-doctest-include-only-extensions");
+/// # let _ = fs::remove_dir_all(&dir);
+/// fs::create_dir_all(dir.join("photos")).unwrap();
+///
+/// for file in ["photos/a.jpg", "photos/B.PNG", "photos/c.Raw", "photos/notes.txt", "photos/jpg", "photos/.png"] {
+///     fs::write(dir.join(file), "").unwrap();
+/// }
+///
+/// let dir = fs::canonicalize(&dir).unwrap();
+///
+/// let mut items = walk(&dir, &WalkerConfig::new(vec![rules::include_only_extensions(&["jpg", ".png", "RAW"])])).unwrap();
+/// items.sort();
+///
+/// assert_eq!(items, vec![dir.join("photos/B.PNG"), dir.join("photos/a.jpg"), dir.join("photos/c.Raw")]);
+/// # fs::remove_dir_all(&dir).unwrap();
+/// ```
+pub fn include_only_extensions(extensions: &[&str]) -> WalkerRule {
+    let extensions = normalize_extensions(extensions);
+    let extensions: Vec<_> = extensions.iter().map(String::as_str).collect();
+
+    WalkerRule::include_only_extensions(&extensions)
+}
+
+/// Exclude files last modified longer ago than the provided duration; other types of items are not affected
+///
+/// Unlike [`WalkerConfig::modified_after`](crate::WalkerConfig::modified_after), the limit is relative to the moment the rule
+/// is created. Files modified in the future are included, as well as files whose modification time cannot be read.
+///
+/// ```
+/// use rebackup::{rules, walk, WalkerConfig};
+/// use std::fs::{self, File};
+/// use std::time::{Duration, SystemTime};
+///
+/// let dir = std::env::temp_dir().join("rebackup-doctest-exclude-older-than");
+/// # let _ = fs::remove_dir_all(&dir);
+/// fs::create_dir_all(dir.join("old_dir")).unwrap();
+///
+/// for file in ["stale", "recent", "future", "old_dir/recent"] {
+///     fs::write(dir.join(file), "").unwrap();
+/// }
+///
+/// let day = Duration::from_secs(86400);
+/// File::open(dir.join("stale")).unwrap().set_modified(SystemTime::now() - day * 45).unwrap();
+/// File::open(dir.join("recent")).unwrap().set_modified(SystemTime::now() - day * 2).unwrap();
+/// File::open(dir.join("future")).unwrap().set_modified(SystemTime::now() + day * 10).unwrap();
+/// File::open(dir.join("old_dir")).unwrap().set_modified(SystemTime::now() - day * 90).unwrap();
+///
+/// let dir = fs::canonicalize(&dir).unwrap();
+///
+/// let mut items = walk(&dir, &WalkerConfig::new(vec![rules::exclude_older_than(day * 30)])).unwrap();
+/// items.sort();
+///
+/// assert_eq!(items, vec![dir.join("future"), dir.join("old_dir/recent"), dir.join("recent")]);
+/// # fs::remove_dir_all(&dir).unwrap();
+/// ```
+pub fn exclude_older_than(max_age: Duration) -> WalkerRule {
+    let now = SystemTime::now();
+
+    WalkerRule {
+        name: "exclude_older_than",
+        description: Some(format!("Exclude files not modified in the last {} seconds", max_age.as_secs())),
+        priority: 0,
+        only_for: WalkerItemTypes::only(WalkerItemType::File),
+        min_depth: None,
+        max_depth: None,
+        cacheable: false,
+        timeout: None,
+        matches: Box::new(move |ctx| match ctx.metadata.modified() {
+            // Files modified in the future are not considered as old
+            Ok(modified) => Ok(matches!(now.duration_since(modified), Ok(age) if age > max_age)),
+            Err(err) => {
+                debug!(
+                    ">> Failed to get modification time, not excluding based on age: {} ({})",
+                    ctx.path.display(),
+                    err
+                );
+                Ok(false)
+            }
+        }),
+        action: Box::new(|_| Ok(WalkerRuleResult::ExcludeItem)),
+    }
+}
+
+/// (Internal) Lowercase extensions and remove their leading dot
+fn normalize_extensions(extensions: &[&str]) -> Vec<String> {
+    extensions.iter().map(|ext| ext.strip_prefix('.').unwrap_or(ext).to_lowercase()).collect()
+}
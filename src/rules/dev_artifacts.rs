@@ -0,0 +1,214 @@
+//! Rules excluding the build artifacts, caches and dependencies of common development ecosystems
+
+use super::rust_cargo_build;
+use crate::config::{WalkerItemType, WalkerItemTypes, WalkerRule, WalkerRuleResult};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Exclude Python's bytecode caches (`__pycache__` directories next to `.py` files)
+pub fn python_pycache() -> WalkerRule {
+    artifacts_dir("python_pycache", "Exclude Python bytecode caches", &["__pycache__"], |dir| {
+        has_sibling_with_ext(dir, "py")
+    })
+}
+
+/// Exclude Python's virtual environments (`.venv` or `venv` directories containing a `pyvenv.cfg` file)
+pub fn python_venv() -> WalkerRule {
+    artifacts_dir("python_venv", "Exclude Python virtual environments", &[".venv", "venv"], |dir| {
+        Ok(dir.join("pyvenv.cfg").is_file())
+    })
+}
+
+/// Exclude tox's environments (`.tox` directories next to a `tox.ini`, `setup.py`, `setup.cfg` or `pyproject.toml` file)
+pub fn python_tox() -> WalkerRule {
+    artifacts_dir("python_tox", "Exclude tox environments", &[".tox"], |dir| {
+        Ok(has_sibling(dir, &["tox.ini", "setup.py", "setup.cfg", "pyproject.toml"]))
+    })
+}
+
+/// Exclude mypy's caches (`.mypy_cache` directories next to a `mypy.ini`, `setup.py`, `setup.cfg` or `pyproject.toml` file)
+pub fn python_mypy_cache() -> WalkerRule {
+    artifacts_dir("python_mypy_cache", "Exclude mypy caches", &[".mypy_cache"], |dir| {
+        Ok(has_sibling(dir, &["mypy.ini", "setup.py", "setup.cfg", "pyproject.toml"]))
+    })
+}
+
+/// Exclude the build directories of Python packages (`build` and `dist` directories next to a `setup.py` or `pyproject.toml` file)
+pub fn python_build() -> WalkerRule {
+    artifacts_dir("python_build", "Exclude Python packages' build directories", &["build", "dist"], |dir| {
+        Ok(has_sibling(dir, &["setup.py", "pyproject.toml"]))
+    })
+}
+
+/// Exclude Gradle's caches (`.gradle` directories next to a Gradle build or settings script)
+pub fn gradle_cache() -> WalkerRule {
+    artifacts_dir("gradle_cache", "Exclude Gradle caches", &[".gradle"], |dir| {
+        Ok(has_sibling(
+            dir,
+            &["build.gradle", "build.gradle.kts", "settings.gradle", "settings.gradle.kts", "gradlew"],
+        ))
+    })
+}
+
+/// Exclude the `node_modules` directories next to a `package.json` file
+///
+/// Unlike [`node_modules`](super::node_modules), directories which are not part of a Node.js package are kept.
+pub fn node_package_modules() -> WalkerRule {
+    artifacts_dir("node_package_modules", "Exclude Node.js packages' modules", &["node_modules"], |dir| {
+        Ok(has_sibling(dir, &["package.json"]))
+    })
+}
+
+/// Exclude Next.js' build directories (`.next` directories next to a `package.json` file)
+pub fn nextjs_build() -> WalkerRule {
+    artifacts_dir("nextjs_build", "Exclude Next.js build directories", &[".next"], |dir| {
+        Ok(has_sibling(dir, &["package.json"]))
+    })
+}
+
+/// Exclude Terraform's working directories (`.terraform` directories next to `.tf` files)
+pub fn terraform_dir() -> WalkerRule {
+    artifacts_dir("terraform_dir", "Exclude Terraform working directories", &[".terraform"], |dir| {
+        has_sibling_with_ext(dir, "tf")
+    })
+}
+
+/// Exclude Go's vendored dependencies (`vendor` directories next to a `go.mod` file)
+pub fn go_vendor() -> WalkerRule {
+    artifacts_dir("go_vendor", "Exclude Go vendored dependencies", &["vendor"], |dir| {
+        Ok(has_sibling(dir, &["go.mod"]))
+    })
+}
+
+/// Exclude the build artifacts, caches and dependencies of common development ecosystems
+///
+/// Each rule checks both the name of the directory and the presence of a marker file (e.g. `build` directories are only
+/// excluded next to a `setup.py` or `pyproject.toml` file), so unrelated directories with the same name are kept.
+/// Rules have their own name (e.g. `python_venv`, `go_vendor`), which is displayed when explaining why an item was excluded.
+///
+/// Contains [`python_pycache`], [`python_venv`], [`python_tox`], [`python_mypy_cache`], [`python_build`], [`gradle_cache`],
+/// [`node_package_modules`], [`nextjs_build`], [`terraform_dir`], [`go_vendor`] and [`rust_cargo_build`].
+///
+/// ```
+/// use rebackup::{rules, walk, WalkerConfig};
+/// use std::fs;
+///
+/// let dir = std::env::temp_dir().join("rebackup-doctest-dev-artifacts");
+/// # let _ = fs::remove_dir_all(&dir);
+///
+/// for sub_dir in [
+///     "cargo/target/debug",
+///     "node/node_modules/left-pad",
+///     "node/.next",
+///     "python/__pycache__",
+///     "python/.venv",
+///     "python/build/lib",
+///     "go/vendor/golang.org",
+///     "photos/build",
+///     "photos/vendor",
+/// ] {
+///     fs::create_dir_all(dir.join(sub_dir)).unwrap();
+/// }
+///
+/// for file in [
+///     "cargo/Cargo.toml",
+///     "cargo/target/debug/app",
+///     "node/package.json",
+///     "node/node_modules/left-pad/index.js",
+///     "node/.next/BUILD_ID",
+///     "python/pyproject.toml",
+///     "python/app.py",
+///     "python/__pycache__/app.cpython-39.pyc",
+///     "python/.venv/pyvenv.cfg",
+///     "python/build/lib/app.py",
+///     "go/go.mod",
+///     "go/vendor/golang.org/modules.txt",
+///     "photos/build/cathedral.jpg",
+///     "photos/vendor/invoice.pdf",
+/// ] {
+///     fs::write(dir.join(file), "").unwrap();
+/// }
+///
+/// let dir = fs::canonicalize(&dir).unwrap();
+///
+/// let mut items = walk(&dir, &WalkerConfig::new(rules::dev_artifacts())).unwrap();
+/// items.sort();
+///
+/// assert_eq!(
+///     items,
+///     vec![
+///         dir.join("cargo/Cargo.toml"),
+///         dir.join("go/go.mod"),
+///         dir.join("node/package.json"),
+///         dir.join("photos/build/cathedral.jpg"),
+///         dir.join("photos/vendor/invoice.pdf"),
+///         dir.join("python/app.py"),
+///         dir.join("python/pyproject.toml"),
+///     ]
+/// );
+/// # fs::remove_dir_all(&dir).unwrap();
+/// ```
+pub fn dev_artifacts() -> Vec<WalkerRule> {
+    vec![
+        python_pycache(),
+        python_venv(),
+        python_tox(),
+        python_mypy_cache(),
+        python_build(),
+        gradle_cache(),
+        node_package_modules(),
+        nextjs_build(),
+        terraform_dir(),
+        go_vendor(),
+        rust_cargo_build(),
+    ]
+}
+
+/// (Internal) Exclude directories with one of the provided names, if the provided predicate returns `true` for them
+fn artifacts_dir(
+    name: &'static str,
+    description: &str,
+    dir_names: &'static [&'static str],
+    is_artifacts_dir: fn(&Path) -> io::Result<bool>,
+) -> WalkerRule {
+    WalkerRule {
+        name,
+        description: Some(description.to_string()),
+        priority: 0,
+        only_for: WalkerItemTypes::only(WalkerItemType::Directory),
+        min_depth: None,
+        max_depth: None,
+        cacheable: false,
+        timeout: None,
+        matches: Box::new(move |ctx| match ctx.path.file_name() {
+            Some(dir_name) if dir_names.iter().any(|name| dir_name == *name) => is_artifacts_dir(ctx.path),
+            _ => Ok(false),
+        }),
+        action: Box::new(|_| Ok(WalkerRuleResult::ExcludeItem)),
+    }
+}
+
+/// (Internal) Check if a directory has a sibling file with one of the provided names
+fn has_sibling(dir: &Path, file_names: &[&str]) -> bool {
+    dir.parent()
+        .is_some_and(|parent| file_names.iter().any(|file_name| parent.join(file_name).is_file()))
+}
+
+/// (Internal) Check if a directory has a sibling file with the provided extension
+fn has_sibling_with_ext(dir: &Path, ext: &str) -> io::Result<bool> {
+    let parent = match dir.parent() {
+        Some(parent) => parent,
+        None => return Ok(false),
+    };
+
+    for entry in fs::read_dir(parent)? {
+        let path = entry?.path();
+
+        if path.extension().is_some_and(|path_ext| path_ext == ext) && path.is_file() {
+            return Ok(true);
+        }
+    }
+
+    Ok(false)
+}
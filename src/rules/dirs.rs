@@ -0,0 +1,218 @@
+//! Rules excluding specific directories
+
+use crate::config::{WalkerItemType, WalkerItemTypes, WalkerRule, WalkerRuleResult};
+use std::ffi::OsStr;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+
+/// Exclude the `.git` directories
+pub fn dotgit() -> WalkerRule {
+    WalkerRule {
+        name: "dotgit",
+        description: Some("Exclude Git directories".to_string()),
+        ..WalkerRule::exclude_dir_named(".git")
+    }
+}
+
+/// Exclude the `node_modules` directories
+pub fn node_modules() -> WalkerRule {
+    WalkerRule {
+        name: "node_modules",
+        description: Some("Exclude Node.js modules".to_string()),
+        ..WalkerRule::exclude_dir_named("node_modules")
+    }
+}
+
+/// Names of the metadata directories of version control systems (see [`vcs_dirs`])
+pub const VCS_DIR_NAMES: &[&str] = &[".git", ".hg", ".svn", ".bzr", "CVS"];
+
+/// Exclude the metadata directories of the usual version control systems (see [`VCS_DIR_NAMES`])
+///
+/// Only directories are excluded, so files with the same name (e.g. `.git` files in Git worktrees) are kept.
+///
+/// ```
+/// use rebackup::{rules, walk, WalkerConfig};
+/// use std::fs;
+///
+/// let dir = std::env::temp_dir().join("rebackup-doctest-vcs-dirs");
+/// # let _ = fs::remove_dir_all(&dir);
+///
+/// for vcs_dir in ["repo/.git", "repo/.hg", "repo/.svn", "repo/.bzr", "repo/CVS", "worktree"] {
+///     fs::create_dir_all(dir.join(vcs_dir)).unwrap();
+/// }
+///
+/// for file in ["repo/.git/HEAD", "repo/.hg/store", "repo/.svn/wc.db", "repo/.bzr/branch", "repo/CVS/Root", "repo/main.rs", "worktree/.git"] {
+///     fs::write(dir.join(file), "").unwrap();
+/// }
+///
+/// let dir = fs::canonicalize(&dir).unwrap();
+///
+/// let mut items = walk(&dir, &WalkerConfig::new(rules::vcs_dirs())).unwrap();
+/// items.sort();
+///
+/// assert_eq!(items, vec![dir.join("repo/main.rs"), dir.join("worktree/.git")]);
+/// # fs::remove_dir_all(&dir).unwrap();
+/// ```
+pub fn vcs_dirs() -> Vec<WalkerRule> {
+    VCS_DIR_NAMES
+        .iter()
+        .map(|name| WalkerRule {
+            name: "vcs_dirs",
+            description: Some(format!("Exclude version control directories named '{}'", name)),
+            ..WalkerRule::exclude_dir_named(name)
+        })
+        .collect()
+}
+
+/// Exclude directories containing a `.nomedia` file
+pub fn nomedia() -> WalkerRule {
+    WalkerRule {
+        name: "nomedia",
+        description: Some("Exclude directories containing a '.nomedia' file".to_string()),
+        ..WalkerRule::exclude_if_contains(".nomedia")
+    }
+}
+
+/// Exclude the `target` directory in Cargo projects
+pub fn rust_cargo_build() -> WalkerRule {
+    WalkerRule {
+        name: "rust_cargo_build",
+        description: Some("Exclude Cargo's build directories".to_string()),
+        priority: 0,
+        only_for: WalkerItemTypes::only(WalkerItemType::Directory),
+        min_depth: None,
+        max_depth: None,
+        cacheable: false,
+        timeout: None,
+        matches: Box::new(|ctx| {
+            Ok(ctx.path.file_name() == Some(OsStr::new("target")) && ctx.path.parent().is_some_and(|parent| parent.join("Cargo.toml").is_file()))
+        }),
+        action: Box::new(|_| Ok(WalkerRuleResult::ExcludeItem)),
+    }
+}
+
+/// Signature CACHEDIR.TAG files must start with (see [`cachedir_tag`])
+pub const CACHEDIR_TAG_SIGNATURE: &[u8] = b"Signature: 8a477f597d28d172789f06886806bc55";
+
+/// Exclude cache directories, which contain a valid `CACHEDIR.TAG` file
+/// (see the [Cache Directory Tagging Specification](https://bford.info/cachedir/))
+///
+/// Tag files must start with [`CACHEDIR_TAG_SIGNATURE`], so unrelated files with the same name are ignored.
+///
+/// ```
+/// use rebackup::{rules, walk, WalkerConfig};
+/// use std::fs;
+///
+/// let dir = std::env::temp_dir().join("rebackup-doctest-cachedir-tag");
+/// # let _ = fs::remove_dir_all(&dir);
+///
+/// for (sub_dir, tag) in [
+///     ("valid", "Signature: 8a477f597d28d172789f06886806bc55\n# This file is a cache directory tag."),
+///     ("short", "Signature: 8a477f597d28d172789f"),
+///     ("malformed", "signature: 8a477f597d28d172789f06886806bc55"),
+/// ] {
+///     fs::create_dir_all(dir.join(sub_dir)).unwrap();
+///     fs::write(dir.join(sub_dir).join("CACHEDIR.TAG"), tag).unwrap();
+/// }
+///
+/// let dir = fs::canonicalize(&dir).unwrap();
+///
+/// let mut items = walk(&dir, &WalkerConfig::new(vec![rules::cachedir_tag()])).unwrap();
+/// items.sort();
+///
+/// assert_eq!(items, vec![dir.join("malformed/CACHEDIR.TAG"), dir.join("short/CACHEDIR.TAG")]);
+/// # fs::remove_dir_all(&dir).unwrap();
+/// ```
+pub fn cachedir_tag() -> WalkerRule {
+    WalkerRule {
+        name: "cachedir_tag",
+        description: Some("Exclude cache directories (containing a 'CACHEDIR.TAG' file)".to_string()),
+        ..exclude_if_present("CACHEDIR.TAG", Some(CACHEDIR_TAG_SIGNATURE))
+    }
+}
+
+/// Exclude directories containing a file with a specific name (e.g. `.nobackup`)
+///
+/// If a content prefix is provided, the file must start with it, which allows to validate marker files
+/// with a required header (e.g. `CACHEDIR.TAG`).
+///
+/// ```
+/// use rebackup::{rules, walk, WalkerConfig};
+/// use std::fs;
+///
+/// let dir = std::env::temp_dir().join("rebackup-doctest-exclude-if-present");
+/// # let _ = fs::remove_dir_all(&dir);
+///
+/// for sub_dir in ["skipped", "kept", "cache", "fake_cache"] {
+///     fs::create_dir_all(dir.join(sub_dir)).unwrap();
+///     fs::write(dir.join(sub_dir).join("file"), "").unwrap();
+/// }
+///
+/// fs::write(dir.join("skipped/.nobackup"), "").unwrap();
+/// fs::create_dir(dir.join("kept/.nobackup")).unwrap();
+/// fs::write(dir.join("cache/CACHEDIR.TAG"), "Signature: 8a477f597d28d172789f06886806bc55\n# Cache").unwrap();
+/// fs::write(dir.join("fake_cache/CACHEDIR.TAG"), "").unwrap();
+///
+/// let dir = fs::canonicalize(&dir).unwrap();
+///
+/// let config = WalkerConfig::new(vec![
+///     rules::exclude_if_present(".nobackup", None),
+///     rules::exclude_if_present("CACHEDIR.TAG", Some(b"Signature: 8a477f597d28d172789f06886806bc55")),
+/// ]);
+///
+/// let mut items = walk(&dir, &config).unwrap();
+/// items.sort();
+///
+/// // Only files are considered as markers, and their content must match the provided prefix
+/// assert_eq!(
+///     items,
+///     vec![dir.join("fake_cache/CACHEDIR.TAG"), dir.join("fake_cache/file"), dir.join("kept/.nobackup"), dir.join("kept/file")]
+/// );
+/// # fs::remove_dir_all(&dir).unwrap();
+/// ```
+pub fn exclude_if_present(name: &str, content_prefix: Option<&[u8]>) -> WalkerRule {
+    let name = name.to_string();
+    let content_prefix = content_prefix.map(<[u8]>::to_vec);
+
+    let description = match &content_prefix {
+        None => format!("Exclude directories containing a '{}' file", name),
+        Some(prefix) => format!(
+            "Exclude directories containing a '{}' file starting with '{}'",
+            name,
+            String::from_utf8_lossy(prefix)
+        ),
+    };
+
+    WalkerRule {
+        name: "exclude_if_present",
+        description: Some(description),
+        priority: 0,
+        only_for: WalkerItemTypes::only(WalkerItemType::Directory),
+        min_depth: None,
+        max_depth: None,
+        cacheable: false,
+        timeout: None,
+        matches: Box::new(move |ctx| {
+            let marker = ctx.path.join(&name);
+
+            if !marker.is_file() {
+                return Ok(false);
+            }
+
+            Ok(match &content_prefix {
+                None => true,
+                Some(prefix) => starts_with(&marker, prefix)?,
+            })
+        }),
+        action: Box::new(|_| Ok(WalkerRuleResult::ExcludeItem)),
+    }
+}
+
+/// (Internal) Check if a file's content starts with the provided bytes
+fn starts_with(path: &Path, prefix: &[u8]) -> io::Result<bool> {
+    let mut content = Vec::with_capacity(prefix.len());
+    File::open(path)?.take(prefix.len() as u64).read_to_end(&mut content)?;
+
+    Ok(content == prefix)
+}
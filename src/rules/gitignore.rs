@@ -0,0 +1,52 @@
+//! Rule excluding items ignored by Git
+
+use crate::config::{WalkerItemType, WalkerItemTypes, WalkerRule, WalkerRuleResult};
+use std::io;
+use std::process::{Command, Stdio};
+
+/// Exclude items ignored by Git (through `.gitignore` files, the repository's exclude file, ...) in Git repositories
+///
+/// This rule requires the `git` command to be available, and runs it on every item inside a Git repository.
+/// A faster alternative which doesn't require Git is available with the `gitignore` feature (see `respect_gitignore`).
+pub fn gitignore() -> WalkerRule {
+    WalkerRule {
+        name: "gitignore",
+        description: Some("Exclude items ignored by Git".to_string()),
+        priority: 0,
+        only_for: WalkerItemTypes::all(),
+        min_depth: None,
+        max_depth: None,
+        cacheable: false,
+        timeout: None,
+        // Only run on items inside a Git repository, excluding the content of `.git` directories (which Git doesn't consider)
+        matches: Box::new(|ctx| {
+            Ok(!ctx.path.components().any(|component| component.as_os_str() == ".git") && ctx.path.ancestors().any(|path| path.join(".git").is_dir()))
+        }),
+        action: Box::new(|ctx| {
+            let path = ctx.path;
+
+            // Run the command from the repository's root for repositories themselves, and from the parent directory
+            // for other items (without changing the current directory of the whole process)
+            let cwd = match path.parent() {
+                Some(parent) if !(ctx.item_type == WalkerItemType::Directory && path.join(".git").is_dir()) => parent,
+                _ => path,
+            };
+
+            let status = Command::new("git")
+                .arg("check-ignore")
+                .arg("--quiet")
+                .arg(path)
+                .current_dir(cwd)
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .status()?;
+
+            // The command exits with code 0 if the item is ignored, 1 if it isn't, and 128 on failure
+            match status.code() {
+                Some(0) => Ok(WalkerRuleResult::ExcludeItem),
+                Some(1) => Ok(WalkerRuleResult::IncludeItem),
+                _ => Err(io::Error::other(format!("Git command failed ({})", status))),
+            }
+        }),
+    }
+}
@@ -0,0 +1,567 @@
+//! Rule including or excluding items based on many glob patterns at once (requires the `globset` feature)
+
+use crate::config::{RuleCtx, WalkerItemType, WalkerItemTypes, WalkerRule, WalkerRuleResult};
+use globset::{Glob, GlobBuilder, GlobSet, GlobSetBuilder};
+use std::io::{self, BufRead};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use thiserror::Error;
+
+/// Glob patterns of a [`glob_rules`] rule
+///
+/// By default, patterns follow the `.gitignore` convention:
+///
+/// * Patterns ending with a `/` only match directories
+/// * Patterns without any other `/` match the items' name, at any depth (e.g. `*.log`)
+/// * Other patterns match the items' full path (a leading `/` is ignored), and `*` doesn't match `/` (e.g. `docs/**/*.pdf`)
+///
+/// This can be disabled with [`GlobPatterns::full_path`].
+///
+/// When an item matches patterns of multiple kinds, the first one applies in this order:
+///
+/// 1. `include_absolute` ([`WalkerRuleResult::IncludeItemAbsolute`])
+/// 2. `exclude` ([`WalkerRuleResult::ExcludeItem`])
+/// 3. `prune` ([`WalkerRuleResult::IncludeWithoutDescendants`])
+/// 4. `ordered` (see [`GlobPatterns::ordered`])
+/// 5. `include` ([`WalkerRuleResult::IncludeItem`])
+#[derive(Debug, Clone, Default)]
+pub struct GlobPatterns {
+    /// Include matching items and ignore all following rules
+    pub include_absolute: Vec<String>,
+
+    /// Include matching items (following rules are still run on them)
+    pub include: Vec<String>,
+
+    /// Exclude matching items
+    pub exclude: Vec<String>,
+
+    /// Include matching directories, but not their content
+    pub prune: Vec<String>,
+
+    /// Patterns evaluated in order, where the last matching one applies (like in `.gitignore` files)
+    ///
+    /// Matching items are excluded, unless the pattern starts with a `!`, which re-includes them instead
+    /// (a leading `\!` matches a literal `!`). Items which don't match any pattern get the same treatment as their
+    /// closest parent directory which does, so files can be re-included inside an excluded directory
+    /// (e.g. `build/` followed by `!build/keep.txt`). To allow this, excluded directories are still traversed
+    /// if a re-including pattern may match any of their descendants.
+    pub ordered: Vec<String>,
+
+    /// Match the patterns against the items' absolute path instead of their path relative to the source directory
+    ///
+    /// Items located outside of the source directory (e.g. when they were mapped to absolute paths by a rule)
+    /// are always matched using their absolute path.
+    pub absolute: bool,
+
+    /// Match all patterns against the items' full path, as they are written (`*` matches `/` as well)
+    pub full_path: bool,
+}
+
+/// Include or exclude items based on glob patterns (see [`GlobPatterns`])
+///
+/// All patterns are compiled into a single set, so items are matched against all of them at once, which is a lot faster
+/// than using one rule per pattern (like [`WalkerRule::exclude_glob`]) when there are many patterns.
+///
+/// ```
+/// use rebackup::rules::{glob_rules, GlobPatterns};
+/// use rebackup::{walk, WalkerConfig, WalkerRule};
+/// use std::fs;
+///
+/// let dir = std::env::temp_dir().join("rebackup-doctest-glob-rules");
+/// # let _ = fs::remove_dir_all(&dir);
+///
+/// for i in 0..100 {
+///     let sub_dir = dir.join(format!("dir{}", i));
+///     fs::create_dir_all(&sub_dir).unwrap();
+///
+///     for j in 0..100 {
+///         fs::write(sub_dir.join(format!("file{}.{}", j, ["txt", "log", "tmp", "rs"][j % 4])), "").unwrap();
+///     }
+/// }
+///
+/// let patterns: Vec<_> = (0..200)
+///     .map(|i| match i % 4 {
+///         0 => format!("dir{}/*.log", i / 2),
+///         1 => format!("**/file{}.txt", i),
+///         2 => format!("dir{}/file1?.*", i / 3),
+///         _ => format!("*/file{}.*", i),
+///     })
+///     .collect();
+///
+/// let dir = fs::canonicalize(&dir).unwrap();
+///
+/// let glob_set = WalkerConfig::new(vec![glob_rules(&GlobPatterns {
+///     exclude: patterns.clone(),
+///     ..GlobPatterns::default()
+/// })
+/// .unwrap()]);
+///
+/// let one_rule_per_pattern = WalkerConfig::new(
+///     patterns
+///         .iter()
+///         .map(|pattern| WalkerRule::exclude_glob(pattern).unwrap())
+///         .collect::<Vec<_>>(),
+/// );
+///
+/// let mut with_glob_set = walk(&dir, &glob_set).unwrap();
+/// let mut with_one_rule_per_pattern = walk(&dir, &one_rule_per_pattern).unwrap();
+///
+/// with_glob_set.sort();
+/// with_one_rule_per_pattern.sort();
+///
+/// assert!(with_glob_set.len() < 10_000);
+/// assert_eq!(with_glob_set, with_one_rule_per_pattern);
+/// # fs::remove_dir_all(&dir).unwrap();
+/// ```
+///
+/// Patterns can also be matched against absolute paths, which still works on items mapped by other rules:
+///
+/// ```
+/// use rebackup::rules::{glob_rules, GlobPatterns};
+/// use rebackup::{walk, WalkerConfig, WalkerItemType, WalkerItemTypes, WalkerRule, WalkerRuleResult};
+/// use std::fs;
+///
+/// let dir = std::env::temp_dir().join("rebackup-doctest-glob-rules-absolute");
+/// # let _ = fs::remove_dir_all(&dir);
+/// fs::create_dir_all(dir.join("logs")).unwrap();
+/// fs::write(dir.join("logs/app.log"), "").unwrap();
+/// fs::write(dir.join("logs/app.txt"), "").unwrap();
+///
+/// let dir = fs::canonicalize(&dir).unwrap();
+/// let logs = dir.join("logs");
+///
+/// // Map the "logs" directory to the absolute paths of its content
+/// let mapping = WalkerRule {
+///     name: "map_logs",
+///     description: None,
+///     priority: 1,
+///     only_for: WalkerItemTypes::only(WalkerItemType::Directory),
+///     min_depth: None,
+///     max_depth: None,
+///     cacheable: false,
+///     timeout: None,
+///     matches: Box::new(|ctx| Ok(ctx.path.ends_with("logs"))),
+///     action: Box::new(move |_| Ok(WalkerRuleResult::MapAsList(vec![logs.join("app.log"), logs.join("app.txt")], false))),
+/// };
+///
+/// let exclude = glob_rules(&GlobPatterns {
+///     exclude: vec![format!("{}/**/*.log", dir.display())],
+///     absolute: true,
+///     ..GlobPatterns::default()
+/// })
+/// .unwrap();
+///
+/// let config = WalkerConfig::new(vec![mapping, exclude]);
+/// assert_eq!(walk(&dir, &config).unwrap(), vec![dir.join("logs/app.txt")]);
+/// # fs::remove_dir_all(&dir).unwrap();
+/// ```
+///
+/// Patterns without a `/` match the items' name at any depth, unless [`GlobPatterns::full_path`] is set:
+///
+/// ```
+/// use rebackup::rules::{glob_rules, GlobPatterns};
+/// use rebackup::{walk, WalkerConfig};
+/// use std::fs;
+///
+/// let dir = std::env::temp_dir().join("rebackup-doctest-glob-rules-basename");
+/// # let _ = fs::remove_dir_all(&dir);
+///
+/// for sub_dir in ["src/build", "build", "docs/guide", "src/docs"] {
+///     fs::create_dir_all(dir.join(sub_dir)).unwrap();
+/// }
+///
+/// for file in ["app.log", "src/app.log", "src/build/out.o", "build/out.o", "src/build.rs", "docs/guide/guide.pdf", "docs/guide/notes.md", "docs/index.pdf", "src/docs/api.pdf", "src/main.rs"] {
+///     fs::write(dir.join(file), "").unwrap();
+/// }
+///
+/// let dir = fs::canonicalize(&dir).unwrap();
+///
+/// let list = |full_path| {
+///     let rule = glob_rules(&GlobPatterns {
+///         exclude: vec!["*.log".to_string(), "build/".to_string(), "docs/**/*.pdf".to_string()],
+///         full_path,
+///         ..GlobPatterns::default()
+///     });
+///
+///     let mut items: Vec<_> = walk(&dir, &WalkerConfig::new(vec![rule.unwrap()]))
+///         .unwrap()
+///         .into_iter()
+///         .map(|item| item.strip_prefix(&dir).unwrap().to_string_lossy().into_owned())
+///         .collect();
+///
+///     items.sort();
+///     items
+/// };
+///
+/// assert_eq!(list(false), vec!["docs/guide/notes.md", "src/build.rs", "src/docs/api.pdf", "src/main.rs"]);
+///
+/// // With full path matching, "*" matches "/" as well and "build/" never matches
+/// assert_eq!(
+///     list(true),
+///     vec!["build/out.o", "docs/guide/notes.md", "src/build.rs", "src/build/out.o", "src/docs/api.pdf", "src/main.rs"]
+/// );
+/// # fs::remove_dir_all(&dir).unwrap();
+/// ```
+///
+/// Ordered patterns allow to re-include items inside excluded directories:
+///
+/// ```
+/// use rebackup::rules::{glob_rules, GlobPatterns};
+/// use rebackup::{walk, WalkerConfig};
+/// use std::fs;
+///
+/// let dir = std::env::temp_dir().join("rebackup-doctest-glob-rules-ordered");
+/// # let _ = fs::remove_dir_all(&dir);
+/// fs::create_dir_all(dir.join("build/sub")).unwrap();
+/// fs::create_dir_all(dir.join("cache")).unwrap();
+///
+/// for file in ["build/out.o", "build/keep.txt", "build/sub/keep.txt", "cache/data", "cache/keep.txt", "main.rs", "notes.txt"] {
+///     fs::write(dir.join(file), "").unwrap();
+/// }
+///
+/// let dir = fs::canonicalize(&dir).unwrap();
+///
+/// let rule = glob_rules(&GlobPatterns {
+///     ordered: vec![
+///         "*.txt".to_string(),
+///         "!/notes.txt".to_string(),
+///         "build/**".to_string(),
+///         "!build/keep.txt".to_string(),
+///         "cache/".to_string(),
+///     ],
+///     ..GlobPatterns::default()
+/// })
+/// .unwrap();
+///
+/// let mut items: Vec<_> = walk(&dir, &WalkerConfig::new(vec![rule]))
+///     .unwrap()
+///     .into_iter()
+///     .map(|item| item.strip_prefix(&dir).unwrap().to_string_lossy().into_owned())
+///     .collect();
+///
+/// items.sort();
+///
+/// // "build/keep.txt" is re-included, but not "build/sub/keep.txt" which was only excluded again by "build/**",
+/// // and the "cache" directory is not traversed at all as no re-including pattern may match inside it
+/// assert_eq!(items, vec!["build/keep.txt", "main.rs", "notes.txt"]);
+/// # fs::remove_dir_all(&dir).unwrap();
+/// ```
+pub fn glob_rules(patterns: &GlobPatterns) -> Result<WalkerRule, globset::Error> {
+    let full_path = patterns.full_path;
+    let absolute = patterns.absolute;
+
+    let build = |patterns: &[String]| PatternSet::build(patterns.iter().map(String::as_str), full_path, absolute);
+
+    let sets = Arc::new(GlobRulesSets {
+        include_absolute: build(&patterns.include_absolute)?,
+        include: build(&patterns.include)?,
+        exclude: build(&patterns.exclude)?,
+        prune: build(&patterns.prune)?,
+        ordered: OrderedPatterns::build(&patterns.ordered, full_path, absolute)?,
+    });
+
+    let action_sets = Arc::clone(&sets);
+
+    let count = patterns.include_absolute.len() + patterns.include.len() + patterns.exclude.len() + patterns.prune.len() + patterns.ordered.len();
+
+    Ok(WalkerRule {
+        name: "glob_rules",
+        description: Some(format!("{} pattern(s)", count)),
+        priority: 0,
+        only_for: WalkerItemTypes::all(),
+        min_depth: None,
+        max_depth: None,
+        cacheable: false,
+        timeout: None,
+        matches: Box::new(move |ctx| {
+            let path = pattern_target(ctx, absolute);
+
+            Ok([&sets.include_absolute, &sets.include, &sets.exclude, &sets.prune]
+                .iter()
+                .any(|set| set.is_match(path, ctx.item_type))
+                || sets.ordered.excludes(ctx, absolute).is_some())
+        }),
+        action: Box::new(move |ctx| {
+            let path = pattern_target(ctx, absolute);
+            let sets = &*action_sets;
+
+            Ok(if sets.include_absolute.is_match(path, ctx.item_type) {
+                WalkerRuleResult::IncludeItemAbsolute
+            } else if sets.exclude.is_match(path, ctx.item_type) {
+                WalkerRuleResult::ExcludeItem
+            } else if sets.prune.is_match(path, ctx.item_type) {
+                WalkerRuleResult::IncludeWithoutDescendants
+            } else if sets.ordered.excludes(ctx, absolute) == Some(true)
+                && !(ctx.item_type == WalkerItemType::Directory && sets.ordered.may_reinclude_in(path))
+            {
+                WalkerRuleResult::ExcludeItem
+            } else {
+                WalkerRuleResult::IncludeItem
+            })
+        }),
+    })
+}
+
+/// Error occured while reading a list of glob patterns (see [`read_patterns`])
+#[derive(Error, Debug)]
+pub enum PatternsListErr {
+    /// The list could not be read
+    #[error("Failed to read patterns: {0}")]
+    FailedToRead(io::Error),
+
+    /// A pattern is invalid (lines start at 1)
+    #[error("Invalid pattern at line {line}: {err}")]
+    InvalidPattern { line: usize, err: globset::Error },
+}
+
+/// Read a list of glob patterns, one per line (e.g. to fill [`GlobPatterns::exclude`])
+///
+/// Blank lines and lines starting with a `#` are ignored, and trailing whitespaces are trimmed.
+///
+/// ```
+/// use rebackup::rules::{read_patterns, PatternsListErr};
+///
+/// let list = "# Build artifacts\n*.o  \n\ntarget/\n  # Indented comment\n";
+/// assert_eq!(read_patterns(list.as_bytes()).unwrap(), vec!["*.o", "target/"]);
+///
+/// let list = "# Invalid pattern below\n*.o\n\n[a-\n";
+///
+/// match read_patterns(list.as_bytes()) {
+///     Err(PatternsListErr::InvalidPattern { line, .. }) => assert_eq!(line, 4),
+///     result => panic!("Unexpected result: {:?}", result),
+/// }
+/// ```
+pub fn read_patterns(reader: impl BufRead) -> Result<Vec<String>, PatternsListErr> {
+    let mut patterns = vec![];
+
+    for (index, line) in reader.lines().enumerate() {
+        let line = line.map_err(PatternsListErr::FailedToRead)?;
+        let pattern = line.trim_end();
+
+        if pattern.trim_start().is_empty() || pattern.trim_start().starts_with('#') {
+            continue;
+        }
+
+        Glob::new(pattern).map_err(|err| PatternsListErr::InvalidPattern { line: index + 1, err })?;
+
+        patterns.push(pattern.to_string());
+    }
+
+    Ok(patterns)
+}
+
+/// (Internal) Get the path of an item patterns must be matched against (see [`GlobPatterns::absolute`])
+fn pattern_target<'a>(ctx: &RuleCtx<'a>, absolute: bool) -> &'a Path {
+    if absolute {
+        return ctx.path;
+    }
+
+    ctx.path.strip_prefix(ctx.source).unwrap_or(ctx.path)
+}
+
+/// (Internal) Compiled patterns of a [`glob_rules`] rule
+struct GlobRulesSets {
+    include_absolute: PatternSet,
+    include: PatternSet,
+    exclude: PatternSet,
+    prune: PatternSet,
+    ordered: OrderedPatterns,
+}
+
+/// (Internal) Compiled ordered patterns (see [`GlobPatterns::ordered`])
+struct OrderedPatterns {
+    /// Patterns, without their leading `!`
+    set: PatternSet,
+
+    /// Indicates which patterns re-include items
+    negated: Vec<bool>,
+
+    /// Paths re-including patterns may match descendants of (`None` if they may match anywhere)
+    reinclude_scopes: Vec<Option<PathBuf>>,
+}
+
+impl OrderedPatterns {
+    /// Compile patterns (see [`GlobPatterns::full_path`] and [`GlobPatterns::absolute`])
+    fn build(patterns: &[String], full_path: bool, absolute: bool) -> Result<Self, globset::Error> {
+        let mut globs = vec![];
+        let mut negated = vec![];
+        let mut reinclude_scopes = vec![];
+
+        for pattern in patterns {
+            let (glob, is_negated) = match pattern.strip_prefix('!') {
+                Some(glob) => (glob, true),
+                None => (pattern.strip_prefix('\\').filter(|glob| glob.starts_with('!')).unwrap_or(pattern), false),
+            };
+
+            if is_negated {
+                reinclude_scopes.push(match ParsedPattern::parse(glob, full_path, absolute) {
+                    ParsedPattern::Name { .. } => None,
+                    ParsedPattern::Path { glob, .. } => literal_prefix(glob),
+                });
+            }
+
+            globs.push(glob);
+            negated.push(is_negated);
+        }
+
+        Ok(Self {
+            set: PatternSet::build(globs, full_path, absolute)?,
+            negated,
+            reinclude_scopes,
+        })
+    }
+
+    /// Check if an item must be excluded, depending on the last pattern it or its closest parent directory matches
+    ///
+    /// Returns `None` if neither the item nor any of its parents (inside the source directory) matches any pattern.
+    fn excludes(&self, ctx: &RuleCtx, absolute: bool) -> Option<bool> {
+        if self.negated.is_empty() {
+            return None;
+        }
+
+        let last_match = |path: &Path, item_type| self.set.matching(path, item_type).into_iter().max().map(|index| !self.negated[index]);
+
+        if let Some(excluded) = last_match(pattern_target(ctx, absolute), ctx.item_type) {
+            return Some(excluded);
+        }
+
+        for parent in ctx.path.ancestors().skip(1) {
+            if parent == ctx.source || !parent.starts_with(ctx.source) {
+                break;
+            }
+
+            let target = if absolute {
+                parent
+            } else {
+                parent.strip_prefix(ctx.source).unwrap_or(parent)
+            };
+
+            if let Some(excluded) = last_match(target, WalkerItemType::Directory) {
+                return Some(excluded);
+            }
+        }
+
+        None
+    }
+
+    /// Check if a re-including pattern may match any descendant of a directory
+    fn may_reinclude_in(&self, dir: &Path) -> bool {
+        self.reinclude_scopes.iter().any(|scope| match scope {
+            None => true,
+            Some(scope) => scope.starts_with(dir) || dir.starts_with(scope),
+        })
+    }
+}
+
+/// (Internal) Get the longest list of path components of a pattern without any special character
+/// (`None` if the pattern starts with a special character)
+fn literal_prefix(glob: &str) -> Option<PathBuf> {
+    let literal = match glob.find(['*', '?', '[', '{', '\\']) {
+        None => glob,
+        Some(end) => glob[..end].rfind('/').map_or("", |slash| &glob[..slash]),
+    };
+
+    if literal.is_empty() {
+        None
+    } else {
+        Some(PathBuf::from(literal))
+    }
+}
+
+/// (Internal) Compiled glob patterns of a single kind (see [`GlobPatterns`])
+///
+/// Matching patterns are retrieved by index from the sets, as some of them only match directories.
+struct PatternSet {
+    /// Patterns matching the items' full path
+    path: GlobSet,
+
+    /// Index (in the provided list) of the patterns of `path`, and if they only match directories
+    path_patterns: Vec<(usize, bool)>,
+
+    /// Patterns matching the items' name
+    name: GlobSet,
+
+    /// Index (in the provided list) of the patterns of `name`, and if they only match directories
+    name_patterns: Vec<(usize, bool)>,
+}
+
+impl PatternSet {
+    /// Compile patterns (see [`GlobPatterns::full_path`] and [`GlobPatterns::absolute`])
+    fn build<'a>(patterns: impl IntoIterator<Item = &'a str>, full_path: bool, absolute: bool) -> Result<Self, globset::Error> {
+        let mut path = GlobSetBuilder::new();
+        let mut path_patterns = vec![];
+
+        let mut name = GlobSetBuilder::new();
+        let mut name_patterns = vec![];
+
+        for (index, pattern) in patterns.into_iter().enumerate() {
+            match ParsedPattern::parse(pattern, full_path, absolute) {
+                ParsedPattern::Path { glob, dirs_only } => {
+                    path.add(GlobBuilder::new(glob).literal_separator(!full_path).build()?);
+                    path_patterns.push((index, dirs_only));
+                }
+                ParsedPattern::Name { glob, dirs_only } => {
+                    name.add(Glob::new(glob)?);
+                    name_patterns.push((index, dirs_only));
+                }
+            }
+        }
+
+        Ok(Self {
+            path: path.build()?,
+            path_patterns,
+            name: name.build()?,
+            name_patterns,
+        })
+    }
+
+    /// Get the index (in the provided list) of the patterns an item matches
+    fn matching(&self, path: &Path, item_type: WalkerItemType) -> Vec<usize> {
+        let is_dir = item_type == WalkerItemType::Directory;
+        let applies = |(index, dirs_only): (usize, bool)| if is_dir || !dirs_only { Some(index) } else { None };
+
+        let mut matching: Vec<_> = self.path.matches(path).into_iter().filter_map(|i| applies(self.path_patterns[i])).collect();
+
+        if let Some(name) = path.file_name() {
+            matching.extend(self.name.matches(name).into_iter().filter_map(|i| applies(self.name_patterns[i])));
+        }
+
+        matching
+    }
+
+    /// Check if an item matches any of the patterns
+    fn is_match(&self, path: &Path, item_type: WalkerItemType) -> bool {
+        !self.matching(path, item_type).is_empty()
+    }
+}
+
+/// (Internal) Glob pattern, parsed following the `.gitignore` convention (see [`GlobPatterns`])
+enum ParsedPattern<'a> {
+    /// Pattern matching the items' full path
+    Path { glob: &'a str, dirs_only: bool },
+
+    /// Pattern matching the items' name
+    Name { glob: &'a str, dirs_only: bool },
+}
+
+impl<'a> ParsedPattern<'a> {
+    fn parse(pattern: &'a str, full_path: bool, absolute: bool) -> Self {
+        if full_path {
+            return Self::Path {
+                glob: pattern,
+                dirs_only: false,
+            };
+        }
+
+        let dirs_only = pattern.len() > 1 && pattern.ends_with('/');
+        let glob = if dirs_only { &pattern[..pattern.len() - 1] } else { pattern };
+
+        if !glob.contains('/') {
+            return Self::Name { glob, dirs_only };
+        }
+
+        let glob = if absolute { glob } else { glob.strip_prefix('/').unwrap_or(glob) };
+
+        Self::Path { glob, dirs_only }
+    }
+}
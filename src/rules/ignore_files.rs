@@ -0,0 +1,268 @@
+//! Rules excluding items based on ignore files, like `.gitignore` ones (requires the `gitignore` feature)
+
+use crate::config::{WalkerItemType, WalkerItemTypes, WalkerRule, WalkerRuleResult};
+use ignore::gitignore::{gitconfig_excludes_path, Gitignore, GitignoreBuilder};
+use ignore::Match;
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// Exclude items ignored by Git in Git repositories, by reading the ignore files directly
+///
+/// This is a lot faster than [`gitignore`](super::gitignore) as no command is run, and each ignore file is only read once.
+/// The following files are taken into account, by decreasing precedence:
+///
+/// * The `.gitignore` files of the item's parent directories, up to the repository's root (the nearest one winning)
+/// * The repository's `.git/info/exclude` file
+/// * The global excludes file (Git's `core.excludesFile` option)
+///
+/// Negation patterns (`!pattern`) and directory-only patterns (`pattern/`) are supported. Nested repositories
+/// (directories containing a `.git` item) don't inherit from the ignore files of the repositories they are located in.
+///
+/// ```
+/// use rebackup::{rules, walk, WalkerConfig};
+/// use std::fs;
+///
+/// let dir = std::env::temp_dir().join("rebackup-doctest-respect-gitignore");
+/// # let _ = fs::remove_dir_all(&dir);
+/// for sub_dir in &["repo/.git/info", "repo/build", "repo/src/logs", "repo/nested/.git"] {
+///     fs::create_dir_all(dir.join(sub_dir)).unwrap();
+/// }
+///
+/// fs::write(dir.join("repo/.gitignore"), "*.log\nbuild/\n").unwrap();
+/// fs::write(dir.join("repo/.git/info/exclude"), "*.tmp\n").unwrap();
+/// fs::write(dir.join("repo/src/.gitignore"), "!important.log\nlogs\n").unwrap();
+/// fs::write(dir.join("repo/nested/.gitignore"), "*.tmp\n").unwrap();
+///
+/// for file in &["build/out", "main.rs", "debug.log", "cache.tmp", "src/important.log", "src/other.log", "src/logs/a",
+///               "nested/debug.log", "nested/cache.tmp"] {
+///     fs::write(dir.join("repo").join(file), "").unwrap();
+/// }
+///
+/// let dir = fs::canonicalize(&dir).unwrap();
+/// let mut config = WalkerConfig::new(vec![rules::dotgit(), rules::respect_gitignore()]);
+/// config.sort_entries = true;
+///
+/// let items: Vec<_> = walk(&dir, &config).unwrap().into_iter()
+///     .map(|path| path.strip_prefix(&dir).unwrap().to_string_lossy().into_owned())
+///     .collect();
+///
+/// assert_eq!(items, vec![
+///     "repo/.gitignore",
+///     "repo/main.rs",
+///     "repo/nested/.gitignore",
+///     "repo/nested/debug.log",
+///     "repo/src/.gitignore",
+///     "repo/src/important.log",
+/// ]);
+/// # fs::remove_dir_all(&dir).unwrap();
+/// ```
+pub fn respect_gitignore() -> WalkerRule {
+    let cache = Arc::new(IgnoreFilesCache::new(".gitignore"));
+
+    WalkerRule {
+        name: "respect_gitignore",
+        description: Some("Exclude items ignored by Git (native)".to_string()),
+        priority: 0,
+        only_for: WalkerItemTypes::all(),
+        min_depth: None,
+        max_depth: None,
+        cacheable: false,
+        timeout: None,
+        // Git doesn't consider the content of `.git` directories
+        matches: Box::new(|ctx| Ok(!ctx.path.components().any(|component| component.as_os_str() == ".git"))),
+        action: Box::new(move |ctx| {
+            let parent = match ctx.path.parent() {
+                Some(parent) => parent,
+                None => return Ok(WalkerRuleResult::IncludeItem),
+            };
+
+            let repo_root = match cache.repo_root(parent) {
+                Some(repo_root) => repo_root,
+                None => return Ok(WalkerRuleResult::IncludeItem),
+            };
+
+            let is_dir = ctx.item_type == WalkerItemType::Directory;
+
+            // Check the '.gitignore' files from the nearest one up to the repository's root, then the repository's excludes
+            let matchers = parent
+                .ancestors()
+                .take_while(|dir| dir.starts_with(&repo_root))
+                .map(|dir| cache.dir_matcher(dir))
+                .chain(std::iter::once_with(|| cache.repo_matcher(&repo_root)));
+
+            for matcher in matchers {
+                match matcher?.matched(ctx.path, is_dir) {
+                    Match::None => continue,
+                    Match::Ignore(_) => return Ok(WalkerRuleResult::ExcludeItem),
+                    Match::Whitelist(_) => return Ok(WalkerRuleResult::IncludeItem),
+                }
+            }
+
+            Ok(WalkerRuleResult::IncludeItem)
+        }),
+    }
+}
+
+/// Exclude items based on ignore files with a specific name (e.g. `.rebackupignore`), located in any directory
+///
+/// Ignore files use the same syntax as `.gitignore` files, including negation patterns (`!pattern`) to re-include items.
+/// Their patterns are relative to the directory containing them, and apply to all its descendants.
+///
+/// All ignore files from the item's parent directory up to the source directory are taken into account, the nearest
+/// ones taking precedence over the others. Each ignore file is only read once.
+///
+/// ```
+/// use rebackup::{rules, walk, WalkerConfig};
+/// use std::fs;
+///
+/// let dir = std::env::temp_dir().join("rebackup-doctest-ignore-file");
+/// # let _ = fs::remove_dir_all(&dir);
+/// fs::create_dir_all(dir.join("docs/drafts")).unwrap();
+///
+/// fs::write(dir.join(".rebackupignore"), "*.bak\n/docs/drafts/\n").unwrap();
+/// fs::write(dir.join("docs/.rebackupignore"), "!keep.bak\n").unwrap();
+///
+/// for file in &["notes.txt", "notes.bak", "docs/keep.bak", "docs/other.bak", "docs/drafts/draft.txt"] {
+///     fs::write(dir.join(file), "").unwrap();
+/// }
+///
+/// let dir = fs::canonicalize(&dir).unwrap();
+/// let mut config = WalkerConfig::new(vec![rules::ignore_file(".rebackupignore")]);
+/// config.sort_entries = true;
+///
+/// let items: Vec<_> = walk(&dir, &config).unwrap().into_iter()
+///     .map(|path| path.strip_prefix(&dir).unwrap().to_string_lossy().into_owned())
+///     .collect();
+///
+/// assert_eq!(items, vec![".rebackupignore", "docs/.rebackupignore", "docs/keep.bak", "notes.txt"]);
+/// # fs::remove_dir_all(&dir).unwrap();
+/// ```
+pub fn ignore_file(file_name: &str) -> WalkerRule {
+    let cache = Arc::new(IgnoreFilesCache::new(file_name));
+
+    WalkerRule {
+        name: "ignore_file",
+        description: Some(format!("Ignore files: {}", file_name)),
+        priority: 0,
+        only_for: WalkerItemTypes::all(),
+        min_depth: None,
+        max_depth: None,
+        cacheable: false,
+        timeout: None,
+        matches: Box::new(|ctx| Ok(ctx.path != ctx.source)),
+        action: Box::new(move |ctx| {
+            let parent = match ctx.path.parent() {
+                Some(parent) => parent,
+                None => return Ok(WalkerRuleResult::IncludeItem),
+            };
+
+            let is_dir = ctx.item_type == WalkerItemType::Directory;
+
+            for dir in parent.ancestors().take_while(|dir| dir.starts_with(ctx.source)) {
+                match cache.dir_matcher(dir)?.matched(ctx.path, is_dir) {
+                    Match::None => continue,
+                    Match::Ignore(_) => return Ok(WalkerRuleResult::ExcludeItem),
+                    Match::Whitelist(_) => return Ok(WalkerRuleResult::IncludeItem),
+                }
+            }
+
+            Ok(WalkerRuleResult::IncludeItem)
+        }),
+    }
+}
+
+/// (Internal) Cache of the compiled ignore files
+struct IgnoreFilesCache {
+    /// Name of the ignore files
+    file_name: OsString,
+
+    /// Root of the repository each directory is located in (if any)
+    repo_roots: Mutex<HashMap<PathBuf, Option<PathBuf>>>,
+
+    /// Compiled ignore file of each directory
+    dirs: Mutex<HashMap<PathBuf, Arc<Gitignore>>>,
+
+    /// Compiled excludes of each repository (`.git/info/exclude` and global excludes)
+    repos: Mutex<HashMap<PathBuf, Arc<Gitignore>>>,
+}
+
+impl IgnoreFilesCache {
+    fn new(file_name: &str) -> Self {
+        Self {
+            file_name: OsString::from(file_name),
+            repo_roots: Mutex::new(HashMap::new()),
+            dirs: Mutex::new(HashMap::new()),
+            repos: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Get the root of the repository a directory is located in
+    fn repo_root(&self, dir: &Path) -> Option<PathBuf> {
+        if let Some(repo_root) = self.repo_roots.lock().expect("Internal error: ignore files cache's lock is poisoned").get(dir) {
+            return repo_root.clone();
+        }
+
+        let repo_root = if dir.join(".git").symlink_metadata().is_ok() {
+            Some(dir.to_path_buf())
+        } else {
+            dir.parent().and_then(|parent| self.repo_root(parent))
+        };
+
+        self.repo_roots
+            .lock()
+            .expect("Internal error: ignore files cache's lock is poisoned")
+            .insert(dir.to_path_buf(), repo_root.clone());
+
+        repo_root
+    }
+
+    /// Get the compiled ignore file of a directory
+    fn dir_matcher(&self, dir: &Path) -> Result<Arc<Gitignore>, io::Error> {
+        let mut dirs = self.dirs.lock().expect("Internal error: ignore files cache's lock is poisoned");
+
+        if let Some(matcher) = dirs.get(dir) {
+            return Ok(Arc::clone(matcher));
+        }
+
+        let matcher = Arc::new(build_matcher(dir, &[dir.join(&self.file_name)])?);
+        dirs.insert(dir.to_path_buf(), Arc::clone(&matcher));
+
+        Ok(matcher)
+    }
+
+    /// Get the compiled excludes of a repository
+    fn repo_matcher(&self, repo_root: &Path) -> Result<Arc<Gitignore>, io::Error> {
+        let mut repos = self.repos.lock().expect("Internal error: ignore files cache's lock is poisoned");
+
+        if let Some(matcher) = repos.get(repo_root) {
+            return Ok(Arc::clone(matcher));
+        }
+
+        // Patterns added last take precedence, so the global excludes must come first
+        let mut files: Vec<_> = gitconfig_excludes_path().into_iter().collect();
+        files.push(repo_root.join(".git").join("info").join("exclude"));
+
+        let matcher = Arc::new(build_matcher(repo_root, &files)?);
+        repos.insert(repo_root.to_path_buf(), Arc::clone(&matcher));
+
+        Ok(matcher)
+    }
+}
+
+/// (Internal) Compile ignore files (missing ones are skipped, as well as invalid patterns like Git does)
+fn build_matcher(root: &Path, files: &[PathBuf]) -> Result<Gitignore, io::Error> {
+    let mut builder = GitignoreBuilder::new(root);
+
+    for file in files.iter().filter(|file| file.is_file()) {
+        if let Some(err) = builder.add(file) {
+            if err.is_io() {
+                return Err(io::Error::other(format!("Failed to read ignore file '{}': {}", file.display(), err)));
+            }
+        }
+    }
+
+    builder.build().map_err(io::Error::other)
+}
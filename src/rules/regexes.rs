@@ -0,0 +1,112 @@
+//! Rules including or excluding items based on regular expressions (requires the `regex` feature)
+
+use crate::config::{RuleCtx, WalkerItemType, WalkerItemTypes, WalkerRule, WalkerRuleResult};
+use regex::{Regex, RegexSet};
+use std::borrow::Cow;
+
+/// Exclude items whose path relative to the source directory matches a regular expression
+///
+/// Paths always use `/` as a separator, on all platforms. Items located outside of the source directory
+/// are matched using their absolute path.
+///
+/// ```
+/// use rebackup::{rules, walk, WalkerConfig};
+/// use regex::Regex;
+/// use std::fs;
+///
+/// let dir = std::env::temp_dir().join("rebackup-doctest-exclude-regex");
+/// # let _ = fs::remove_dir_all(&dir);
+/// fs::create_dir_all(dir.join("build")).unwrap();
+/// fs::create_dir_all(dir.join("src/build")).unwrap();
+///
+/// for file in ["build/out.o", "src/build/gen.rs", "src/main.rs", "d41d8cd98f00b204e9800998ecf8427e", "src/0123456789abcdef0123456789abcdef", "src/0123456789abcdefg"] {
+///     fs::write(dir.join(file), "").unwrap();
+/// }
+///
+/// let dir = fs::canonicalize(&dir).unwrap();
+///
+/// let config = WalkerConfig::new(vec![
+///     // Only the top-level "build" directory
+///     rules::exclude_regex(Regex::new("^build$").unwrap()),
+///     // Files whose name is exactly 32 hexadecimal characters
+///     rules::exclude_regex(Regex::new("(^|/)[0-9a-f]{32}$").unwrap()),
+/// ]);
+///
+/// let mut items: Vec<_> = walk(&dir, &config)
+///     .unwrap()
+///     .into_iter()
+///     .map(|item| item.strip_prefix(&dir).unwrap().to_string_lossy().into_owned())
+///     .collect();
+///
+/// items.sort();
+///
+/// assert_eq!(items, vec!["src/0123456789abcdefg", "src/build/gen.rs", "src/main.rs"]);
+/// # fs::remove_dir_all(&dir).unwrap();
+/// ```
+pub fn exclude_regex(re: Regex) -> WalkerRule {
+    WalkerRule {
+        name: "exclude_regex",
+        description: Some(format!("Exclude items matching regex: {}", re)),
+        priority: 0,
+        only_for: WalkerItemTypes::all(),
+        min_depth: None,
+        max_depth: None,
+        cacheable: false,
+        timeout: None,
+        matches: Box::new(move |ctx| Ok(re.is_match(&regex_target(ctx)))),
+        action: Box::new(|_| Ok(WalkerRuleResult::ExcludeItem)),
+    }
+}
+
+/// Exclude items whose path relative to the source directory doesn't match any of the provided regular expressions
+///
+/// Directories are never excluded, so their content can still be matched. Paths are matched like in [`exclude_regex`].
+///
+/// ```
+/// use rebackup::{rules, walk, WalkerConfig};
+/// use regex::RegexSet;
+/// use std::fs;
+///
+/// let dir = std::env::temp_dir().join("rebackup-doctest-include-only-regex");
+/// # let _ = fs::remove_dir_all(&dir);
+/// fs::create_dir_all(dir.join("src")).unwrap();
+///
+/// for file in ["src/main.rs", "src/lib.rs", "src/notes.txt", "README.md", "Cargo.toml"] {
+///     fs::write(dir.join(file), "").unwrap();
+/// }
+///
+/// let dir = fs::canonicalize(&dir).unwrap();
+///
+/// let config = WalkerConfig::new(vec![rules::include_only_regex(RegexSet::new(&[r"\.rs$", "^[A-Z]+\\.md$"]).unwrap())]);
+///
+/// let mut items = walk(&dir, &config).unwrap();
+/// items.sort();
+///
+/// assert_eq!(items, vec![dir.join("README.md"), dir.join("src/lib.rs"), dir.join("src/main.rs")]);
+/// # fs::remove_dir_all(&dir).unwrap();
+/// ```
+pub fn include_only_regex(set: RegexSet) -> WalkerRule {
+    WalkerRule {
+        name: "include_only_regex",
+        description: Some(format!("Only include items matching any of {} regex(es)", set.len())),
+        priority: 0,
+        only_for: WalkerItemTypes::all(),
+        min_depth: None,
+        max_depth: None,
+        cacheable: false,
+        timeout: None,
+        matches: Box::new(move |ctx| Ok(ctx.item_type != WalkerItemType::Directory && !set.is_match(&regex_target(ctx)))),
+        action: Box::new(|_| Ok(WalkerRuleResult::ExcludeItem)),
+    }
+}
+
+/// (Internal) Get the path of an item regular expressions must be matched against, with `/` separators
+fn regex_target<'a>(ctx: &RuleCtx<'a>) -> Cow<'a, str> {
+    let path = ctx.path.strip_prefix(ctx.source).unwrap_or(ctx.path).to_string_lossy();
+
+    if cfg!(windows) {
+        Cow::Owned(path.replace('\\', "/"))
+    } else {
+        path
+    }
+}
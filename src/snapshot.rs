@@ -0,0 +1,350 @@
+//! # The snapshot module
+//!
+//! This module contains [`Snapshot`], which records the size, modification time and optionally the checksum of the items of a files list,
+//! so the files list can later be compared to another one (see [`Snapshot::diff`]).
+//!
+//! Snapshots can be saved to and read from a simple, versioned text format (see [`Snapshot::write_to`]).
+
+use crate::config::WalkerItemType;
+use crate::item::WalkerItem;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Header line of the snapshots' text format
+const SNAPSHOT_HEADER: &str = "rebackup-snapshot v2";
+
+/// Header line of the first version of the snapshots' text format (which has no checksums column)
+const SNAPSHOT_HEADER_V1: &str = "rebackup-snapshot v1";
+
+/// Size and modification time of the items of a files list
+///
+/// ```
+/// use rebackup::{walk_detailed, Snapshot, WalkerConfig};
+/// use std::fs;
+/// use std::time::Duration;
+///
+/// let dir = std::env::temp_dir().join("rebackup-doctest-snapshot");
+/// # let _ = fs::remove_dir_all(&dir);
+/// fs::create_dir_all(&dir).unwrap();
+/// fs::write(dir.join("kept"), "kept").unwrap();
+/// fs::write(dir.join("modified"), "old content").unwrap();
+/// fs::write(dir.join("removed"), "").unwrap();
+///
+/// let config = WalkerConfig::default();
+/// let old = Snapshot::from_items(&walk_detailed(&dir, &config).unwrap());
+///
+/// fs::write(dir.join("modified"), "new").unwrap();
+/// fs::remove_file(dir.join("removed")).unwrap();
+/// fs::write(dir.join("added"), "").unwrap();
+///
+/// let new = Snapshot::from_items(&walk_detailed(&dir, &config).unwrap());
+/// let diff = Snapshot::diff(&old, &new);
+///
+/// assert_eq!(diff.added, vec![dir.join("added")]);
+/// assert_eq!(diff.removed, vec![dir.join("removed")]);
+/// assert_eq!(diff.modified, vec![dir.join("modified")]);
+///
+/// // Snapshots can be saved and read back
+/// let mut saved = vec![];
+/// new.write_to(&mut saved).unwrap();
+///
+/// assert_eq!(Snapshot::read_from(saved.as_slice()).unwrap(), new);
+///
+/// // Modification times can be compared with a tolerance (e.g. for filesystems with a coarse precision)
+/// let mut coarse = new.clone();
+///
+/// for entry in coarse.entries.values_mut() {
+///     entry.modified = entry.modified.map(|modified| modified + Duration::from_millis(1500));
+/// }
+///
+/// assert_eq!(Snapshot::diff(&new, &coarse).modified.len(), 3);
+/// assert!(Snapshot::diff_within(&new, &coarse, Duration::from_secs(2)).modified.is_empty());
+/// # fs::remove_dir_all(&dir).unwrap();
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Snapshot {
+    /// Items of the files list, indexed by their path (as reported in the files list)
+    pub entries: BTreeMap<PathBuf, SnapshotEntry>,
+}
+
+/// Informations about an item of a [`Snapshot`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SnapshotEntry {
+    /// Item's type
+    pub item_type: WalkerItemType,
+
+    /// Item's size, in bytes
+    pub size: u64,
+
+    /// Item's modification time (if available on the current platform)
+    pub modified: Option<SystemTime>,
+
+    /// Checksum of the item's content (e.g. an hexadecimal SHA-256 digest), if it was computed
+    ///
+    /// Checksums must not contain whitespaces.
+    pub checksum: Option<String>,
+}
+
+/// Differences between two [snapshots](Snapshot) (see [`Snapshot::diff`])
+///
+/// All lists are sorted by path.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SnapshotDiff {
+    /// Items that are only present in the new snapshot
+    pub added: Vec<PathBuf>,
+
+    /// Items that are only present in the old snapshot
+    pub removed: Vec<PathBuf>,
+
+    /// Items that are present in both snapshots, but whose type, size or modification time changed
+    pub modified: Vec<PathBuf>,
+}
+
+impl SnapshotEntry {
+    /// Get the informations of an item from its metadata (symbolic links must not be followed)
+    pub fn from_metadata(metadata: &fs::Metadata) -> Self {
+        Self {
+            item_type: WalkerItemType::from_file_type(metadata.file_type()),
+            size: metadata.len(),
+            modified: metadata.modified().ok(),
+            checksum: None,
+        }
+    }
+
+    /// Check if an item changed between two snapshots
+    ///
+    /// Items whose type changed (e.g. a file that was replaced by a directory) are always considered as changed.
+    /// Files and symbolic links are considered as changed if either their size or their modification time is different,
+    /// so a change of size is detected even if the modification time went backwards (e.g. because of a clock skew).
+    /// The size and modification time of directories are not compared, as they only depend on their entries.
+    ///
+    /// If both entries have a checksum, they are compared instead of the modification times.
+    pub fn changed_from(&self, old: &SnapshotEntry) -> bool {
+        self.changed_within(old, Duration::ZERO)
+    }
+
+    /// Check if an item changed between two snapshots, considering modification times within `mtime_window` of each other as equal
+    /// (see [`SnapshotEntry::changed_from`])
+    ///
+    /// This is useful for filesystems which store modification times with a coarse precision (e.g. two seconds for FAT).
+    pub fn changed_within(&self, old: &SnapshotEntry, mtime_window: Duration) -> bool {
+        if self.item_type != old.item_type {
+            return true;
+        }
+
+        if self.item_type == WalkerItemType::Directory {
+            return false;
+        }
+
+        if self.size != old.size {
+            return true;
+        }
+
+        if let (Some(checksum), Some(old_checksum)) = (&self.checksum, &old.checksum) {
+            return checksum != old_checksum;
+        }
+
+        match (self.modified, old.modified) {
+            (Some(modified), Some(old_modified)) => {
+                let difference = modified.duration_since(old_modified).unwrap_or_else(|err| err.duration());
+                difference > mtime_window
+            }
+            (modified, old_modified) => modified != old_modified,
+        }
+    }
+}
+
+impl Snapshot {
+    /// Create an empty snapshot
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a snapshot from the items of a detailed files list (see [`walk_detailed`](crate::walk_detailed))
+    pub fn from_items(items: &[WalkerItem]) -> Self {
+        Self {
+            entries: items
+                .iter()
+                .map(|item| (item.path.clone(), SnapshotEntry::from_metadata(&item.metadata)))
+                .collect(),
+        }
+    }
+
+    /// Add an item to the snapshot from its metadata (symbolic links must not be followed)
+    pub fn insert(&mut self, path: PathBuf, metadata: &fs::Metadata) {
+        self.entries.insert(path, SnapshotEntry::from_metadata(metadata));
+    }
+
+    /// Compare an old snapshot to a new one (see [`SnapshotEntry::changed_from`])
+    ///
+    /// Both snapshots must use the same paths style (see [`WalkerConfig::output_paths`](crate::WalkerConfig::output_paths)).
+    pub fn diff(old: &Snapshot, new: &Snapshot) -> SnapshotDiff {
+        Self::diff_within(old, new, Duration::ZERO)
+    }
+
+    /// Compare an old snapshot to a new one, with a tolerance for modification times (see [`SnapshotEntry::changed_within`])
+    ///
+    /// Both snapshots must use the same paths style (see [`WalkerConfig::output_paths`](crate::WalkerConfig::output_paths)).
+    pub fn diff_within(old: &Snapshot, new: &Snapshot, mtime_window: Duration) -> SnapshotDiff {
+        let mut diff = SnapshotDiff::default();
+
+        for (path, entry) in &new.entries {
+            match old.entries.get(path) {
+                None => diff.added.push(path.clone()),
+                Some(old_entry) if entry.changed_within(old_entry, mtime_window) => diff.modified.push(path.clone()),
+                Some(_) => {}
+            }
+        }
+
+        diff.removed = old.entries.keys().filter(|path| !new.entries.contains_key(*path)).cloned().collect();
+
+        diff
+    }
+
+    /// Write the snapshot in its text format
+    ///
+    /// The first line is a header indicating the format's version, followed by one line per item with its type
+    /// (`f` for files, `d` for directories, `l` for symbolic links and `o` for special files), size in bytes,
+    /// modification time as seconds and nanoseconds since the Unix epoch (`-` if unavailable), checksum (`-` if unavailable)
+    /// and path, separated by tabs.
+    ///
+    /// Backslashes and line breaks in paths are escaped. Paths must be valid UTF-8.
+    pub fn write_to(&self, mut writer: impl Write) -> io::Result<()> {
+        writeln!(writer, "{}", SNAPSHOT_HEADER)?;
+
+        for (path, entry) in &self.entries {
+            let path = path
+                .to_str()
+                .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, format!("Invalid UTF-8 path: {}", path.display())))?;
+
+            let item_type = match entry.item_type {
+                WalkerItemType::File => 'f',
+                WalkerItemType::Directory => 'd',
+                WalkerItemType::Symlink => 'l',
+                WalkerItemType::Other => 'o',
+            };
+
+            let modified = match entry.modified.map(|modified| modified.duration_since(UNIX_EPOCH)) {
+                Some(Ok(modified)) => format!("{}.{:09}", modified.as_secs(), modified.subsec_nanos()),
+                _ => "-".to_string(),
+            };
+
+            let checksum = match &entry.checksum {
+                Some(checksum) if checksum.is_empty() || checksum == "-" || checksum.contains(char::is_whitespace) => {
+                    return Err(io::Error::new(io::ErrorKind::InvalidData, format!("Invalid checksum: {:?}", checksum)));
+                }
+                Some(checksum) => checksum.as_str(),
+                None => "-",
+            };
+
+            let path = path.replace('\\', "\\\\").replace('\n', "\\n").replace('\r', "\\r");
+
+            writeln!(writer, "{}\t{}\t{}\t{}\t{}", item_type, entry.size, modified, checksum, path)?;
+        }
+
+        writer.flush()
+    }
+
+    /// Read a snapshot from its text format (see [`Snapshot::write_to`])
+    ///
+    /// Snapshots written in the first version of the format, which has no checksums column, are supported as well.
+    pub fn read_from(reader: impl BufRead) -> io::Result<Self> {
+        let invalid = |line: usize, message: &str| io::Error::new(io::ErrorKind::InvalidData, format!("{} (at line {})", message, line));
+
+        let mut lines = reader.lines();
+
+        let with_checksums = match lines.next().transpose()? {
+            Some(header) if header == SNAPSHOT_HEADER => true,
+            Some(header) if header == SNAPSHOT_HEADER_V1 => false,
+            Some(header) if header.starts_with("rebackup-snapshot ") => return Err(invalid(1, "Unsupported snapshot version")),
+            _ => return Err(invalid(1, "Missing snapshot header")),
+        };
+
+        let mut snapshot = Self::new();
+
+        for (index, line) in lines.enumerate() {
+            let line = line?;
+            let line_nr = index + 2;
+
+            if line.is_empty() {
+                continue;
+            }
+
+            let mut columns = line.splitn(if with_checksums { 5 } else { 4 }, '\t');
+
+            let item_type = match columns.next() {
+                Some("f") => WalkerItemType::File,
+                Some("d") => WalkerItemType::Directory,
+                Some("l") => WalkerItemType::Symlink,
+                Some("o") => WalkerItemType::Other,
+                _ => return Err(invalid(line_nr, "Invalid item type")),
+            };
+
+            let size = columns
+                .next()
+                .and_then(|size| size.parse().ok())
+                .ok_or_else(|| invalid(line_nr, "Invalid item size"))?;
+
+            let modified = match columns.next() {
+                Some("-") => None,
+                Some(modified) => Some(parse_timestamp(modified).ok_or_else(|| invalid(line_nr, "Invalid modification time"))?),
+                None => return Err(invalid(line_nr, "Missing modification time")),
+            };
+
+            let checksum = match columns.next() {
+                _ if !with_checksums => None,
+                Some("-") => None,
+                Some(checksum) => Some(checksum.to_string()),
+                None => return Err(invalid(line_nr, "Missing checksum")),
+            };
+
+            let path = columns.next().ok_or_else(|| invalid(line_nr, "Missing item path"))?;
+            let path = unescape_path(path).ok_or_else(|| invalid(line_nr, "Invalid escape sequence in item path"))?;
+
+            snapshot.entries.insert(
+                PathBuf::from(path),
+                SnapshotEntry {
+                    item_type,
+                    size,
+                    modified,
+                    checksum,
+                },
+            );
+        }
+
+        Ok(snapshot)
+    }
+}
+
+/// (Internal) Parse a timestamp written as seconds and nanoseconds since the Unix epoch
+fn parse_timestamp(timestamp: &str) -> Option<SystemTime> {
+    let (secs, nanos) = timestamp.split_once('.')?;
+    let duration = Duration::new(secs.parse().ok()?, nanos.parse().ok()?);
+
+    UNIX_EPOCH.checked_add(duration)
+}
+
+/// (Internal) Unescape a path written by [`Snapshot::write_to`]
+fn unescape_path(path: &str) -> Option<String> {
+    let mut out = String::with_capacity(path.len());
+    let mut chars = path.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+
+        out.push(match chars.next()? {
+            '\\' => '\\',
+            'n' => '\n',
+            'r' => '\r',
+            _ => return None,
+        });
+    }
+
+    Some(out)
+}
@@ -3,14 +3,35 @@
 //! This module contains the [walker](walk), which is the algorithm used to traverse filesystem items
 //! in order to build the files list.
 
-use crate::config::{WalkerConfig, WalkerRule, WalkerRuleResult};
-use crate::WalkerItemType;
-use std::collections::HashSet;
+use crate::config::{
+    DirEmitPolicy, ErrorPolicy, MappingValidation, PathStyle, RuleCtx, SymlinkEscapePolicy, SymlinkMode, TraversalStrategy, WalkerConfig, WalkerItemType,
+    WalkerRule, WalkerRuleResult,
+};
+use crate::filesystem::{DirEntriesIter, FileSystem};
+use crate::item::WalkerItem;
+use crate::logger::{json_message, log_format, log_level, with_status_line_cleared, LoggerFormat, LoggerLevel};
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::fmt;
 use std::fs;
-use std::path::{Path, PathBuf};
+use std::ops::ControlFlow;
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Instant;
 use thiserror::Error;
 
+mod parallel;
+mod stats;
+#[cfg(feature = "async-walker")]
+mod stream;
+
+pub use parallel::walk_parallel;
+pub use stats::{DuplicateCause, DuplicateItem, RuleStats, WalkStats};
+#[cfg(feature = "async-walker")]
+pub use stream::{walk_async, WalkStream};
+
+use stats::{ItemsCounters, RuleCounters};
+
 /// Walk through a directory (recursively) to build a list of files to backup
 ///
 /// ## Path conversion
@@ -23,159 +44,1692 @@ use thiserror::Error;
 ///
 /// ## Traversal order
 ///
-/// Traversal is performed up-to-down, in the order provided by the result of [`std::fs::read_dir`].
+/// Traversal is performed up-to-down, in the order provided by the result of [`std::fs::read_dir`]
+/// (or sorted by file name if [`WalkerConfig::sort_entries`] is enabled).
 ///
 /// ## Error handling
 ///
 /// If an error occurs (I/O error or if a rule fails), the files list won't be built and a [`WalkerErr`] value will be returned instead.
+///
+/// If [`WalkerConfig::on_error`] is set to [`ErrorPolicy::Collect`], recoverable errors (see [`WalkerErr::is_recoverable`]) are
+/// logged and the faulty items skipped instead. Use [`walk_tolerant`] to get the list of these errors.
 pub fn walk(dir: &Path, config: &WalkerConfig) -> Result<Vec<PathBuf>, WalkerErr> {
-    let dir = fs::canonicalize(dir).map_err(|err| WalkerErr::FailedToCanonicalize(dir.to_path_buf(), err))?;
+    let mut items = vec![];
+
+    drive(&mut WalkIter::new(dir, config), |item| {
+        items.push(item.path);
+        ControlFlow::Continue(())
+    })?;
+
+    Ok(items)
+}
+
+/// Walk through a directory (recursively) to build a list of files to backup, collecting errors instead of aborting
+///
+/// This behaves like [`walk`], but when [`WalkerConfig::on_error`] is set to [`ErrorPolicy::Collect`], recoverable errors
+/// (see [`WalkerErr::is_recoverable`]) are returned alongside the partial files list instead of only being logged.
+///
+/// Unrecoverable errors (like a failing rule) still abort the walk.
+pub fn walk_tolerant(dir: &Path, config: &WalkerConfig) -> Result<WalkOutcome, WalkerErr> {
+    let mut iter = WalkIter::new(dir, config);
+
+    if let Some(err) = iter.init_err.take() {
+        return Err(err);
+    }
+
+    let mut items = vec![];
+    let mut errors = vec![];
+
+    drive_tolerant(&mut iter, |item| items.push(item.path), &mut errors)?;
+
+    Ok(WalkOutcome {
+        items,
+        errors,
+        stats: iter.stats(),
+        exclusions: iter.take_exclusions(),
+    })
+}
+
+/// Walk through a directory (recursively) to build a list of files to backup, alongside [statistics](WalkStats) about the walk
+///
+/// This behaves exactly like [`walk`]. Rules' counters are always collected, while the time spent in their callbacks
+/// is only measured when [`WalkerConfig::time_rules`] is enabled.
+///
+/// Counts and sizes of the listed items are computed from the metadata the walker already reads:
+///
+/// ```
+/// use rebackup::{walk_with_stats, WalkerConfig, WalkerRule};
+/// use std::fs;
+///
+/// let dir = std::env::temp_dir().join("rebackup-doctest-walk-with-stats");
+/// # let _ = fs::remove_dir_all(&dir);
+/// fs::create_dir_all(dir.join("sub")).unwrap();
+/// fs::create_dir_all(dir.join("empty")).unwrap();
+/// fs::create_dir_all(dir.join("node_modules")).unwrap();
+/// fs::write(dir.join("a.txt"), vec![0; 100]).unwrap();
+/// fs::write(dir.join("sub/b.txt"), vec![0; 2500]).unwrap();
+/// fs::write(dir.join("sub/c.txt"), vec![0; 42]).unwrap();
+/// fs::write(dir.join("node_modules/d.js"), vec![0; 1000]).unwrap();
+///
+/// let config = WalkerConfig::builder().rule(WalkerRule::exclude_dir_named("node_modules")).build();
+/// let (items, stats) = walk_with_stats(&dir, &config).unwrap();
+///
+/// assert_eq!(items.len(), 4);
+/// assert_eq!(stats.files, 3);
+/// assert_eq!(stats.directories, 1);
+/// assert_eq!(stats.total_size, 2642);
+/// assert_eq!(stats.excluded, 1);
+/// assert_eq!(stats.largest_files[0], (dir.join("sub/b.txt"), 2500));
+/// assert_eq!(stats.largest_files.len(), 3);
+///
+/// // The rule was run on every directory, and excluded one of them
+/// assert_eq!(stats.rules.len(), 1);
+/// assert_eq!(stats.rules[0].evaluated, 3);
+/// assert_eq!(stats.rules[0].excluded, 1);
+/// # fs::remove_dir_all(&dir).unwrap();
+/// ```
+pub fn walk_with_stats(dir: &Path, config: &WalkerConfig) -> Result<(Vec<PathBuf>, WalkStats), WalkerErr> {
+    let mut iter = WalkIter::new(dir, config);
+    let mut items = vec![];
+
+    drive(&mut iter, |item| {
+        items.push(item.path);
+        ControlFlow::Continue(())
+    })?;
+
+    Ok((items, iter.stats()))
+}
+
+/// (Internal) Run the walker until it ends, collecting recoverable errors if asked to (see [`walk_tolerant`])
+fn drive_tolerant(iter: &mut WalkIter, mut on_item: impl FnMut(WalkerItem), errors: &mut Vec<WalkerErr>) -> Result<(), WalkerErr> {
+    while let Some(item) = iter.next_item() {
+        match item {
+            Ok(item) => on_item(item),
+            Err(err) if iter.config.on_error == ErrorPolicy::Collect && err.is_recoverable() => {
+                err.log(LoggerLevel::Error, "");
+                errors.push(err);
+            }
+            Err(err) => return Err(err),
+        }
+    }
+
+    Ok(())
+}
+
+/// Result of a [tolerant walk](walk_tolerant)
+#[derive(Debug)]
+pub struct WalkOutcome {
+    /// Files to backup
+    pub items: Vec<PathBuf>,
+
+    /// Errors that occurred during the walk (the related items were skipped)
+    pub errors: Vec<WalkerErr>,
+
+    /// Statistics about the walk
+    pub stats: WalkStats,
+
+    /// Items excluded or mapped by rules (only when [`WalkerConfig::explain`] is enabled, see [`walk_explain`])
+    pub exclusions: WalkExclusions,
+}
+
+/// Reason why an item is not part of the files list (see [`walk_explain`])
+#[derive(Debug, Clone)]
+pub struct ExclusionReason {
+    /// Name of the rule which excluded or mapped the item
+    pub rule_name: &'static str,
+
+    /// Optional description of the rule
+    pub rule_description: Option<String>,
+
+    /// Result returned by the rule: either [`WalkerRuleResult::ExcludeItem`], [`WalkerRuleResult::MapAsList`]
+    /// or [`WalkerRuleResult::MapAsListFromSource`] (with the mapped items' absolute paths)
+    pub result: WalkerRuleResult,
+}
+
+/// Items excluded or mapped by rules, alongside the reason why (see [`walk_explain`])
+pub type WalkExclusions = Vec<(PathBuf, ExclusionReason)>;
+
+/// Walk through a directory (recursively) to build a list of files to backup, explaining why items were left out
+///
+/// This behaves exactly like [`walk`], but also returns each item that was excluded or mapped by a rule, alongside
+/// the [reason](ExclusionReason) (the rule's name and result). This is useful to understand why an item is missing
+/// from the files list.
+///
+/// The descendants of excluded directories are not traversed, so they are not listed.
+pub fn walk_explain(dir: &Path, config: &WalkerConfig) -> Result<(Vec<PathBuf>, WalkExclusions), WalkerErr> {
+    let mut iter = WalkIter::new(dir, config);
+    let mut items = vec![];
+
+    iter.explain = true;
+
+    drive(&mut iter, |item| {
+        items.push(item.path);
+        ControlFlow::Continue(())
+    })?;
+
+    Ok((items, iter.take_exclusions()))
+}
+
+/// Walk through a directory (recursively) to build a detailed list of files to backup
+///
+/// This behaves exactly like [`walk`], but returns each item's informations (type, metadata, symbolic link's target)
+/// alongside its path. As the walker already needs to get each item's metadata, this comes at almost no cost.
+///
+/// When [`WalkerConfig::dedup_hard_links`] is enabled, the hard links that were skipped are listed in [`WalkerItem::hard_links`].
+pub fn walk_detailed(dir: &Path, config: &WalkerConfig) -> Result<Vec<WalkerItem>, WalkerErr> {
+    let mut iter = WalkIter::new(dir, config);
+    let mut items = vec![];
+
+    drive(&mut iter, |item| {
+        items.push(item);
+        ControlFlow::Continue(())
+    })?;
+
+    let mut state = iter.state();
+
+    if !state.hard_links.is_empty() {
+        for item in &mut items {
+            // Relative paths are relative to the source directory (see [`WalkerConfig::output_paths`])
+            let path = iter.source.join(item.renamed_from.as_ref().unwrap_or(&item.path));
+
+            if let Some(hard_links) = state.hard_links.remove(&path) {
+                item.hard_links = match config.output_paths {
+                    PathStyle::Absolute => hard_links,
+                    PathStyle::RelativeToSource => hard_links
+                        .into_iter()
+                        .map(|path| path.strip_prefix(&iter.source).map(Path::to_path_buf).unwrap_or(path))
+                        .collect(),
+                };
+            }
+        }
+    }
+
+    drop(state);
+
+    Ok(items)
+}
+
+/// Files to backup from multiple source directories, alongside the (canonicalized) source directory they were found in
+/// (see [`walk_multiple`])
+pub type RootsItems = Vec<(PathBuf, Vec<PathBuf>)>;
+
+/// Walk through multiple directories (recursively) to build a list of files to backup from each of them
+///
+/// This behaves like calling [`walk`] on each directory, but the history of visited items is shared between the walks,
+/// so items reachable from multiple directories (e.g. through symbolic links) are only listed once.
+///
+/// Directories located inside another one of the provided directories are dropped with a warning, as they will be
+/// traversed while walking the outer one. The files lists are returned in the order the directories were provided in,
+/// and can be merged with `.into_iter().flat_map(|(_, items)| items)`.
+///
+/// All directories are canonicalized before any of them is traversed.
+///
+/// ```
+/// use rebackup::{walk_multiple, WalkerConfig};
+/// use std::fs;
+///
+/// let dir = std::env::temp_dir().join("rebackup-doctest-walk-multiple");
+/// # let _ = fs::remove_dir_all(&dir);
+/// fs::create_dir_all(dir.join("etc/nested")).unwrap();
+/// fs::create_dir_all(dir.join("home")).unwrap();
+/// fs::write(dir.join("etc/nested/file"), "").unwrap();
+/// fs::write(dir.join("home/file"), "").unwrap();
+///
+/// let dir = fs::canonicalize(&dir).unwrap();
+/// let roots = vec![dir.join("etc"), dir.join("home"), dir.join("etc/nested")];
+///
+/// assert_eq!(
+///     walk_multiple(&roots, &WalkerConfig::default()).unwrap(),
+///     vec![
+///         (dir.join("etc"), vec![dir.join("etc/nested/file")]),
+///         (dir.join("home"), vec![dir.join("home/file")]),
+///     ]
+/// );
+/// # fs::remove_dir_all(&dir).unwrap();
+/// ```
+pub fn walk_multiple(roots: &[PathBuf], config: &WalkerConfig) -> Result<RootsItems, WalkerErr> {
+    let mut out = vec![];
+
+    walk_roots(roots, config, |root, iter| {
+        let mut items = vec![];
+
+        drive(iter, |item| {
+            items.push(item.path);
+            ControlFlow::Continue(())
+        })?;
+
+        out.push((root, items));
+        Ok(())
+    })?;
+
+    Ok(out)
+}
+
+/// Walk through multiple directories (recursively) to build a list of files to backup from each of them, collecting errors
+/// instead of aborting
+///
+/// This behaves like [`walk_multiple`], with the error handling of [`walk_tolerant`].
+pub fn walk_multiple_tolerant(roots: &[PathBuf], config: &WalkerConfig) -> Result<MultiWalkOutcome, WalkerErr> {
+    let mut outcome = MultiWalkOutcome::default();
+
+    walk_roots(roots, config, |root, iter| {
+        let mut items = vec![];
+
+        drive_tolerant(iter, |item| items.push(item.path), &mut outcome.errors)?;
+
+        outcome.items.push((root, items));
+        outcome.stats = iter.stats();
+        outcome.exclusions.extend(iter.take_exclusions());
+        Ok(())
+    })?;
+
+    Ok(outcome)
+}
+
+/// Walk through multiple directories (recursively) like [`walk_multiple_tolerant`], calling the provided function on each file
+/// to backup as soon as it is discovered instead of collecting them
+///
+/// The callback receives the source directory the item was found in, alongside the [detailed item](WalkerItem).
+/// The returned outcome's files lists are left empty, but still indicate which source directories were walked.
+///
+/// ```
+/// use rebackup::{walk_multiple_tolerant_with, WalkerConfig};
+/// use std::fs;
+///
+/// let dir = std::env::temp_dir().join("rebackup-doctest-walk-multiple-tolerant-with");
+/// # let _ = fs::remove_dir_all(&dir);
+/// fs::create_dir_all(dir.join("etc")).unwrap();
+/// fs::create_dir_all(dir.join("home")).unwrap();
+/// fs::write(dir.join("etc/file"), "").unwrap();
+/// fs::write(dir.join("home/file"), "").unwrap();
+///
+/// let dir = fs::canonicalize(&dir).unwrap();
+/// let mut found = vec![];
+///
+/// let outcome = walk_multiple_tolerant_with(&[dir.join("etc"), dir.join("home")], &WalkerConfig::default(), |source, item| {
+///     found.push((source.to_path_buf(), item.path));
+/// })
+/// .unwrap();
+///
+/// assert_eq!(found, vec![(dir.join("etc"), dir.join("etc/file")), (dir.join("home"), dir.join("home/file"))]);
+/// assert_eq!(outcome.items, vec![(dir.join("etc"), vec![]), (dir.join("home"), vec![])]);
+/// # fs::remove_dir_all(&dir).unwrap();
+/// ```
+pub fn walk_multiple_tolerant_with(
+    roots: &[PathBuf],
+    config: &WalkerConfig,
+    mut on_item: impl FnMut(&Path, WalkerItem),
+) -> Result<MultiWalkOutcome, WalkerErr> {
+    let mut outcome = MultiWalkOutcome::default();
+
+    walk_roots(roots, config, |root, iter| {
+        drive_tolerant(iter, |item| on_item(&root, item), &mut outcome.errors)?;
+
+        outcome.items.push((root, vec![]));
+        outcome.stats = iter.stats();
+        outcome.exclusions.extend(iter.take_exclusions());
+        Ok(())
+    })?;
+
+    Ok(outcome)
+}
+
+/// Result of a [tolerant walk through multiple directories](walk_multiple_tolerant)
+#[derive(Debug, Default)]
+pub struct MultiWalkOutcome {
+    /// Files to backup, alongside the source directory they were found in
+    pub items: RootsItems,
+
+    /// Errors that occurred during the walks (the related items were skipped)
+    pub errors: Vec<WalkerErr>,
+
+    /// Statistics about all the walks (cumulated)
+    pub stats: WalkStats,
+
+    /// Items excluded or mapped by rules (only when [`WalkerConfig::explain`] is enabled, see [`walk_explain`])
+    pub exclusions: WalkExclusions,
+}
+
+/// (Internal) Canonicalize the provided directories, drop the nested ones, and run a walker sharing the same state on each of them
+fn walk_roots<'a>(
+    roots: &[PathBuf],
+    config: &'a WalkerConfig,
+    mut f: impl FnMut(PathBuf, &mut WalkIter<'a>) -> Result<(), WalkerErr>,
+) -> Result<(), WalkerErr> {
+    let filesystem = &*config.filesystem;
+    let roots = roots.iter().map(|root| canonicalize_source(filesystem, root)).collect::<Result<Vec<_>, _>>()?;
+
+    let roots: Vec<_> = roots
+        .iter()
+        .enumerate()
+        .filter(|(index, root)| {
+            // Identical directories are only walked once, the first time they appear
+            let outer = roots
+                .iter()
+                .enumerate()
+                .find(|(other_index, other)| root.starts_with(other) && (root != other || other_index < index));
+
+            match outer {
+                Some((_, outer)) => {
+                    err!(
+                        "Source directory is located inside another one, skipping it: {} (in {})",
+                        root.display(),
+                        outer.display()
+                    );
+                    false
+                }
+                None => true,
+            }
+        })
+        .map(|(_, root)| root.clone())
+        .collect();
+
+    let state = Arc::new(Mutex::new(WalkState::default()));
+    let mut last_iter = None;
+
+    for root in roots {
+        let mut iter = WalkIter::with_state(config, root.clone(), Arc::clone(&state));
+        iter.check_empty = false;
+
+        let source = open_source(filesystem, root.clone(), &mut iter.state())?;
+        iter.open_dir(source)?;
+
+        f(root, &mut iter)?;
+        last_iter = Some(iter);
+    }
+
+    // The files lists are only empty if none of the directories contained any item
+    match last_iter {
+        Some(iter) => iter.ensure_not_empty(),
+        None => Ok(()),
+    }
+}
+
+/// Walk through a directory (recursively) lazily, yielding the files to backup one by one
+///
+/// This behaves exactly like [`walk`], but the filesystem is only traversed as the iterator is consumed.
+/// Only the stack of currently opened directories and the history of visited items are kept in memory.
+///
+/// Empty directories are yielded when they are closed (unless [`WalkerConfig::drop_empty_dirs`] is set).
+/// When [`WalkerConfig::emit_directories`] is set to [`DirEmitPolicy::All`], directories are yielded before their entries instead.
+///
+/// ## Error handling
+///
+/// Errors are yielded as they happen. The iterator can be resumed after an error, in which case the faulty item
+/// (e.g. a directory that couldn't be read) is skipped and the traversal goes on with the next one.
+/// Collecting the iterator into a `Result<Vec<_>, _>` stops at the first error, like [`walk`] does.
+pub fn walk_iter<'a>(dir: &Path, config: &'a WalkerConfig) -> WalkIter<'a> {
+    WalkIter::new(dir, config)
+}
+
+/// Walk through a directory (recursively), calling the provided function on each file to backup as soon as it is discovered
+///
+/// This behaves like [`walk`] without allocating the files list. The callback receives the item's path as well as its type,
+/// and is only called on items that were not excluded by rules.
+///
+/// Returning [`ControlFlow::Break`] from the callback stops the traversal, in which case this function returns `Ok(())`.
+///
+/// ```no_run
+/// use std::ops::ControlFlow;
+/// use std::path::Path;
+/// use rebackup::{walk_with, WalkerConfig, WalkerItemType};
+///
+/// let config = WalkerConfig::new(vec![]);
+/// let mut files = 0;
+///
+/// walk_with(Path::new("/home"), &config, |path, item_type| {
+///     if item_type == WalkerItemType::File {
+///         println!("{}", path.display());
+///         files += 1;
+///     }
+///
+///     // Stop after the first 100 files
+///     if files < 100 { ControlFlow::Continue(()) } else { ControlFlow::Break(()) }
+/// })
+/// .unwrap();
+/// ```
+pub fn walk_with<F>(dir: &Path, config: &WalkerConfig, mut f: F) -> Result<(), WalkerErr>
+where
+    F: FnMut(&Path, WalkerItemType) -> ControlFlow<()>,
+{
+    drive(&mut WalkIter::new(dir, config), |item| f(&item.path, item.item_type))
+}
+
+/// (Internal) Run the walker until it ends or the provided function stops it, handling errors as per the configuration
+fn drive(iter: &mut WalkIter, mut f: impl FnMut(WalkerItem) -> ControlFlow<()>) -> Result<(), WalkerErr> {
+    let on_error = iter.config.on_error;
+
+    if let Some(err) = iter.init_err.take() {
+        return Err(err);
+    }
+
+    while let Some(item) = iter.next_item() {
+        match item {
+            Ok(item) => {
+                if f(item).is_break() {
+                    break;
+                }
+            }
+            Err(err) if on_error == ErrorPolicy::Collect && err.is_recoverable() => {
+                err.log(LoggerLevel::Error, "");
+            }
+            Err(err) => return Err(err),
+        }
+    }
+
+    Ok(())
+}
+
+/// Lazy iterator over the files to backup (see [`walk_iter`])
+pub struct WalkIter<'a> {
+    /// Walker's configuration
+    config: &'a WalkerConfig,
+
+    /// Walker's rules alongside their index in the configuration, sorted by decreasing priority (see [`WalkerRule::priority`])
+    rules: Vec<(usize, &'a WalkerRule)>,
+
+    /// Source directory (absolute, canonicalized)
+    source: PathBuf,
+
+    /// State of the walk (may be shared with other walkers, see [`walk_parallel`])
+    state: Arc<Mutex<WalkState>>,
+
+    /// Items currently being traversed
+    stack: Vec<WalkFrame>,
+
+    /// Items ready to be yielded without any further treatment
+    ready: VecDeque<WalkerItem>,
+
+    /// Error that occurred before the traversal started
+    init_err: Option<WalkerErr>,
+
+    /// Defer the traversal of directories instead of entering them (used by [`walk_parallel`])
+    defer_dirs: bool,
+
+    /// Directories whose traversal was deferred
+    deferred: Vec<PendingDir>,
+
+    /// Directories waiting to be traversed (see [`TraversalStrategy::BreadthFirst`])
+    pending: VecDeque<PendingDir>,
+
+    /// Check if the files list is empty at the end of the walk (see [`WalkerConfig::fail_if_empty`]),
+    /// disabled for walkers which are only a part of a bigger walk
+    check_empty: bool,
+
+    /// Record the reason each item was excluded (see [`walk_explain`])
+    explain: bool,
+}
+
+impl<'a> WalkIter<'a> {
+    fn new(dir: &Path, config: &'a WalkerConfig) -> Self {
+        let mut iter = Self::with_state(config, PathBuf::new(), Arc::new(Mutex::new(WalkState::default())));
+
+        if let Err(err) = iter.init(dir) {
+            iter.init_err = Some(err);
+        }
+
+        iter
+    }
+
+    /// Create a walker that doesn't traverse anything yet
+    fn with_state(config: &'a WalkerConfig, source: PathBuf, state: Arc<Mutex<WalkState>>) -> Self {
+        // Sort the rules once (the sort is stable, so rules with the same priority keep the order they were provided in)
+        let mut rules: Vec<_> = config.rules.iter().enumerate().collect();
+        rules.sort_by_key(|(_, rule)| std::cmp::Reverse(rule.priority));
+
+        Self {
+            config,
+            rules,
+            source,
+            state,
+            stack: vec![],
+            ready: VecDeque::new(),
+            init_err: None,
+            defer_dirs: false,
+            deferred: vec![],
+            pending: VecDeque::new(),
+            check_empty: true,
+            explain: config.explain,
+        }
+    }
+
+    /// Canonicalize the source directory and open it
+    fn init(&mut self, dir: &Path) -> Result<(), WalkerErr> {
+        let dir = canonicalize_source(self.filesystem(), dir)?;
+        self.source = dir.clone();
+
+        let source = open_source(self.filesystem(), dir, &mut self.state())?;
+        self.open_dir(source)
+    }
+
+    /// Get the filesystem items are read from
+    fn filesystem(&self) -> &'a dyn FileSystem {
+        &*self.config.filesystem
+    }
+
+    /// Get the walk's state
+    fn state(&self) -> MutexGuard<'_, WalkState> {
+        self.state.lock().expect("Internal error: walk state's lock is poisoned")
+    }
+
+    /// Start traversing a directory
+    ///
+    /// Provided directory must be guaranteed to be a directory (or a symbolic link to one).
+    fn open_dir(&mut self, PendingDir { item: dir, depth, canonical }: PendingDir) -> Result<(), WalkerErr> {
+        debug!("Walking into directory: {}", dir.path.display());
+
+        self.state().counters.dirs_entered += 1;
+        self.report_progress(&dir.path);
+
+        let entries = match self.skip_if_unreadable(
+            self.filesystem()
+                .read_dir(&dir.path)
+                .map_err(|err| WalkerErr::FailedToWalkDir(dir.path.clone(), err)),
+        )? {
+            Some(entries) => entries,
+            None => return Ok(()),
+        };
+
+        let entries = if self.config.sort_entries {
+            let mut entries: Vec<_> = entries.collect();
+
+            // Errors are put first, then entries are sorted by file name
+            entries.sort_by(|a, b| match (a, b) {
+                (Ok(a), Ok(b)) => a.file_name().cmp(&b.file_name()),
+                (Ok(_), Err(_)) => std::cmp::Ordering::Greater,
+                (Err(_), Ok(_)) => std::cmp::Ordering::Less,
+                (Err(_), Err(_)) => std::cmp::Ordering::Equal,
+            });
+
+            DirEntries::Sorted(entries.into_iter())
+        } else {
+            DirEntries::Unsorted(entries)
+        };
+
+        // Directories are emitted before their entries, unless they must be dropped if empty
+        // (in which case they are emitted when their first entry is found)
+        if self.config.emit_directories == DirEmitPolicy::All && depth > 0 && !self.config.drop_empty_dirs {
+            self.ready.push_back(dir.clone());
+        }
+
+        self.stack.push(WalkFrame::Dir {
+            item: Box::new(dir),
+            entries,
+            depth,
+            canonical,
+            contains_items: false,
+        });
+
+        Ok(())
+    }
+
+    /// Run the walker on a single item
+    ///
+    /// The item's depth is relative to the source directory (direct children of the source directory have a depth of 0).
+    ///
+    /// If the item's canonical path is already known (because its parent directory's one is), it can be provided to avoid
+    /// canonicalizing the item again. It will still be canonicalized if it is a symbolic link. Canonical paths are only
+    /// used on platforms without inode numbers (see [`item_id`]).
+    fn walk_item(&mut self, item_path: PathBuf, depth: usize, canonical: Option<PathBuf>) -> Result<WalkStep, WalkerErr> {
+        let config = self.config;
+
+        // Get the item's metadata and type
+        let mut item = match self.skip_if_unreadable(read_item(self.filesystem(), item_path.clone(), false))? {
+            Some(item) => item,
+            None => return Ok(WalkStep::Skip),
+        };
+        let item_type = item.item_type;
+
+        debug!("> Treating item: {}", item_path.display());
+
+        let items_treated = {
+            let mut state = self.state();
+            state.counters.items_treated += 1;
+            state.counters.items_treated
+        };
+
+        if items_treated.is_multiple_of(PROGRESS_INTERVAL) {
+            self.report_progress(&item_path);
+        }
+
+        // If asked to, ignore special files
+        if item_type == WalkerItemType::Other && !config.include_special_files {
+            debug!(">> Detected special file, skipping based on configuration.");
+            return Ok(WalkStep::Skip);
+        }
+
+        // If asked to, ignore regular files based on their size and modification time
+        if item_type == WalkerItemType::File {
+            let size = item.metadata.len();
+
+            let too_small = matches!(config.min_file_size, Some(min) if size < min);
+            let too_large = matches!(config.max_file_size, Some(max) if size > max);
+
+            if too_small || too_large {
+                debug!(">> File size is out of the allowed range ({} bytes), skipping based on configuration.", size);
+                self.state().counters.items_excluded += 1;
+                return Ok(WalkStep::Skip);
+            }
+
+            if config.modified_after.is_some() || config.modified_before.is_some() {
+                match item.metadata.modified() {
+                    Ok(modified) => {
+                        let too_old = matches!(config.modified_after, Some(after) if modified < after);
+                        let too_recent = matches!(config.modified_before, Some(before) if modified > before);
+
+                        if too_old || too_recent {
+                            debug!(">> File modification time is out of the allowed range, skipping based on configuration.");
+                            self.state().counters.items_excluded += 1;
+                            return Ok(WalkStep::Skip);
+                        }
+                    }
+
+                    Err(err) => {
+                        debug!(">> Failed to get file's modification time, including it anyway: {}", err);
+                    }
+                }
+            }
+        }
+
+        // If asked to, ignore symbolic links
+        if item_type == WalkerItemType::Symlink {
+            if config.symlink_mode == SymlinkMode::Skip {
+                debug!(">> Detected symlink, skipping based on configuration.");
+                return Ok(WalkStep::Skip);
+            }
+
+            let sym_target = self
+                .filesystem()
+                .read_link(&item_path)
+                .map_err(|err| WalkerErr::FailedToReadSymlinkTarget(item_path.clone(), err));
+
+            match self.skip_if_unreadable(sym_target)? {
+                Some(sym_target) => item.symlink_target = Some(sym_target),
+                None => return Ok(WalkStep::Skip),
+            }
+
+            debug!(">> Detected symlink, handling it based on configuration ({:?}).", config.symlink_mode);
+        }
+
+        // Symbolic links listed as entries are not followed, so their target doesn't matter
+        let followed_symlink = item_type == WalkerItemType::Symlink && config.symlink_mode == SymlinkMode::Follow;
+        let listed_symlink = item_type == WalkerItemType::Symlink && !followed_symlink;
+
+        // Ensure items are not treated twice (e.g. through symbolic links, which could also lead to cycles)
+        let (id, canonical) = match item_id(self.filesystem(), &item, canonical) {
+            _ if listed_symlink => (None, None),
+            Err(_) if followed_symlink && is_symlink_loop(self.filesystem(), &item_path) => {
+                err!("Symbolic link loop detected, skipping it: {}", item_path.display());
+                return Ok(WalkStep::Skip);
+            }
+            result => result?,
+        };
+
+        // If asked to, check if the symbolic link points outside of the source directory
+        if followed_symlink && config.symlink_escape != SymlinkEscapePolicy::Allow {
+            let target = self
+                .filesystem()
+                .canonicalize(&item_path)
+                .map_err(|err| WalkerErr::FailedToCanonicalize(item_path.clone(), err))?;
+
+            if !target.starts_with(&self.source) {
+                if config.symlink_escape == SymlinkEscapePolicy::Error {
+                    return Err(WalkerErr::SymlinkEscapesSource(item_path, target));
+                }
+
+                err!(
+                    "Symbolic link points outside of the source directory, skipping it: {} => {}",
+                    item_path.display(),
+                    target.display()
+                );
+                return Ok(WalkStep::Skip);
+            }
+        }
+
+        if let Some(id) = &id {
+            let mut state = self.state();
+
+            if let Some(original) = state.history.get(id) {
+                let duplicate = DuplicateItem {
+                    original: original.clone(),
+                    duplicate: item_path,
+                    cause: if followed_symlink {
+                        DuplicateCause::SymlinkTargetVisited
+                    } else {
+                        DuplicateCause::AlreadyVisited
+                    },
+                };
+
+                match &duplicate.original {
+                    Some(original) => {
+                        err!(
+                            "Item was already walked on, skippping it: {} (already walked on at: {})",
+                            duplicate.duplicate.display(),
+                            original.display()
+                        );
+                    }
+                    None => {
+                        err!("Item was already walked on, skippping it: {}", duplicate.duplicate.display());
+                    }
+                }
+
+                state.duplicates.push(duplicate);
+                return Ok(WalkStep::Skip);
+            }
+
+            // Only the paths of directories and symbolic links are kept, as other items are a lot more numerous
+            let path = if item_type == WalkerItemType::Directory || followed_symlink {
+                Some(item_path.clone())
+            } else {
+                None
+            };
+
+            state.history.insert(id.to_owned(), path);
+        }
+
+        // Context provided to the rules
+        let ctx = RuleCtx {
+            path: &item_path,
+            metadata: &item.metadata,
+            item_type,
+            depth,
+            parent: item_path.parent().unwrap_or(&self.source),
+            config,
+            source: &self.source,
+        };
+
+        // New path of the item, if a rule renamed it
+        let mut rename = None;
+
+        // Run all rules
+        for &(index, rule) in &self.rules {
+            if !rule.only_for.contains(item_type) {
+                continue;
+            }
+
+            if rule.min_depth.is_some_and(|min| depth < min) || rule.max_depth.is_some_and(|max| depth > max) {
+                continue;
+            }
+
+            // If matching, run the rule and check if it indicates to skip the current item
+            let started = if config.time_rules { Some(Instant::now()) } else { None };
+
+            let rule_do = match self.match_rule(index, rule, &ctx) {
+                Ok(true) => run_walker_rule(&ctx, rule).map(Some),
+                Ok(false) => Ok(None),
+                Err(err) => Err(err),
+            };
+
+            self.record_rule_stats(index, started, &rule_do);
+
+            if let Some(rule_do) = rule_do? {
+                match rule_do {
+                    WalkerRuleDo::Nothing => {}
+                    WalkerRuleDo::SkipFollowingRules => break,
+                    WalkerRuleDo::SkipItem => {
+                        self.state().counters.items_excluded += 1;
+                        self.explain_exclusion(rule, &item_path, || WalkerRuleResult::ExcludeItem);
+                        return Ok(WalkStep::Skip);
+                    }
+                    WalkerRuleDo::IncludeWithoutDescendants => {
+                        debug!(">>> Rule indicated to include the directory without its descendants");
+
+                        // The target of a pruned symbolic link was not traversed, so it must still be reachable through other paths
+                        if let (true, Some(id)) = (followed_symlink, &id) {
+                            self.state().history.remove(id);
+                        }
+
+                        self.register_rename(&item_path, rename)?;
+                        return Ok(WalkStep::Emit(item));
+                    }
+                    WalkerRuleDo::Rename(renamed_path) => {
+                        if rename.is_some() {
+                            return Err(rename_conflict(rule, &item_path, renamed_path));
+                        }
+
+                        debug!(">>> Rule renamed item to: {}", renamed_path.display());
+                        rename = Some((rule, renamed_path));
+                    }
+                    WalkerRuleDo::MapItem(mapped_items, absolute, from_source) => {
+                        debug!(">>> Rule mapped to items (items = {}, absolute = {})", mapped_items.len(), absolute);
+
+                        self.register_rename(&item_path, rename)?;
+                        self.explain_exclusion(rule, &item_path, || match from_source {
+                            true => WalkerRuleResult::MapAsListFromSource(mapped_items.clone(), absolute),
+                            false => WalkerRuleResult::MapAsList(mapped_items.clone(), absolute),
+                        });
+
+                        return Ok(if absolute {
+                            WalkStep::EmitAll(
+                                mapped_items
+                                    .into_iter()
+                                    .map(|mapped_item_path| match config.mapping_validation {
+                                        MappingValidation::KeepUnchecked if self.filesystem().symlink_metadata(&mapped_item_path).is_err() => {
+                                            self.unchecked_item(&item_path, mapped_item_path)
+                                        }
+                                        _ => read_item(self.filesystem(), mapped_item_path, true),
+                                    })
+                                    .collect::<Result<_, _>>()?,
+                            )
+                        } else if from_source {
+                            WalkStep::Map(self.source.clone(), 0, mapped_items)
+                        } else {
+                            WalkStep::Map(item_path, depth + 1, mapped_items)
+                        });
+                    }
+                }
+            }
+        }
+
+        self.register_rename(&item_path, rename)?;
+
+        // Handle the item type (only symbolic links require an additional check, as they may point to directories)
+        let is_dir = match item_type {
+            WalkerItemType::Directory => true,
+            WalkerItemType::Symlink => followed_symlink && target_is_dir(self.filesystem(), &item_path)?,
+            WalkerItemType::File | WalkerItemType::Other => false,
+        };
+
+        if is_dir {
+            match config.max_depth {
+                Some(max_depth) if depth >= max_depth => {
+                    debug!(">> Reached maximum depth, not traversing directory.");
+                    Ok(WalkStep::Emit(item))
+                }
+                _ => Ok(WalkStep::Descend(PendingDir {
+                    item,
+                    depth: depth + 1,
+                    canonical,
+                })),
+            }
+        } else {
+            if config.dedup_hard_links && item_type == WalkerItemType::File {
+                if let Some(id) = hard_link_id(&item.metadata) {
+                    let mut state = self.state();
+
+                    if let Some(first) = state.hard_link_ids.get(&id).cloned() {
+                        debug!(">> Item is a hard link to an already found file, skipping it: {}", first.display());
+                        state.hard_links.entry(first).or_default().push(item_path);
+                        return Ok(WalkStep::Skip);
+                    }
+
+                    state.hard_link_ids.insert(id, item_path);
+                }
+            }
+
+            Ok(WalkStep::Emit(item))
+        }
+    }
+
+    /// Update a rule's counters after it was run on an item
+    fn record_rule_stats(&self, index: usize, started: Option<Instant>, rule_do: &Result<Option<WalkerRuleDo>, WalkerErr>) {
+        let mut state = self.state();
+
+        if state.rule_stats.len() <= index {
+            state.rule_stats.resize(self.config.rules.len().max(index + 1), RuleCounters::default());
+        }
+
+        let counters = &mut state.rule_stats[index];
+        counters.evaluated += 1;
+
+        if let Some(started) = started {
+            counters.duration += started.elapsed();
+        }
+
+        if let Ok(Some(rule_do)) = rule_do {
+            counters.matched += 1;
+
+            match rule_do {
+                WalkerRuleDo::SkipItem => counters.excluded += 1,
+                WalkerRuleDo::MapItem(_, _, _) => counters.mapped += 1,
+                _ => {}
+            }
+        }
+    }
+
+    /// Record why an item was excluded or mapped, if asked to
+    fn explain_exclusion(&self, rule: &WalkerRule, item_path: &Path, result: impl FnOnce() -> WalkerRuleResult) {
+        if self.explain {
+            let reason = ExclusionReason {
+                rule_name: rule.name,
+                rule_description: rule.description.clone(),
+                result: result(),
+            };
+
+            self.state().exclusions.push((item_path.to_path_buf(), reason));
+        }
+    }
+
+    /// (Internal) Check if a rule should be run on an item, reusing the result of its predicate for the item's parent
+    /// directory if possible (see [`WalkerRule::cacheable`])
+    fn match_rule(&self, index: usize, rule: &WalkerRule, ctx: &RuleCtx) -> Result<bool, WalkerErr> {
+        let parent = match ctx.path.parent() {
+            Some(parent) if rule.cacheable => parent,
+            _ => return match_walker_rule(ctx, rule),
+        };
+
+        if let Some(Some(matches)) = self.state().rule_cache.get(parent).and_then(|cache| cache.get(index)) {
+            return Ok(*matches);
+        }
+
+        // The state is not locked while the predicate runs, as it may be slow
+        let matches = match_walker_rule(ctx, rule)?;
+
+        let mut state = self.state();
+        let cache = state.rule_cache.entry(parent.to_path_buf()).or_default();
+
+        if cache.len() <= index {
+            cache.resize(self.config.rules.len(), None);
+        }
+
+        cache[index] = Some(matches);
+
+        Ok(matches)
+    }
+
+    /// Take the exclusions recorded so far (see [`WalkerConfig::explain`])
+    pub fn take_exclusions(&mut self) -> WalkExclusions {
+        std::mem::take(&mut self.state().exclusions)
+    }
+
+    /// Get the statistics collected so far
+    pub fn stats(&self) -> WalkStats {
+        let state = self.state();
+        WalkStats::new(&self.config.rules, &state, self.config.time_rules)
+    }
+
+    /// (Internal) List a mapped item that doesn't exist (see [`MappingValidation::KeepUnchecked`])
+    ///
+    /// As its metadata cannot be read, the item is given the type and metadata of the directory it was mapped relatively to.
+    fn unchecked_item(&self, base: &Path, item_path: PathBuf) -> Result<WalkerItem, WalkerErr> {
+        debug!("> Listing missing mapped item as is: {}", item_path.display());
+
+        let mut item = read_item(self.filesystem(), base.to_path_buf(), false)?;
+        item.path = item_path;
+
+        Ok(item)
+    }
+
+    /// (Internal) Turn permission errors into skipped items if asked to (see [`WalkerConfig::skip_unreadable`])
+    fn skip_if_unreadable<T>(&self, result: Result<T, WalkerErr>) -> Result<Option<T>, WalkerErr> {
+        match result {
+            Ok(value) => Ok(Some(value)),
+            Err(err) if self.config.skip_unreadable && err.is_permission_denied() => {
+                err.log(LoggerLevel::Error, "Skipping unreadable item: ");
+                self.state().counters.skipped_unreadable += 1;
+                Ok(None)
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Register an item's renaming (see [`WalkerRuleResult::RenameItem`])
+    ///
+    /// Relative paths are relative to the item's parent directory (as reported, i.e. after its own eventual renaming).
+    fn register_rename(&self, item_path: &Path, rename: Option<(&WalkerRule, PathBuf)>) -> Result<(), WalkerErr> {
+        let (rule, renamed_path) = match rename {
+            Some(rename) => rename,
+            None => return Ok(()),
+        };
+
+        let mut state = self.state();
+
+        let renamed_path = match item_path.parent() {
+            Some(parent) if renamed_path.is_relative() => state.reported_path(parent).join(renamed_path),
+            _ => renamed_path,
+        };
+
+        if state.renames.contains_key(item_path) || state.renames.values().any(|path| *path == renamed_path) {
+            return Err(rename_conflict(rule, item_path, renamed_path));
+        }
+
+        state.renames.insert(item_path.to_path_buf(), renamed_path);
+
+        Ok(())
+    }
+
+    /// Get the hard links that were skipped so far, indexed by the path of the first hard link found for the same file
+    /// (see [`WalkerConfig::dedup_hard_links`])
+    pub fn hard_links(&self) -> HashMap<PathBuf, Vec<PathBuf>> {
+        self.state().hard_links.clone()
+    }
+
+    /// Call the progress callback, if any
+    fn report_progress(&self, current_path: &Path) {
+        if let Some(progress) = &self.config.progress {
+            let counters = self.state().counters;
+
+            progress(&WalkProgress {
+                dirs_entered: counters.dirs_entered,
+                files_included: counters.files_included,
+                items_excluded: counters.items_excluded,
+                current_path,
+            });
+        }
+    }
+
+    /// Get the next item to backup
+    fn next_item(&mut self) -> Option<Result<WalkerItem, WalkerErr>> {
+        let mut item = self.find_next_item();
+
+        // Skip the items whose type must not be added to the files list
+        while matches!(&item, Some(Ok(found)) if !self.config.output_types.contains(found.item_type)) {
+            item = self.find_next_item();
+        }
+
+        // Fail at the end of the walk if no item was found (only once, in case the iterator is resumed)
+        if item.is_none() && self.check_empty {
+            self.check_empty = false;
+
+            if let Err(err) = self.ensure_not_empty() {
+                return Some(Err(err));
+            }
+        }
+
+        if let Some(Ok(found)) = &mut item {
+            let mut state = self.state();
+            state.counters.files_included += 1;
+
+            if found.path != self.source {
+                state.counters.items_found += 1;
+            }
+
+            // Stop the walk if there are too many items
+            if let Some(limit) = self.config.max_items {
+                if state.counters.files_included > limit as u64 {
+                    debug!("Maximum number of items was exceeded, stopping.");
+
+                    let at_path = found.path.clone();
+                    drop(state);
+
+                    self.stack.clear();
+                    self.ready.clear();
+                    self.pending.clear();
+
+                    return Some(Err(WalkerErr::TooManyItems { limit, at_path }));
+                }
+            }
+
+            // Report the item under its new path if it (or one of its ancestors) was renamed
+            if !state.renames.is_empty() {
+                let reported_path = state.reported_path(&found.path);
+
+                if reported_path != found.path {
+                    found.renamed_from = Some(std::mem::replace(&mut found.path, reported_path));
+                }
+            }
+
+            if self.config.output_paths == PathStyle::RelativeToSource {
+                match found.path.strip_prefix(&self.source) {
+                    Ok(relative) => found.path = relative.to_path_buf(),
+                    Err(_) => return Some(Err(WalkerErr::ItemOutsideSource(found.path.clone()))),
+                }
+
+                if let Some(renamed_from) = &mut found.renamed_from {
+                    if let Ok(relative) = renamed_from.strip_prefix(&self.source) {
+                        *renamed_from = relative.to_path_buf();
+                    }
+                }
+            }
+
+            state.items_counters.record(found);
+        }
+
+        item
+    }
+
+    /// (Internal) Fail if no item was found during the walk while asked to (see [`WalkerConfig::fail_if_empty`])
+    fn ensure_not_empty(&self) -> Result<(), WalkerErr> {
+        let counters = self.state().counters;
+
+        if self.config.fail_if_empty && counters.items_found == 0 {
+            return Err(WalkerErr::EmptyResult {
+                excluded: counters.items_excluded,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// (Internal) Mark the currently opened directories as containing items (see [`WalkerConfig::treat_filtered_as_empty`])
+    ///
+    /// Directories which must be emitted before their entries but only if they aren't empty are queued at this point.
+    fn mark_contents(&mut self) {
+        if !self.config.treat_filtered_as_empty {
+            return;
+        }
+
+        let emit_dirs = self.config.emit_directories == DirEmitPolicy::All && self.config.drop_empty_dirs;
+        let mut newly_marked = vec![];
+
+        for frame in self.stack.iter_mut().rev() {
+            if let WalkFrame::Dir {
+                item, depth, contains_items, ..
+            } = frame
+            {
+                // Parent directories of a marked directory are already marked as well
+                if *contains_items {
+                    break;
+                }
+
+                *contains_items = true;
+
+                if emit_dirs && *depth > 0 {
+                    newly_marked.push((**item).clone());
+                }
+            }
+        }
+
+        self.ready.extend(newly_marked.into_iter().rev());
+    }
+
+    /// (Internal) Traverse the filesystem until the next item to backup is found
+    fn find_next_item(&mut self) -> Option<Result<WalkerItem, WalkerErr>> {
+        if let Some(err) = self.init_err.take() {
+            return Some(Err(err));
+        }
+
+        loop {
+            if self.stack.is_empty() && self.ready.is_empty() && self.pending.is_empty() {
+                return None;
+            }
+
+            // Stop the walk if it was cancelled (this is checked before treating each item)
+            if let Some(cancel) = &self.config.cancel {
+                if cancel.load(Ordering::SeqCst) {
+                    debug!("Walk was cancelled, stopping.");
+                    self.stack.clear();
+                    self.ready.clear();
+                    self.pending.clear();
+                    return Some(Err(WalkerErr::Cancelled));
+                }
+            }
+
+            if let Some(item) = self.ready.pop_front() {
+                return Some(Ok(item));
+            }
+
+            // Enter the next pending directory once the current one was fully traversed
+            if self.stack.is_empty() {
+                if let Some(dir) = self.pending.pop_front() {
+                    if let Err(err) = self.open_dir(dir) {
+                        return Some(Err(err));
+                    }
+                }
+
+                continue;
+            }
+
+            // Get the next item to treat
+            let (item_path, depth, canonical, unchecked_base) = match self.stack.last_mut()? {
+                WalkFrame::Dir {
+                    item,
+                    entries,
+                    depth,
+                    canonical,
+                    contains_items,
+                } => match entries.next() {
+                    Some(Ok(entry_path)) => {
+                        // Directories' contents are marked when items are emitted if filtered entries don't count
+                        if !self.config.treat_filtered_as_empty {
+                            if !*contains_items && self.config.emit_directories == DirEmitPolicy::All && *depth > 0 && self.config.drop_empty_dirs {
+                                self.ready.push_back((**item).clone());
+                            }
+
+                            *contains_items = true;
+                        }
+
+                        let entry_canonical = canonical.as_ref().zip(entry_path.file_name()).map(|(canonical, name)| canonical.join(name));
+                        (entry_path, *depth, entry_canonical, None)
+                    }
+
+                    Some(Err(err)) => return Some(Err(WalkerErr::FailedToReadDirEntry(item.path.clone(), err))),
+
+                    None => {
+                        if let Some(WalkFrame::Dir { item, contains_items, .. }) = self.stack.pop() {
+                            // Cached results of the rules' predicates won't be used anymore for this directory
+                            let mut state = self.state();
+
+                            if !state.rule_cache.is_empty() {
+                                state.rule_cache.remove(&item.path);
+                            }
+
+                            drop(state);
+
+                            if !contains_items && !self.config.drop_empty_dirs && self.config.emit_directories == DirEmitPolicy::OnlyEmpty {
+                                self.mark_contents();
+                                return Some(Ok(*item));
+                            }
+                        }
+
+                        continue;
+                    }
+                },
+
+                WalkFrame::Mapped { base, depth, items } => match items.next() {
+                    Some(item_path) => {
+                        let depth = match item_path.strip_prefix(&*base) {
+                            Ok(relative) => *depth + relative.components().count().saturating_sub(1),
+                            Err(_) => *depth,
+                        };
+
+                        // Items mapped without validation may not exist, in which case they are listed as they are
+                        let unchecked_base = match self.config.mapping_validation {
+                            MappingValidation::KeepUnchecked if self.config.filesystem.symlink_metadata(&item_path).is_err() => Some(base.clone()),
+                            _ => None,
+                        };
+
+                        (item_path, depth, None, unchecked_base)
+                    }
+                    None => {
+                        self.stack.pop();
+                        continue;
+                    }
+                },
+            };
 
-    if !dir.is_dir() {
+            let step = match unchecked_base {
+                Some(base) => self.unchecked_item(&base, item_path).map(WalkStep::Emit),
+                None => self.walk_item(item_path, depth, canonical),
+            };
+
+            match step {
+                Ok(WalkStep::Skip) => {}
+                Ok(WalkStep::Emit(item)) => {
+                    self.mark_contents();
+
+                    // Items are queued behind the eventual directories emitted just before them
+                    if self.ready.is_empty() {
+                        return Some(Ok(item));
+                    }
+
+                    self.ready.push_back(item);
+                }
+                Ok(WalkStep::EmitAll(items)) => {
+                    if !items.is_empty() {
+                        self.mark_contents();
+                    }
+
+                    self.ready.extend(items);
+                }
+                Ok(WalkStep::Map(base, depth, items)) => self.stack.push(WalkFrame::Mapped {
+                    base,
+                    depth,
+                    items: items.into_iter(),
+                }),
+                Ok(WalkStep::Descend(dir)) => {
+                    if self.defer_dirs {
+                        // Deferred directories are traversed by another walker, so their contents are unknown here
+                        self.mark_contents();
+                        self.deferred.push(dir);
+                    } else if self.config.strategy == TraversalStrategy::BreadthFirst {
+                        // Pending directories are traversed after the current one is closed, so their contents are unknown here
+                        self.mark_contents();
+                        self.pending.push_back(dir);
+                    } else if let Err(err) = self.open_dir(dir) {
+                        return Some(Err(err));
+                    }
+                }
+                Err(err) => return Some(Err(err)),
+            }
+        }
+    }
+}
+
+impl<'a> Iterator for WalkIter<'a> {
+    type Item = Result<PathBuf, WalkerErr>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_item().map(|item| item.map(|item| item.path))
+    }
+}
+
+/// (Internal) Canonicalize the source directory and ensure it is a directory
+fn canonicalize_source(filesystem: &dyn FileSystem, dir: &Path) -> Result<PathBuf, WalkerErr> {
+    let dir = filesystem
+        .canonicalize(dir)
+        .map_err(|err| WalkerErr::FailedToCanonicalize(dir.to_path_buf(), err))?;
+
+    if !filesystem.metadata(&dir).is_ok_and(|metadata| metadata.is_dir()) {
         err!("Input directory not found: {}", dir.display());
         return Err(WalkerErr::DirNotFound);
     }
 
-    let mut history = HashSet::new();
-    history.insert(dir.clone());
+    Ok(dir)
+}
+
+/// (Internal) Read the source directory and register it as visited
+fn open_source(filesystem: &dyn FileSystem, dir: PathBuf, state: &mut WalkState) -> Result<PendingDir, WalkerErr> {
+    let item = read_item(filesystem, dir.clone(), false)?;
+    let (id, canonical) = item_id(filesystem, &item, Some(dir))?;
+
+    if let Some(id) = id {
+        state.history.insert(id, Some(item.path.clone()));
+    }
+
+    Ok(PendingDir { item, depth: 0, canonical })
+}
+
+/// (Internal) Check if an item is a directory, following symbolic links
+fn target_is_dir(filesystem: &dyn FileSystem, path: &Path) -> Result<bool, WalkerErr> {
+    filesystem
+        .metadata(path)
+        .map(|metadata| metadata.is_dir())
+        .map_err(|err| WalkerErr::FailedToGetItemMetadata(path.to_path_buf(), err))
+}
+
+/// (Internal) Check if a symbolic link is part of a loop of symbolic links (e.g. `a -> b -> a`)
+fn is_symlink_loop(filesystem: &dyn FileSystem, path: &Path) -> bool {
+    let mut visited = HashSet::new();
+    let mut current = path.to_path_buf();
+
+    while let Ok(target) = filesystem.read_link(&current) {
+        let target = match current.parent() {
+            Some(parent) => parent.join(target),
+            None => target,
+        };
+
+        if !visited.insert(current) {
+            return true;
+        }
+
+        current = target;
+    }
 
-    walk_nested(&dir, config, &dir, &mut history)
+    false
 }
 
-/// (Internal) Walk through a directory (recursively) to build a list of files to backup
+/// (Internal) Identity of an item, used to detect items that were already visited
+/// (device and inode numbers on Unix platforms, canonical path on other ones)
+#[cfg(unix)]
+type ItemId = (u64, u64);
+
+/// (Internal) Identity of an item, used to detect items that were already visited
+/// (device and inode numbers on Unix platforms, canonical path on other ones)
+#[cfg(not(unix))]
+type ItemId = PathBuf;
+
+/// (Internal) Get an item's identity, alongside its canonical path if it is required to get the identity of its children
 ///
-/// Provided directory path must be canonicalized and guaranteed to be a directory.
-fn walk_nested(dir: &Path, config: &WalkerConfig, canonicalized_source: &Path, history: &mut HashSet<PathBuf>) -> Result<Vec<PathBuf>, WalkerErr> {
-    debug!("Walking into directory: {}", dir.display());
+/// For symbolic links, this is the identity of their target. Regular files with multiple hard links are not given
+/// an identity, as each of their paths must be listed (unless [`WalkerConfig::dedup_hard_links`] is enabled).
+#[cfg(unix)]
+fn item_id(filesystem: &dyn FileSystem, item: &WalkerItem, _: Option<PathBuf>) -> Result<(Option<ItemId>, Option<PathBuf>), WalkerErr> {
+    use std::os::unix::fs::MetadataExt;
 
-    let mut items = vec![];
-    let mut contains_items = false;
+    let target_metadata;
 
-    // Iterate through all items inside the provided directory
-    for item in fs::read_dir(dir).map_err(WalkerErr::FailedToWalkDir)? {
-        let item = item.map_err(WalkerErr::FailedToReadDirEntry)?;
-        walk_item(item.path(), config, canonicalized_source, history, &mut items)?;
+    let metadata = if item.item_type == WalkerItemType::Symlink {
+        target_metadata = filesystem
+            .metadata(&item.path)
+            .map_err(|err| WalkerErr::FailedToGetItemMetadata(item.path.clone(), err))?;
+        &target_metadata
+    } else {
+        &item.metadata
+    };
 
-        contains_items = true;
+    if item.item_type != WalkerItemType::Symlink && !metadata.is_dir() && metadata.nlink() > 1 {
+        return Ok((None, None));
     }
 
-    if !contains_items && !config.drop_empty_dirs {
-        items.push(dir.to_path_buf());
+    Ok((Some((metadata.dev(), metadata.ino())), None))
+}
+
+/// (Internal) Get an item's identity, alongside its canonical path if it is required to get the identity of its children
+///
+/// Items are canonicalized if their canonical path isn't already known, or if they are symbolic links.
+#[cfg(not(unix))]
+fn item_id(filesystem: &dyn FileSystem, item: &WalkerItem, canonical: Option<PathBuf>) -> Result<(Option<ItemId>, Option<PathBuf>), WalkerErr> {
+    let canonical = match canonical {
+        Some(canonical) if item.item_type != WalkerItemType::Symlink => canonical,
+        _ => filesystem
+            .canonicalize(&item.path)
+            .map_err(|err| WalkerErr::FailedToCanonicalize(item.path.clone(), err))?,
+    };
+
+    Ok((Some(canonical.clone()), Some(canonical)))
+}
+
+/// (Internal) Get the device and inode numbers of a file with multiple hard links
+#[cfg(unix)]
+fn hard_link_id(metadata: &fs::Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+
+    if metadata.nlink() > 1 {
+        Some((metadata.dev(), metadata.ino()))
+    } else {
+        None
     }
+}
 
-    Ok(items)
+/// (Internal) Get the device and inode numbers of a file with multiple hard links
+#[cfg(not(unix))]
+fn hard_link_id(_: &fs::Metadata) -> Option<(u64, u64)> {
+    None
 }
 
-/// (Internal) Run the walker on a single item
-fn walk_item(
-    item_path: PathBuf,
-    config: &WalkerConfig,
-    canonicalized_source: &Path,
-    history: &mut HashSet<PathBuf>,
-    items: &mut Vec<PathBuf>,
-) -> Result<(), WalkerErr> {
-    // Get the item's metadata
-    let item_metadata = item_path
-        .symlink_metadata()
+/// (Internal) Get an item's metadata and type (without following symbolic links)
+///
+/// The target of symbolic links is only read if asked to.
+fn read_item(filesystem: &dyn FileSystem, item_path: PathBuf, read_symlink_target: bool) -> Result<WalkerItem, WalkerErr> {
+    let metadata = filesystem
+        .symlink_metadata(&item_path)
         .map_err(|err| WalkerErr::FailedToGetItemMetadata(item_path.clone(), err))?;
 
-    // Determine the item's type
-    let item_type = item_metadata.file_type();
-    let item_type = if item_type.is_symlink() {
-        WalkerItemType::Symlink
-    } else if item_type.is_file() {
-        WalkerItemType::File
-    } else if item_type.is_dir() {
-        WalkerItemType::Directory
+    let item_type = WalkerItemType::from_file_type(metadata.file_type());
+
+    let symlink_target = if read_symlink_target && item_type == WalkerItemType::Symlink {
+        Some(
+            filesystem
+                .read_link(&item_path)
+                .map_err(|err| WalkerErr::FailedToReadSymlinkTarget(item_path.clone(), err))?,
+        )
     } else {
-        unreachable!("Internal error: unknown file type at path: {}", item_path.display());
+        None
     };
 
-    debug!("> Treating item: {}", item_path.display());
+    Ok(WalkerItem {
+        path: item_path,
+        item_type,
+        metadata,
+        symlink_target,
+        hard_links: vec![],
+        renamed_from: None,
+    })
+}
 
-    // Ensure items are not treated twice
-    if !history.insert(item_path.clone()) {
-        err!("Item was already walked on, skippping it: {}", item_path.display());
-        return Ok(());
-    }
+/// Progress of a walk, provided to the [progress callback](WalkerConfig::progress)
+#[derive(Debug, Clone, Copy)]
+pub struct WalkProgress<'a> {
+    /// Number of directories entered so far
+    pub dirs_entered: u64,
 
-    // If asked to, ignore symbolic links
-    if item_type == WalkerItemType::Symlink {
-        if !config.follow_symlinks {
-            debug!(">> Detected symlink, skipping based on configuration.");
-            return Ok(());
-        }
+    /// Number of items added to the files list so far
+    pub files_included: u64,
 
-        let sym_target = fs::read_link(&item_path).map_err(|err| WalkerErr::FailedToReadSymlinkTarget(item_path.clone(), err))?;
+    /// Number of items excluded by rules so far
+    pub items_excluded: u64,
 
-        if history.contains(&sym_target) {
-            err!("Symlink target was already walked on, skipping it: {}", item_path.display());
-            return Ok(());
-        }
+    /// Path of the item currently being treated
+    pub current_path: &'a Path,
+}
 
-        debug!(">> Detected symlink, following it based on configuration.");
-    }
+/// (Internal) Number of treated items between two calls to the progress callback
+/// (the callback is also called each time a directory is entered)
+const PROGRESS_INTERVAL: u64 = 1000;
 
-    // Canonicalize the path
-    let canonicalized = fs::canonicalize(&item_path).map_err(|err| WalkerErr::FailedToCanonicalize(item_path.clone(), err))?;
+/// (Internal) State of a walk, shared between all the walkers of a single walk
+#[derive(Default)]
+struct WalkState {
+    /// Already visited items, with the path they were first found at for directories and followed symbolic links
+    history: HashMap<ItemId, Option<PathBuf>>,
 
-    if item_path != canonicalized && !history.insert(canonicalized.clone()) {
-        err!(
-            "Symbolic link was already walked on, skippping it: {} => {}",
-            item_path.display(),
-            canonicalized.display()
-        );
-        return Ok(());
-    }
+    /// Items skipped because they were already visited (see [`WalkStats::duplicates`])
+    duplicates: Vec<DuplicateItem>,
 
-    // Run all rules
-    for rule in &config.rules {
-        let applies_to_type = match rule.only_for {
-            None => true,
-            Some(only_type) => item_type == only_type,
-        };
+    /// Counters used for progress reporting
+    counters: WalkCounters,
 
-        // If applicable and matching, run the rule and check if it indicates to skip the current item
-        if applies_to_type && (rule.matches)(&item_path, config, canonicalized_source) {
-            match run_walker_rule(&item_path, item_type, config, canonicalized_source, rule)? {
-                WalkerRuleDo::Nothing => {}
-                WalkerRuleDo::SkipFollowingRules => break,
-                WalkerRuleDo::SkipItem => return Ok(()),
-                WalkerRuleDo::MapItem(mut mapped_items, absolute) => {
-                    debug!(">>> Rule mapped to items (items = {}, absolute = {})", mapped_items.len(), absolute);
-
-                    if absolute {
-                        items.append(&mut mapped_items);
-                    } else {
-                        for item in mapped_items {
-                            walk_item(item, config, canonicalized_source, history, items)?;
-                        }
-                    }
+    /// Regular files with multiple hard links that were already found (device and inode => path)
+    hard_link_ids: HashMap<(u64, u64), PathBuf>,
+
+    /// Skipped hard links, indexed by the path of the first hard link found for the same file
+    hard_links: HashMap<PathBuf, Vec<PathBuf>>,
+
+    /// Renamed items (real path => reported path, see [`WalkerRuleResult::RenameItem`])
+    renames: HashMap<PathBuf, PathBuf>,
+
+    /// Counters of each rule, indexed like in the configuration
+    rule_stats: Vec<RuleCounters>,
+
+    /// Counters of the items in the files list (see [`WalkStats`])
+    items_counters: ItemsCounters,
+
+    /// Cached results of the predicates of cacheable rules for each directory, indexed like in the configuration
+    /// (see [`WalkerRule::cacheable`])
+    rule_cache: HashMap<PathBuf, Vec<Option<bool>>>,
+
+    /// Items excluded or mapped by rules, with the reason why (see [`walk_explain`])
+    exclusions: WalkExclusions,
+}
 
-                    return Ok(());
+impl WalkState {
+    /// Get the path an item is reported with, taking into account the renaming of the item itself or of its ancestors
+    fn reported_path(&self, path: &Path) -> PathBuf {
+        if !self.renames.is_empty() {
+            for ancestor in path.ancestors() {
+                if let Some(renamed) = self.renames.get(ancestor) {
+                    return match path.strip_prefix(ancestor) {
+                        Ok(relative) if !relative.as_os_str().is_empty() => renamed.join(relative),
+                        _ => renamed.clone(),
+                    };
                 }
             }
         }
+
+        path.to_path_buf()
     }
+}
 
-    // Handle the item type
-    if item_path.is_dir() {
-        items.append(&mut walk_nested(&item_path, config, canonicalized_source, history)?);
-    } else {
-        items.push(item_path);
+/// (Internal) Counters used to report the walk's progress
+#[derive(Default, Clone, Copy)]
+struct WalkCounters {
+    dirs_entered: u64,
+    files_included: u64,
+    items_excluded: u64,
+    items_treated: u64,
+    items_found: u64,
+    skipped_unreadable: u64,
+}
+
+/// (Internal) Item being traversed by the [walker](WalkIter)
+enum WalkFrame {
+    /// Directory whose entries are being traversed, with the depth of its entries and its canonical path
+    /// (if required, see [`item_id`]), also indicating if at least one entry was found
+    Dir {
+        item: Box<WalkerItem>,
+        entries: DirEntries,
+        depth: usize,
+        canonical: Option<PathBuf>,
+        contains_items: bool,
+    },
+
+    /// List of items a rule mapped an item to, with the base path of the items and the depth of its direct children
+    /// (see [`WalkerRuleResult::MapAsListFromSource`])
+    Mapped {
+        base: PathBuf,
+        depth: usize,
+        items: std::vec::IntoIter<PathBuf>,
+    },
+}
+
+/// (Internal) Entries of a directory being traversed
+enum DirEntries {
+    /// Entries in the order provided by the filesystem (see [`FileSystem::read_dir`])
+    Unsorted(DirEntriesIter),
+
+    /// Entries sorted by file name (see [`WalkerConfig::sort_entries`])
+    Sorted(std::vec::IntoIter<std::io::Result<PathBuf>>),
+}
+
+impl Iterator for DirEntries {
+    type Item = std::io::Result<PathBuf>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            Self::Unsorted(entries) => entries.next(),
+            Self::Sorted(entries) => entries.next(),
+        }
     }
+}
 
-    Ok(())
+/// (Internal) What to do with an item after it was treated by the [walker](WalkIter)
+enum WalkStep {
+    /// Skip the item
+    Skip,
+
+    /// Add the item to the files list
+    Emit(WalkerItem),
+
+    /// Add all the provided items to the files list without treating them
+    EmitAll(Vec<WalkerItem>),
+
+    /// Treat the provided items instead of the item itself (with the items' base path, and the depth of its direct children)
+    Map(PathBuf, usize, Vec<PathBuf>),
+
+    /// Traverse the item (which is a directory)
+    Descend(PendingDir),
+}
+
+/// (Internal) Directory to traverse
+struct PendingDir {
+    /// The directory itself
+    item: WalkerItem,
+
+    /// Depth of the directory's entries
+    depth: usize,
+
+    /// Canonical path of the directory (if required, see [`item_id`])
+    canonical: Option<PathBuf>,
+}
+
+/// (Internal) Check if a walker rule should be run on an item
+fn match_walker_rule(ctx: &RuleCtx, rule: &WalkerRule) -> Result<bool, WalkerErr> {
+    run_rule_callback(ctx, rule, || (rule.matches)(ctx)).map_err(|err| WalkerErr::RuleFailedToRun {
+        rule_name: rule.name,
+        rule_description: rule.description.clone().unwrap_or_else(|| "<no rule description>".to_string()),
+        item_path: ctx.path.to_path_buf(),
+        stage: WalkerRuleStage::Matching,
+        err,
+    })
+}
+
+/// (Internal) Run one of a rule's callbacks, failing if it took longer than the rule's timeout (see [`WalkerRule::timeout`])
+/// or if it changed the current directory while asked to check it (see [`WalkerConfig::strict_rules`])
+fn run_rule_callback<T>(ctx: &RuleCtx, rule: &WalkerRule, callback: impl FnOnce() -> std::io::Result<T>) -> Result<T, WalkerRuleErr> {
+    let cwd = if ctx.config.strict_rules { Some(std::env::current_dir().ok()) } else { None };
+    let started = rule.timeout.map(|_| Instant::now());
+
+    let result = callback();
+
+    if let Some(cwd) = cwd {
+        if std::env::current_dir().ok() != cwd {
+            return Err(WalkerRuleErr::Str("rule changed the working directory".to_string()));
+        }
+    }
+
+    match (rule.timeout, started) {
+        (Some(timeout), Some(started)) if started.elapsed() > timeout => Err(WalkerRuleErr::TimedOut(timeout)),
+        (Some(timeout), _) if matches!(&result, Err(err) if err.kind() == std::io::ErrorKind::TimedOut) => Err(WalkerRuleErr::TimedOut(timeout)),
+        _ => result.map_err(WalkerRuleErr::Io),
+    }
 }
 
 /// (Internal) Run a walker rule on an item
-fn run_walker_rule(
-    item_path: &Path,
-    item_type: WalkerItemType,
-    config: &WalkerConfig,
-    canonicalized_source: &Path,
-    rule: &WalkerRule,
-) -> Result<WalkerRuleDo, WalkerErr> {
+fn run_walker_rule(ctx: &RuleCtx, rule: &WalkerRule) -> Result<WalkerRuleDo, WalkerErr> {
+    let item_path = ctx.path;
+    let item_type = ctx.item_type;
+
     // Get the rule's plain description
     let rule_description = || rule.description.clone().unwrap_or_else(|| "<no rule description>".to_string());
 
@@ -191,16 +1745,17 @@ fn run_walker_rule(
         rule_name: rule.name,
         rule_description: rule_description(),
         item_path: item_path.to_path_buf(),
+        stage: WalkerRuleStage::Action,
         err,
     };
 
     // Run the rule and get its result
-    let rule_result = (rule.action)(&item_path, config, canonicalized_source)
-        .map_err(WalkerRuleErr::Io)
-        .map_err(rule_failed)?;
+    let rule_result = run_rule_callback(ctx, rule, || (rule.action)(ctx)).map_err(rule_failed)?;
 
     debug!(">> Rule returned response: {:?}", rule_result);
 
+    let from_source = matches!(rule_result, WalkerRuleResult::MapAsListFromSource(_, _));
+
     match rule_result {
         // Rule failed with an error message
         WalkerRuleResult::StrError(err) => Err(rule_failed(WalkerRuleErr::Str(err))),
@@ -218,7 +1773,10 @@ fn run_walker_rule(
         WalkerRuleResult::ExcludeItem => Ok(WalkerRuleDo::SkipItem),
 
         // Rule indicated to map the item it was applied on to a specific list of items
-        WalkerRuleResult::MapAsList(paths, absolute) => {
+        WalkerRuleResult::MapAsList(paths, absolute) | WalkerRuleResult::MapAsListFromSource(paths, absolute) => {
+            // Base directory of relative paths, which all paths must be located inside of
+            let base = if from_source { ctx.source } else { item_path };
+
             if item_type == WalkerItemType::File {
                 return Err(WalkerErr::RuleMappedFileAsDir {
                     rule_name: rule.name,
@@ -229,21 +1787,38 @@ fn run_walker_rule(
 
             let mut mapped_items = Vec::with_capacity(paths.len());
 
+            let follow_symlinks = ctx.config.symlink_mode == SymlinkMode::Follow;
+
             for mut mapped_item_path in paths {
                 if !mapped_item_path.is_absolute() {
-                    mapped_item_path = item_path.join(mapped_item_path)
+                    mapped_item_path = base.join(mapped_item_path)
                 }
 
-                if !mapped_item_path.ancestors().any(|ancestor| ancestor == item_path) {
-                    return Err(WalkerErr::RuleMappingContainsExternalItem {
-                        rule_name: rule.name,
-                        rule_description: rule_description(),
-                        item_path: item_path.to_path_buf(),
-                        mapped_item_path,
-                    });
+                if !mapped_item_path.ancestors().any(|ancestor| ancestor == base) {
+                    mapped_item_path = match relocate_mapped_item(&*ctx.config.filesystem, base, &mapped_item_path, follow_symlinks) {
+                        Some(relocated) => relocated,
+                        None => {
+                            return Err(WalkerErr::RuleMappingContainsExternalItem {
+                                rule_name: rule.name,
+                                rule_description: rule_description(),
+                                item_path: item_path.to_path_buf(),
+                                mapped_item_path,
+                            })
+                        }
+                    };
                 }
 
-                if !mapped_item_path.exists() {
+                if ctx.config.mapping_validation != MappingValidation::KeepUnchecked && ctx.config.filesystem.metadata(&mapped_item_path).is_err() {
+                    if ctx.config.mapping_validation == MappingValidation::SkipMissing {
+                        err!(
+                            "Rule '{}' mapped directory '{}' as a list containing inexisting item, skipping it: {}",
+                            rule.name,
+                            item_path.display(),
+                            mapped_item_path.display()
+                        );
+                        continue;
+                    }
+
                     return Err(WalkerErr::RuleMappingContainsNonExistingItem {
                         rule_name: rule.name,
                         rule_description: rule_description(),
@@ -255,9 +1830,64 @@ fn run_walker_rule(
                 mapped_items.push(mapped_item_path);
             }
 
-            Ok(WalkerRuleDo::MapItem(mapped_items, absolute))
+            Ok(WalkerRuleDo::MapItem(mapped_items, absolute, from_source))
+        }
+
+        // Rule indicated to rename the item it was applied on
+        WalkerRuleResult::RenameItem(path) => Ok(WalkerRuleDo::Rename(path)),
+
+        // Rule indicated to include the directory it was applied on without traversing it
+        WalkerRuleResult::IncludeWithoutDescendants => match item_type {
+            WalkerItemType::Directory => Ok(WalkerRuleDo::IncludeWithoutDescendants),
+            WalkerItemType::Symlink if ctx.config.symlink_mode == SymlinkMode::Follow && target_is_dir(&*ctx.config.filesystem, item_path)? => {
+                Ok(WalkerRuleDo::IncludeWithoutDescendants)
+            }
+            WalkerItemType::Symlink | WalkerItemType::File | WalkerItemType::Other => Ok(WalkerRuleDo::Nothing),
+        },
+    }
+}
+
+/// (Internal) Get the path of a mapped item relative to the item it was mapped from, if it is located inside of it
+/// after normalization, or through symbolic links if they are followed (see [`WalkerRuleResult::MapAsList`])
+fn relocate_mapped_item(filesystem: &dyn FileSystem, item_path: &Path, mapped_item_path: &Path, follow_symlinks: bool) -> Option<PathBuf> {
+    // Remove '.' and '..' components
+    let mut normalized = PathBuf::new();
+
+    for component in mapped_item_path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            component => normalized.push(component),
         }
     }
+
+    if let Ok(relative) = normalized.strip_prefix(item_path) {
+        return Some(item_path.join(relative));
+    }
+
+    if !follow_symlinks {
+        return None;
+    }
+
+    let canonical_item = filesystem.canonicalize(item_path).ok()?;
+    let canonical_mapped_item = filesystem.canonicalize(mapped_item_path).ok()?;
+
+    canonical_mapped_item
+        .strip_prefix(&canonical_item)
+        .ok()
+        .map(|relative| item_path.join(relative))
+}
+
+/// (Internal) Create an error for a conflicting renaming
+fn rename_conflict(rule: &WalkerRule, item_path: &Path, renamed_path: PathBuf) -> WalkerErr {
+    WalkerErr::RuleRenameConflict {
+        rule_name: rule.name,
+        rule_description: rule.description.clone().unwrap_or_else(|| "<no rule description>".to_string()),
+        item_path: item_path.to_path_buf(),
+        renamed_path,
+    }
 }
 
 /// (Internal) Action to perform after a specific rule ended
@@ -271,8 +1901,15 @@ enum WalkerRuleDo {
     /// Skip this item
     SkipItem,
 
-    /// Map this item as a list of paths, also indicating if the mapping is absolute
-    MapItem(Vec<PathBuf>, bool),
+    /// Map this item as a list of paths, also indicating if the mapping is absolute and if the paths
+    /// were provided relatively to the source directory
+    MapItem(Vec<PathBuf>, bool, bool),
+
+    /// Include this item without traversing it
+    IncludeWithoutDescendants,
+
+    /// Rename this item (and its descendants) in the files list
+    Rename(PathBuf),
 }
 
 /// Error occured while the [walker](walk) was running
@@ -282,17 +1919,21 @@ pub enum WalkerErr {
     #[error("Failed to canonicalize path: {0} ({1})")]
     FailedToCanonicalize(PathBuf, std::io::Error),
 
+    /// The walk was cancelled (see [`WalkerConfig::cancel`])
+    #[error("Walk was cancelled")]
+    Cancelled,
+
     /// (Internal error) Directory provided to the walker was not found
     #[error("Internal: directory provided to walker was not found")]
     DirNotFound,
 
     /// Failed to walk through a directory ([`std::fs::read_dir`] I/O error)
-    #[error("Failed to walk directory: {0}")]
-    FailedToWalkDir(std::io::Error),
+    #[error("Failed to walk directory: {0} ({1})")]
+    FailedToWalkDir(PathBuf, std::io::Error),
 
     /// Failed to read a directory entry ([`std::fs::DirEntry`] I/O error)
-    #[error("Failed to read directory entry: {0}")]
-    FailedToReadDirEntry(std::io::Error),
+    #[error("Failed to read an entry of directory: {0} ({1})")]
+    FailedToReadDirEntry(PathBuf, std::io::Error),
 
     /// Failed to read the target of a symbolic link ([`std::fs::read_link`] I/O error)
     #[error("Failed to read the target of the symbolic link at path: {0} ({1})")]
@@ -303,11 +1944,12 @@ pub enum WalkerErr {
     FailedToGetItemMetadata(PathBuf, std::io::Error),
 
     /// A [rule](WalkerRule) failed to run
-    #[error("Rule '{rule_name}' ({rule_description}) failed to execute: {err} (on item: {item_path})")]
+    #[error("Rule '{rule_name}' ({rule_description}) failed to execute its {stage}: {err} (on item: {item_path})")]
     RuleFailedToRun {
         rule_name: &'static str,
         rule_description: String,
         item_path: PathBuf,
+        stage: WalkerRuleStage,
         err: WalkerRuleErr,
     },
 
@@ -336,6 +1978,149 @@ pub enum WalkerErr {
         item_path: PathBuf,
         mapped_item_path: PathBuf,
     },
+
+    /// The files list contains more items than allowed (see [`WalkerConfig::max_items`])
+    #[error("Files list contains more than {limit} items (stopped at: {at_path})")]
+    TooManyItems { limit: usize, at_path: PathBuf },
+
+    /// An item to return is not located inside the source directory, so its path can't be made relative to it
+    /// (see [`WalkerConfig::output_paths`])
+    #[error("Item is not located inside the source directory: {0}")]
+    ItemOutsideSource(PathBuf),
+
+    /// The files list doesn't contain any item (see [`WalkerConfig::fail_if_empty`])
+    #[error("Files list is empty ({excluded} items were excluded by rules or filters)")]
+    EmptyResult { excluded: u64 },
+
+    /// A followed symbolic link points outside of the source directory (see [`WalkerConfig::symlink_escape`])
+    #[error("Symbolic link points outside of the source directory: {0} => {1}")]
+    SymlinkEscapesSource(PathBuf, PathBuf),
+
+    /// A rule renamed an item that was already renamed by another rule, or to a path another item was already renamed to
+    /// (see [`WalkerRuleResult::RenameItem`])
+    #[error("Rule '{rule_name}' ({rule_description}) renamed item '{item_path}' to '{renamed_path}', which conflicts with another renaming")]
+    RuleRenameConflict {
+        rule_name: &'static str,
+        rule_description: String,
+        item_path: PathBuf,
+        renamed_path: PathBuf,
+    },
+}
+
+impl WalkerErr {
+    /// Check if the walker can go on after this error by skipping the related item
+    ///
+    /// This is the case for I/O errors occurring on specific items, but not for errors caused by rules.
+    pub fn is_recoverable(&self) -> bool {
+        match self {
+            Self::FailedToCanonicalize(_, _)
+            | Self::FailedToWalkDir(_, _)
+            | Self::FailedToReadDirEntry(_, _)
+            | Self::FailedToReadSymlinkTarget(_, _)
+            | Self::FailedToGetItemMetadata(_, _) => true,
+
+            Self::Cancelled
+            | Self::DirNotFound
+            | Self::SymlinkEscapesSource(_, _)
+            | Self::ItemOutsideSource(_)
+            | Self::EmptyResult { .. }
+            | Self::TooManyItems { .. }
+            | Self::RuleFailedToRun { .. }
+            | Self::RuleMappedFileAsDir { .. }
+            | Self::RuleMappingContainsExternalItem { .. }
+            | Self::RuleMappingContainsNonExistingItem { .. }
+            | Self::RuleRenameConflict { .. } => false,
+        }
+    }
+
+    /// Get a stable machine-readable code identifying the kind of error (the name of its variant, e.g. `FailedToWalkDir`)
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::FailedToCanonicalize(_, _) => "FailedToCanonicalize",
+            Self::Cancelled => "Cancelled",
+            Self::DirNotFound => "DirNotFound",
+            Self::FailedToWalkDir(_, _) => "FailedToWalkDir",
+            Self::FailedToReadDirEntry(_, _) => "FailedToReadDirEntry",
+            Self::FailedToReadSymlinkTarget(_, _) => "FailedToReadSymlinkTarget",
+            Self::FailedToGetItemMetadata(_, _) => "FailedToGetItemMetadata",
+            Self::RuleFailedToRun { .. } => "RuleFailedToRun",
+            Self::RuleMappedFileAsDir { .. } => "RuleMappedFileAsDir",
+            Self::RuleMappingContainsExternalItem { .. } => "RuleMappingContainsExternalItem",
+            Self::RuleMappingContainsNonExistingItem { .. } => "RuleMappingContainsNonExistingItem",
+            Self::TooManyItems { .. } => "TooManyItems",
+            Self::ItemOutsideSource(_) => "ItemOutsideSource",
+            Self::EmptyResult { .. } => "EmptyResult",
+            Self::SymlinkEscapesSource(_, _) => "SymlinkEscapesSource",
+            Self::RuleRenameConflict { .. } => "RuleRenameConflict",
+        }
+    }
+
+    /// Get the path of the item the error occurred on, if any
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            Self::FailedToCanonicalize(path, _)
+            | Self::FailedToWalkDir(path, _)
+            | Self::FailedToReadDirEntry(path, _)
+            | Self::FailedToReadSymlinkTarget(path, _)
+            | Self::FailedToGetItemMetadata(path, _)
+            | Self::ItemOutsideSource(path)
+            | Self::SymlinkEscapesSource(path, _) => Some(path),
+
+            Self::RuleFailedToRun { item_path, .. }
+            | Self::RuleMappedFileAsDir { item_path, .. }
+            | Self::RuleMappingContainsExternalItem { item_path, .. }
+            | Self::RuleMappingContainsNonExistingItem { item_path, .. }
+            | Self::RuleRenameConflict { item_path, .. } => Some(item_path),
+
+            Self::TooManyItems { at_path, .. } => Some(at_path),
+
+            Self::Cancelled | Self::DirNotFound | Self::EmptyResult { .. } => None,
+        }
+    }
+
+    /// Display the error to STDERR with a context prefixed to its message, if the logging level is high enough
+    ///
+    /// When [JSON messages](crate::set_log_format) are enabled, the error's [code](Self::code) and [path](Self::path) are provided as well.
+    pub fn log(&self, level: LoggerLevel, context: &str) {
+        if log_level() < level {
+            return;
+        }
+
+        with_status_line_cleared(|| match log_format() {
+            LoggerFormat::Json => eprintln!("{}", json_message(level, Some(self.code()), self.path(), &format!("{}{}", context, self))),
+            LoggerFormat::Text if level == LoggerLevel::Failure => eprintln!("[FAIL] {}{}", context, self),
+            LoggerFormat::Text => eprintln!("[ERROR] {}{}", context, self),
+        });
+    }
+
+    /// (Internal) Check if the error is caused by insufficient permissions to read an item
+    fn is_permission_denied(&self) -> bool {
+        match self {
+            Self::FailedToWalkDir(_, err) | Self::FailedToReadSymlinkTarget(_, err) | Self::FailedToGetItemMetadata(_, err) => {
+                err.kind() == std::io::ErrorKind::PermissionDenied
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Callback of a walker rule which failed (see [`WalkerErr::RuleFailedToRun`])
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WalkerRuleStage {
+    /// The rule's predicate failed (see [`WalkerRule::matches`])
+    Matching,
+
+    /// The rule's action failed (see [`WalkerRule::action`])
+    Action,
+}
+
+impl fmt::Display for WalkerRuleStage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Matching => write!(f, "predicate"),
+            Self::Action => write!(f, "action"),
+        }
+    }
 }
 
 /// Error caused by a walker rule (see [`WalkerRule`])
@@ -343,6 +2128,9 @@ pub enum WalkerErr {
 pub enum WalkerRuleErr {
     Io(std::io::Error),
     Str(String),
+
+    /// The rule took longer than its timeout (see [`WalkerRule::timeout`])
+    TimedOut(std::time::Duration),
 }
 
 impl fmt::Display for WalkerRuleErr {
@@ -350,6 +2138,7 @@ impl fmt::Display for WalkerRuleErr {
         match self {
             Self::Io(err) => write!(f, "{}", err),
             Self::Str(err) => write!(f, "{}", err),
+            Self::TimedOut(timeout) => write!(f, "timed out after {:?}", timeout),
         }
     }
 }
@@ -0,0 +1,210 @@
+//! # The parallel walker
+//!
+//! This module contains the [parallel walker](walk_parallel), which traverses sibling directories concurrently.
+
+use super::{canonicalize_source, open_source, PendingDir, WalkIter, WalkState, WalkerErr};
+use crate::config::{ErrorPolicy, WalkerConfig};
+use crate::logger::LoggerLevel;
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+
+/// Walk through a directory (recursively) using multiple threads to build a list of files to backup
+///
+/// This behaves like [`walk`](super::walk), but sibling directories are traversed concurrently by a pool of `threads` threads
+/// (if `0` is provided, the number of available CPU cores is used). This is a lot faster on trees with many directories,
+/// where the traversal is mostly spent waiting for the filesystem.
+///
+/// ## Traversal order
+///
+/// The order of the returned items is unspecified. When multiple paths lead to the same item (e.g. through symbolic links
+/// or hard links), which one is returned is unspecified as well.
+///
+/// ## Rules
+///
+/// Rules may be run concurrently on different items.
+///
+/// ## Error handling
+///
+/// Errors are handled as in [`walk`](super::walk): the first error stops all threads and is returned, unless
+/// [`WalkerConfig::on_error`] is set to [`ErrorPolicy::Collect`] and the error is recoverable.
+pub fn walk_parallel(dir: &Path, config: &WalkerConfig, threads: usize) -> Result<Vec<PathBuf>, WalkerErr> {
+    let source = canonicalize_source(&*config.filesystem, dir)?;
+
+    let mut state = WalkState::default();
+    let source_dir = open_source(&*config.filesystem, source.clone(), &mut state)?;
+    let state = Arc::new(Mutex::new(state));
+
+    let threads = match threads {
+        0 => thread::available_parallelism().map(|threads| threads.get()).unwrap_or(1),
+        threads => threads,
+    };
+
+    let queue = WorkQueue::new(source_dir);
+
+    let outputs: Vec<_> = thread::scope(|scope| {
+        let workers: Vec<_> = (0..threads)
+            .map(|_| {
+                let mut worker = WalkIter::with_state(config, source.clone(), Arc::clone(&state));
+                worker.check_empty = false;
+
+                let queue = &queue;
+                scope.spawn(move || run_worker(worker, queue))
+            })
+            .collect();
+
+        workers
+            .into_iter()
+            .map(|worker| worker.join().unwrap_or_else(|err| std::panic::resume_unwind(err)))
+            .collect()
+    });
+
+    if let Some(err) = queue.error.into_inner().expect("Internal error: parallel walker's lock is poisoned") {
+        return Err(err);
+    }
+
+    WalkIter::with_state(config, source, state).ensure_not_empty()?;
+
+    Ok(outputs.into_iter().flatten().collect())
+}
+
+/// (Internal) Run a worker of the parallel walker until there is no more directory to traverse
+fn run_worker(mut worker: WalkIter, queue: &WorkQueue) -> Vec<PathBuf> {
+    let on_error = worker.config.on_error;
+    let mut items = vec![];
+
+    worker.defer_dirs = true;
+
+    while let Some(dir) = queue.pop() {
+        // Ensure the directory is marked as traversed even if a rule panics, so the other workers don't wait forever
+        let _task = TaskGuard(queue);
+
+        let mut result = worker.open_dir(dir);
+
+        while result.is_ok() && !queue.aborted.load(Ordering::SeqCst) {
+            match worker.next_item() {
+                None => break,
+                Some(Ok(item)) => items.push(item.path),
+                Some(Err(err)) => result = Err(err),
+            }
+
+            if let Err(err) = &result {
+                if on_error == ErrorPolicy::Collect && err.is_recoverable() {
+                    err.log(LoggerLevel::Error, "");
+                    result = Ok(());
+                }
+            }
+
+            queue.push(worker.deferred.drain(..));
+        }
+
+        queue.push(worker.deferred.drain(..));
+
+        match result {
+            Err(err) if on_error == ErrorPolicy::Collect && err.is_recoverable() => {
+                err.log(LoggerLevel::Error, "");
+            }
+            Err(err) => queue.abort(err),
+            Ok(()) => {}
+        }
+
+        worker.stack.clear();
+        worker.ready.clear();
+        worker.deferred.clear();
+    }
+
+    items
+}
+
+/// (Internal) Guard indicating a directory was fully traversed when dropped (see [`WorkQueue::done`])
+///
+/// If the worker is panicking, the walk is aborted as well, so the panic can be propagated once all workers stopped.
+struct TaskGuard<'a>(&'a WorkQueue);
+
+impl Drop for TaskGuard<'_> {
+    fn drop(&mut self) {
+        if thread::panicking() {
+            self.0.aborted.store(true, Ordering::SeqCst);
+        }
+
+        self.0.done();
+    }
+}
+
+/// (Internal) Queue of directories to traverse, shared between the workers of the parallel walker
+struct WorkQueue {
+    /// Pending directories, and number of directories being traversed
+    tasks: Mutex<(VecDeque<PendingDir>, usize)>,
+
+    /// Condition notified when the queue changes
+    changed: Condvar,
+
+    /// Indicates if the walk was aborted
+    aborted: AtomicBool,
+
+    /// Error which aborted the walk
+    error: Mutex<Option<WalkerErr>>,
+}
+
+impl WorkQueue {
+    fn new(source: PendingDir) -> Self {
+        Self {
+            tasks: Mutex::new((VecDeque::from(vec![source]), 0)),
+            changed: Condvar::new(),
+            aborted: AtomicBool::new(false),
+            error: Mutex::new(None),
+        }
+    }
+
+    /// Wait for a directory to traverse, or return `None` if the walk is over
+    fn pop(&self) -> Option<PendingDir> {
+        let mut tasks = self.tasks.lock().expect("Internal error: parallel walker's lock is poisoned");
+
+        loop {
+            if self.aborted.load(Ordering::SeqCst) {
+                return None;
+            }
+
+            if let Some(task) = tasks.0.pop_front() {
+                tasks.1 += 1;
+                return Some(task);
+            }
+
+            if tasks.1 == 0 {
+                return None;
+            }
+
+            tasks = self.changed.wait(tasks).expect("Internal error: parallel walker's lock is poisoned");
+        }
+    }
+
+    /// Add directories to traverse
+    fn push(&self, dirs: impl Iterator<Item = PendingDir>) {
+        let mut dirs = dirs.peekable();
+
+        if dirs.peek().is_some() {
+            self.tasks.lock().expect("Internal error: parallel walker's lock is poisoned").0.extend(dirs);
+            self.changed.notify_all();
+        }
+    }
+
+    /// Indicate a directory was fully traversed
+    fn done(&self) {
+        self.tasks.lock().expect("Internal error: parallel walker's lock is poisoned").1 -= 1;
+        self.changed.notify_all();
+    }
+
+    /// Abort the walk with an error (only the first error is kept)
+    fn abort(&self, err: WalkerErr) {
+        let mut error = self.error.lock().expect("Internal error: parallel walker's lock is poisoned");
+
+        if error.is_none() {
+            *error = Some(err);
+        }
+
+        self.aborted.store(true, Ordering::SeqCst);
+        self.changed.notify_all();
+    }
+}
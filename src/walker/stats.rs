@@ -0,0 +1,209 @@
+//! # The walk's statistics
+//!
+//! This module contains [`WalkStats`], which describes what happened during a walk (see [`walk_with_stats`](super::walk_with_stats)).
+
+use super::WalkState;
+use crate::config::{WalkerItemType, WalkerRule};
+use crate::item::WalkerItem;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Statistics collected during a walk
+#[derive(Debug, Clone, Default)]
+pub struct WalkStats {
+    /// Statistics of each rule, in the order the rules were provided in
+    pub rules: Vec<RuleStats>,
+
+    /// Number of items skipped because they could not be read (see [`WalkerConfig::skip_unreadable`](crate::WalkerConfig::skip_unreadable))
+    pub skipped_unreadable: u64,
+
+    /// Number of files in the files list
+    pub files: u64,
+
+    /// Number of directories in the files list
+    pub directories: u64,
+
+    /// Number of symbolic links in the files list
+    pub symlinks: u64,
+
+    /// Number of special files in the files list (see [`WalkerConfig::include_special_files`](crate::WalkerConfig::include_special_files))
+    pub special_files: u64,
+
+    /// Total size of the files in the files list, in bytes
+    pub total_size: u64,
+
+    /// Number of items excluded by rules or filters (the content of excluded directories is not counted)
+    pub excluded: u64,
+
+    /// Largest files of the files list with their size in bytes, from the largest to the smallest
+    /// (at most [`WalkStats::LARGEST_FILES`] files)
+    pub largest_files: Vec<(PathBuf, u64)>,
+
+    /// Items skipped because they were already visited (e.g. through a symbolic link or a bind mount)
+    pub duplicates: Vec<DuplicateItem>,
+}
+
+/// Item skipped because it was already visited (see [`WalkStats::duplicates`])
+///
+/// ```
+/// # #[cfg(unix)] {
+/// use rebackup::{walk_with_stats, DuplicateCause, SymlinkMode, WalkerConfig};
+/// use std::fs;
+///
+/// let dir = std::env::temp_dir().join("rebackup-doctest-duplicates");
+/// # let _ = fs::remove_dir_all(&dir);
+/// fs::create_dir_all(dir.join("real")).unwrap();
+/// fs::write(dir.join("real/file"), "").unwrap();
+/// std::os::unix::fs::symlink(dir.join("real"), dir.join("zlink")).unwrap();
+///
+/// let dir = fs::canonicalize(&dir).unwrap();
+/// let config = WalkerConfig::builder().symlink_mode(SymlinkMode::Follow).sort_entries(true).build();
+/// let (_, stats) = walk_with_stats(&dir, &config).unwrap();
+///
+/// assert_eq!(stats.duplicates.len(), 1);
+/// assert_eq!(stats.duplicates[0].original, Some(dir.join("real")));
+/// assert_eq!(stats.duplicates[0].duplicate, dir.join("zlink"));
+/// assert_eq!(stats.duplicates[0].cause, DuplicateCause::SymlinkTargetVisited);
+/// # fs::remove_dir_all(&dir).unwrap();
+/// # }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DuplicateItem {
+    /// Absolute path the item was first found at
+    ///
+    /// To limit memory usage, this path is only known if the item was first found as a directory or through
+    /// a symbolic link.
+    pub original: Option<PathBuf>,
+
+    /// Absolute path of the skipped item
+    pub duplicate: PathBuf,
+
+    /// Why the item was considered as already visited
+    pub cause: DuplicateCause,
+}
+
+/// Reason why an item was considered as already visited (see [`DuplicateItem`])
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DuplicateCause {
+    /// The item itself was already visited at another path (e.g. through a bind mount or an ancestor symbolic link)
+    AlreadyVisited,
+
+    /// The item is a followed symbolic link whose target was already visited
+    SymlinkTargetVisited,
+}
+
+/// Statistics of a single [rule](WalkerRule)
+#[derive(Debug, Clone)]
+pub struct RuleStats {
+    /// Rule's name
+    pub name: &'static str,
+
+    /// Rule's optional description
+    pub description: Option<String>,
+
+    /// Number of items the rule's predicate was run on
+    pub evaluated: u64,
+
+    /// Number of items the rule was run on (items its predicate matched)
+    pub matched: u64,
+
+    /// Number of items the rule excluded
+    pub excluded: u64,
+
+    /// Number of items the rule mapped to a list of items
+    pub mapped: u64,
+
+    /// Cumulated time spent in the rule's callbacks
+    /// (only measured when [`WalkerConfig::time_rules`](crate::WalkerConfig::time_rules) is enabled)
+    pub duration: Option<Duration>,
+}
+
+/// (Internal) Counters of the items in the files list, collected during the walk
+#[derive(Default)]
+pub(super) struct ItemsCounters {
+    files: u64,
+    directories: u64,
+    symlinks: u64,
+    special_files: u64,
+    total_size: u64,
+    largest_files: BinaryHeap<Reverse<(u64, PathBuf)>>,
+}
+
+impl ItemsCounters {
+    /// Count an item of the files list
+    pub(super) fn record(&mut self, item: &WalkerItem) {
+        match item.item_type {
+            WalkerItemType::Directory => self.directories += 1,
+            WalkerItemType::Symlink => self.symlinks += 1,
+            WalkerItemType::Other => self.special_files += 1,
+            WalkerItemType::File => {
+                let size = item.metadata.len();
+
+                self.files += 1;
+                self.total_size += size;
+
+                // Only keep the largest files
+                if self.largest_files.len() < WalkStats::LARGEST_FILES {
+                    self.largest_files.push(Reverse((size, item.path.clone())));
+                } else if self.largest_files.peek().is_some_and(|Reverse((smallest, _))| size > *smallest) {
+                    self.largest_files.pop();
+                    self.largest_files.push(Reverse((size, item.path.clone())));
+                }
+            }
+        }
+    }
+}
+
+/// (Internal) Counters of a single rule, collected during the walk
+#[derive(Default, Clone, Copy)]
+pub(super) struct RuleCounters {
+    pub(super) evaluated: u64,
+    pub(super) matched: u64,
+    pub(super) excluded: u64,
+    pub(super) mapped: u64,
+    pub(super) duration: Duration,
+}
+
+impl WalkStats {
+    /// Maximum number of files listed in [`WalkStats::largest_files`]
+    pub const LARGEST_FILES: usize = 10;
+
+    /// (Internal) Build the statistics from the counters collected during the walk
+    pub(super) fn new(rules: &[WalkerRule], state: &WalkState, timed: bool) -> Self {
+        let items = &state.items_counters;
+
+        let mut largest_files: Vec<_> = items.largest_files.iter().map(|Reverse((size, path))| (path.clone(), *size)).collect();
+        largest_files.sort_by(|(a_path, a_size), (b_path, b_size)| b_size.cmp(a_size).then_with(|| a_path.cmp(b_path)));
+
+        Self {
+            rules: rules
+                .iter()
+                .enumerate()
+                .map(|(index, rule)| {
+                    let counters = state.rule_stats.get(index).copied().unwrap_or_default();
+
+                    RuleStats {
+                        name: rule.name,
+                        description: rule.description.clone(),
+                        evaluated: counters.evaluated,
+                        matched: counters.matched,
+                        excluded: counters.excluded,
+                        mapped: counters.mapped,
+                        duration: if timed { Some(counters.duration) } else { None },
+                    }
+                })
+                .collect(),
+            skipped_unreadable: state.counters.skipped_unreadable,
+            files: items.files,
+            directories: items.directories,
+            symlinks: items.symlinks,
+            special_files: items.special_files,
+            total_size: items.total_size,
+            excluded: state.counters.items_excluded,
+            largest_files,
+            duplicates: state.duplicates.clone(),
+        }
+    }
+}
@@ -0,0 +1,103 @@
+//! # The asynchronous walker
+//!
+//! This module contains the [asynchronous walker](walk_async), which yields the files to backup without blocking
+//! the Tokio runtime it is used in.
+
+use super::{WalkIter, WalkerErr};
+use crate::config::WalkerConfig;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tokio::sync::mpsc::{self, Receiver};
+
+/// (Internal) Maximum number of items waiting to be consumed before the traversal is paused
+const BUFFER_SIZE: usize = 1024;
+
+/// Walk through a directory (recursively) asynchronously, yielding the files to backup one by one
+///
+/// This behaves exactly like [`walk_iter`](super::walk_iter), but the traversal happens on Tokio's blocking thread pool
+/// (see [`tokio::task::spawn_blocking`]), so the runtime polling the returned [`WalkStream`] is never blocked by
+/// filesystem operations. This function must be called from within a Tokio runtime.
+///
+/// The traversal is paused when too many items are waiting to be consumed, and stops when the stream is dropped.
+///
+/// ## Rules
+///
+/// Rules (as well as the progress callback) are run on the blocking thread pool, not on the runtime's workers. They can
+/// block without affecting the runtime's other tasks.
+///
+/// ```
+/// use rebackup::{walk_async, WalkerConfig};
+/// use std::fs;
+/// use std::sync::Arc;
+///
+/// let dir = std::env::temp_dir().join("rebackup-doctest-walk-async");
+/// # let _ = fs::remove_dir_all(&dir);
+/// fs::create_dir_all(dir.join("dir/empty")).unwrap();
+/// fs::write(dir.join("dir/file"), "").unwrap();
+///
+/// let dir = fs::canonicalize(&dir).unwrap();
+/// let config = Arc::new(WalkerConfig::builder().sort_entries(true).build());
+///
+/// let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+///
+/// let items = runtime.block_on(async {
+///     let mut stream = walk_async(&dir, config);
+///     let mut items = vec![];
+///
+///     while let Some(item) = stream.next_item().await {
+///         items.push(item.unwrap());
+///     }
+///
+///     items
+/// });
+///
+/// assert_eq!(items, vec![dir.join("dir/empty"), dir.join("dir/file")]);
+/// # fs::remove_dir_all(&dir).unwrap();
+/// ```
+pub fn walk_async(dir: &Path, config: Arc<WalkerConfig>) -> WalkStream {
+    let (sender, receiver) = mpsc::channel(BUFFER_SIZE);
+    let dir = dir.to_path_buf();
+
+    tokio::task::spawn_blocking(move || {
+        for item in WalkIter::new(&dir, &config) {
+            // Stop the traversal if the stream was dropped
+            if sender.blocking_send(item).is_err() {
+                return;
+            }
+        }
+
+        // Dropping the sender ends the stream
+    });
+
+    WalkStream { receiver }
+}
+
+/// Asynchronous stream of the files to backup (see [`walk_async`])
+///
+/// It implements the `Stream` trait of the `futures` crate.
+pub struct WalkStream {
+    /// Items found by the walker's blocking task
+    receiver: Receiver<Result<PathBuf, WalkerErr>>,
+}
+
+impl WalkStream {
+    /// Get the next item, or `None` if the walk is over
+    pub async fn next_item(&mut self) -> Option<Result<PathBuf, WalkerErr>> {
+        self.receiver.recv().await
+    }
+
+    /// Poll the next item, registering the current task to be woken up when it is available
+    pub fn poll_next(&mut self, cx: &mut Context<'_>) -> Poll<Option<Result<PathBuf, WalkerErr>>> {
+        self.receiver.poll_recv(cx)
+    }
+}
+
+impl futures_core::Stream for WalkStream {
+    type Item = Result<PathBuf, WalkerErr>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.get_mut().receiver.poll_recv(cx)
+    }
+}
@@ -0,0 +1,84 @@
+//! Tests of the asynchronous walker
+
+use rebackup::{walk, walk_async, WalkerConfig, WalkerItemTypes, WalkerRule, WalkerRuleResult};
+use std::fs;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tempfile::TempDir;
+
+/// Create a temporary directory containing `count` files, returning its canonicalized path
+fn fixture(count: usize) -> (TempDir, std::path::PathBuf) {
+    let tmp = TempDir::new().unwrap();
+    let root = fs::canonicalize(tmp.path()).unwrap();
+
+    fs::create_dir(root.join("dir")).unwrap();
+
+    for i in 0..count {
+        fs::write(root.join(format!("dir/file_{}", i)), "").unwrap();
+    }
+
+    (tmp, root)
+}
+
+#[tokio::test]
+async fn walk_async_yields_same_items_as_walk() {
+    let (_tmp, root) = fixture(100);
+    let config = Arc::new(WalkerConfig::builder().sort_entries(true).build());
+
+    let mut stream = walk_async(&root, Arc::clone(&config));
+    let mut items = vec![];
+
+    while let Some(item) = stream.next_item().await {
+        items.push(item.unwrap());
+    }
+
+    assert_eq!(items, walk(&root, &config).unwrap());
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn dropping_the_stream_stops_the_walk() {
+    // More items than the stream's buffer can hold, so the traversal has to wait for them to be consumed
+    let (_tmp, root) = fixture(3000);
+    let treated = Arc::new(AtomicUsize::new(0));
+
+    let counter = WalkerRule {
+        name: "counter",
+        description: None,
+        priority: 0,
+        only_for: WalkerItemTypes::all(),
+        min_depth: None,
+        max_depth: None,
+        cacheable: false,
+        timeout: None,
+        matches: {
+            let treated = Arc::clone(&treated);
+            Box::new(move |_| {
+                treated.fetch_add(1, Ordering::SeqCst);
+                Ok(false)
+            })
+        },
+        action: Box::new(|_| Ok(WalkerRuleResult::IncludeItem)),
+    };
+
+    let mut stream = walk_async(&root, Arc::new(WalkerConfig::new(vec![counter])));
+
+    for _ in 0..10 {
+        stream.next_item().await.unwrap().unwrap();
+    }
+
+    drop(stream);
+
+    // Leave time to the walker to notice the stream was dropped
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    let treated_after_drop = treated.load(Ordering::SeqCst);
+
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    assert_eq!(
+        treated.load(Ordering::SeqCst),
+        treated_after_drop,
+        "the walk went on after the stream was dropped"
+    );
+    assert!(treated_after_drop < 3000, "all {} items were treated", treated_after_drop);
+}
@@ -0,0 +1,642 @@
+//! Tests of the command-line interface
+
+#![cfg(feature = "cli")]
+
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+use std::process::{Command, Output, Stdio};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+use tempfile::TempDir;
+
+/// Run the command-line interface with the provided arguments
+fn rebackup(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_rebackup")).args(args).output().unwrap()
+}
+
+/// Create a temporary directory containing the provided directories and (empty) files
+fn fixture(dirs: &[&str], files: &[&str]) -> TempDir {
+    let tmp = TempDir::new().unwrap();
+
+    for dir in dirs {
+        fs::create_dir_all(tmp.path().join(dir)).unwrap();
+    }
+
+    for file in files {
+        fs::write(tmp.path().join(file), "").unwrap();
+    }
+
+    tmp
+}
+
+/// Build the files list of a directory with the provided arguments, and get its items
+///
+/// The directory is provided first, as options taking multiple values would consume it otherwise.
+fn list(dir: &Path, args: &[&str]) -> Vec<String> {
+    let output = Command::new(env!("CARGO_BIN_EXE_rebackup")).arg(dir).args(args).output().unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+
+    String::from_utf8(output.stdout).unwrap().lines().map(String::from).collect()
+}
+
+#[test]
+fn benchmark_rules() {
+    let dir = fixture(&["node_modules", "src"], &["a", "node_modules/b", "src/c", "src/d.tmp"]);
+
+    let output = rebackup(&[
+        dir.path().to_str().unwrap(),
+        "--benchmark-rules",
+        "--rule",
+        "node_modules",
+        "--rule",
+        "nomedia",
+        "--exclude-ext",
+        "tmp",
+    ]);
+
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "a\nsrc/c\n");
+
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    let lines: Vec<_> = stderr.lines().collect();
+
+    assert!(lines[0].starts_with("Rule"));
+    assert!(lines[0].contains("Calls") && lines[0].contains("Average"));
+
+    // Each configured rule is listed exactly once
+    let mut names: Vec<_> = lines[1..].iter().map(|line| line.split_whitespace().next().unwrap()).collect();
+    names.sort_unstable();
+    assert_eq!(names, ["exclude_extensions", "node_modules", "nomedia"], "{}", stderr);
+
+    // Directory rules are only run on directories
+    let node_modules = lines.iter().find(|line| line.starts_with("node_modules")).unwrap();
+    let columns: Vec<_> = node_modules.split_whitespace().collect();
+    assert_eq!(columns[columns.len() - 4..columns.len() - 2], ["2", "1"]);
+}
+
+#[test]
+fn exclude_hidden() {
+    let dir = fixture(
+        &[".config/app", "dir", "dir..name"],
+        &[".hidden_file", ".config/app/settings", "dir/.b", "dir/c", "dir..name/d", "e.."],
+    );
+
+    assert_eq!(list(dir.path(), &["--exclude-hidden"]), ["dir..name/d", "dir/c", "e.."]);
+    assert_eq!(
+        list(dir.path(), &["--exclude-hidden=dirs"]),
+        [".hidden_file", "dir..name/d", "dir/.b", "dir/c", "e.."]
+    );
+    assert_eq!(
+        list(dir.path(), &["--exclude-hidden=files"]),
+        [".config/app/settings", "dir..name/d", "dir/c", "e.."]
+    );
+
+    // Parent directory components of the source are not hidden items
+    let parent_component = dir.path().join("dir/..");
+    assert_eq!(list(&parent_component, &["--exclude-hidden"]), ["dir..name/d", "dir/c", "e.."]);
+
+    // Neither is the source directory itself
+    assert_eq!(list(&dir.path().join(".config"), &["--exclude-hidden"]), ["app/settings"]);
+}
+
+#[test]
+fn exclude_and_only_extensions() {
+    let dir = fixture(
+        &["dir.iso"],
+        &["movie.MKV", "notes.txt", "photo.jpg", "archive.tar.gz", "README", "dir.iso/a.txt", "dir.iso/b"],
+    );
+
+    // Directories are not excluded by their extension, and files without one are kept
+    assert_eq!(
+        list(dir.path(), &["--exclude-ext", "mkv,.TXT", "--exclude-ext", "gz"]),
+        ["README", "dir.iso/b", "photo.jpg"]
+    );
+
+    // Files without an extension are left out
+    assert_eq!(
+        list(dir.path(), &["--only-ext", "txt,JPG", "--only-ext", "mkv"]),
+        ["dir.iso/a.txt", "movie.MKV", "notes.txt", "photo.jpg"]
+    );
+}
+
+#[test]
+fn exclude_older_than() {
+    let dir = fixture(&["old_dir"], &["new", "old", "future", "old_dir/new"]);
+
+    let now = SystemTime::now();
+    let day = Duration::from_secs(86400);
+
+    File::open(dir.path().join("old")).unwrap().set_modified(now - day * 3).unwrap();
+    File::open(dir.path().join("old_dir")).unwrap().set_modified(now - day * 3).unwrap();
+    File::open(dir.path().join("future")).unwrap().set_modified(now + day * 365).unwrap();
+
+    // Directories are still traversed, and files modified in the future are kept
+    assert_eq!(list(dir.path(), &["--exclude-older-than", "2d"]), ["future", "new", "old_dir/new"]);
+    assert_eq!(list(dir.path(), &["--exclude-older-than", "4d"]), ["future", "new", "old", "old_dir/new"]);
+
+    let output = rebackup(&[dir.path().to_str().unwrap(), "--exclude-older-than", "2 days"]);
+    assert!(!output.status.success());
+}
+
+#[test]
+fn exclude_dev_artifacts() {
+    let dir = fixture(
+        &[
+            "cargo/target/debug",
+            "node/node_modules/pkg",
+            "node/.next",
+            "python/.venv",
+            "python/__pycache__",
+            "python/build/lib",
+            "go/vendor/golang.org",
+            "docs/build",
+            "docs/vendor",
+        ],
+        &[
+            "cargo/Cargo.toml",
+            "cargo/target/debug/app",
+            "node/package.json",
+            "node/node_modules/pkg/index.js",
+            "node/.next/BUILD_ID",
+            "python/pyproject.toml",
+            "python/main.py",
+            "python/.venv/pyvenv.cfg",
+            "python/__pycache__/main.pyc",
+            "python/build/lib/main.py",
+            "go/go.mod",
+            "go/vendor/golang.org/modules.txt",
+            "docs/build/index.html",
+            "docs/vendor/invoice.pdf",
+        ],
+    );
+
+    // Directories without a marker file next to them are kept
+    assert_eq!(
+        list(dir.path(), &["--exclude-dev-artifacts"]),
+        [
+            "cargo/Cargo.toml",
+            "docs/build/index.html",
+            "docs/vendor/invoice.pdf",
+            "go/go.mod",
+            "node/package.json",
+            "python/main.py",
+            "python/pyproject.toml"
+        ]
+    );
+
+    // Each rule is reported by its own name
+    let output = rebackup(&[dir.path().to_str().unwrap(), "--exclude-dev-artifacts", "--explain", "--verbose"]);
+    let explained = String::from_utf8_lossy(&output.stdout).into_owned() + &String::from_utf8_lossy(&output.stderr);
+
+    for rule in [
+        "rust_cargo_build",
+        "node_package_modules",
+        "nextjs_build",
+        "python_venv",
+        "python_pycache",
+        "python_build",
+        "go_vendor",
+    ] {
+        assert!(explained.contains(rule), "{}: {}", rule, explained);
+    }
+}
+
+#[test]
+fn completions() {
+    for shell in ["bash", "zsh", "fish", "powershell"] {
+        let output = rebackup(&["completions", shell]);
+        assert!(output.status.success(), "{}: {}", shell, String::from_utf8_lossy(&output.stderr));
+
+        let script = String::from_utf8(output.stdout).unwrap();
+        assert!(script.contains("rebackup"), "{}", shell);
+        assert!(script.contains("exclude-ext"), "{}", shell);
+        assert!(script.contains("max-depth"), "{}", shell);
+    }
+
+    assert!(!rebackup(&["completions", "unknown"]).status.success());
+}
+
+#[cfg(unix)]
+#[test]
+fn bash_completions_are_valid() {
+    let script = rebackup(&["completions", "bash"]).stdout;
+    let file = tempfile::NamedTempFile::new().unwrap();
+    fs::write(file.path(), script).unwrap();
+
+    // Skip the check if Bash is not installed
+    if let Ok(status) = Command::new("bash").arg("-n").arg(file.path()).status() {
+        assert!(status.success());
+    }
+}
+
+/// Wait for the next line of a program's output matching a predicate, returning `None` after the timeout
+fn wait_for_line(lines: &Receiver<String>, timeout: Duration, predicate: impl Fn(&str) -> bool) -> Option<String> {
+    let deadline = Instant::now() + timeout;
+
+    loop {
+        let line = lines.recv_timeout(deadline.saturating_duration_since(Instant::now())).ok()?;
+
+        if predicate(&line) {
+            return Some(line);
+        }
+    }
+}
+
+#[test]
+fn watch_rebuilds_once_per_change() {
+    let dir = tempfile::tempdir().unwrap();
+    let source = dir.path().join("source");
+    let output = dir.path().join("files.list");
+
+    fs::create_dir_all(source.join(".git")).unwrap();
+    fs::write(source.join("a"), "").unwrap();
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_rebackup"))
+        .args(["--watch", "--watch-debounce", "0.3", "--exclude-vcs", "--verbose", "--output"])
+        .arg(&output)
+        .arg(&source)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+
+    let (sender, lines) = mpsc::channel();
+    let stdout = child.stdout.take().unwrap();
+
+    thread::spawn(move || {
+        for line in BufReader::new(stdout).lines() {
+            if sender.send(line.unwrap()).is_err() {
+                break;
+            }
+        }
+    });
+
+    let rebuilt = |line: &str| line.contains("Files list was updated") || line.contains("Files list didn't change");
+    let watching = |line: &str| line.contains("Watching source directories for changes");
+
+    assert!(wait_for_line(&lines, Duration::from_secs(30), watching).is_some());
+    assert_eq!(fs::read_to_string(&output).unwrap(), "a");
+
+    // Changes to excluded items are ignored
+    fs::write(source.join(".git/HEAD"), "ref: refs/heads/main").unwrap();
+    assert_eq!(wait_for_line(&lines, Duration::from_secs(2), rebuilt), None);
+
+    // Successive changes are debounced
+    fs::write(source.join("b"), "b").unwrap();
+    fs::write(source.join("b"), "bb").unwrap();
+    fs::write(source.join("c"), "c").unwrap();
+
+    let line = wait_for_line(&lines, Duration::from_secs(30), rebuilt);
+    assert!(line.unwrap().contains("Files list was updated"));
+    assert!(wait_for_line(&lines, Duration::from_secs(30), watching).is_some());
+    assert_eq!(wait_for_line(&lines, Duration::from_secs(2), rebuilt), None);
+
+    assert_eq!(fs::read_to_string(&output).unwrap(), "a\nb\nc");
+
+    child.kill().unwrap();
+    child.wait().unwrap();
+}
+
+#[test]
+#[cfg(unix)]
+fn batch_filters() {
+    let dir = fixture(&["dir"], &["a.txt", "b.log", "dir/c.txt", "dir/d.log"]);
+
+    assert_eq!(list(dir.path(), &["--filter-with-batch", r"grep -v '\.log$'"]), vec!["a.txt", "dir/c.txt"]);
+
+    // The command may exit before reading all paths
+    let names: Vec<String> = (0..5000).map(|i| format!("file-{:04}", i)).collect();
+    let dir = fixture(&[], &names.iter().map(String::as_str).collect::<Vec<_>>());
+
+    let kept = list(dir.path(), &["--filter-with-batch", "head -n1"]);
+    assert_eq!(kept.len(), 1);
+    assert!(names.contains(&kept[0]));
+
+    let output = rebackup(&[dir.path().to_str().unwrap(), "--filter-with-batch", "cat > /dev/null; exit 4"]);
+    assert_eq!(output.status.code(), Some(3));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Batch filter failed"));
+}
+
+#[test]
+#[cfg(unix)]
+fn filter_commands_environment() {
+    let dir = fixture(&["dir"], &[]);
+    fs::write(dir.path().join("dir/file"), "12345").unwrap();
+
+    let log = TempDir::new().unwrap();
+    let log = log.path().join("log");
+
+    let command = format!(
+        r#"echo "$REBACKUP_ITEM_TYPE|$REBACKUP_ITEM_RELATIVE|${{REBACKUP_ITEM_SIZE-none}}|$REBACKUP_SOURCE|$REBACKUP_ITEM" >> '{}'"#,
+        log.display()
+    );
+
+    assert_eq!(list(dir.path(), &["--filter-with", &command]), vec!["dir/file"]);
+
+    let source = fs::canonicalize(dir.path()).unwrap();
+    let source = source.display();
+
+    let mut calls: Vec<String> = fs::read_to_string(&log).unwrap().lines().map(String::from).collect();
+    calls.sort();
+
+    assert_eq!(
+        calls,
+        vec![
+            format!("dir|dir|none|{}|{}/dir", source, source),
+            format!("file|dir/file|5|{}|{}/dir/file", source, source),
+        ]
+    );
+}
+
+#[test]
+#[cfg(unix)]
+fn filter_commands_selection() {
+    let dir = fixture(&["docs"], &["docs/report.docx", "docs/notes.txt", "todo.txt"]);
+
+    let log = TempDir::new().unwrap();
+    let log = log.path().join("log");
+    let logged = |tag: &str| format!(r#"echo "{}:$REBACKUP_ITEM_RELATIVE" >> '{}'"#, tag, log.display());
+
+    // Read the items each command was spawned on, and reset the log
+    let calls = || -> Vec<String> {
+        let mut calls: Vec<String> = fs::read_to_string(&log).unwrap_or_default().lines().map(String::from).collect();
+        let _ = fs::remove_file(&log);
+        calls.sort();
+        calls
+    };
+
+    list(dir.path(), &["--filter-with", &logged("a"), "--filter-match", "*.docx"]);
+    assert_eq!(calls(), vec!["a:docs/report.docx"]);
+
+    // A single pattern applies to all commands
+    list(dir.path(), &["--filter-with", &logged("a"), &logged("b"), "--filter-match", "*.docx"]);
+    assert_eq!(calls(), vec!["a:docs/report.docx", "b:docs/report.docx"]);
+
+    // Otherwise, each command gets its own pattern
+    list(dir.path(), &["--filter-with", &logged("a"), &logged("b"), "--filter-match", "*.docx", "/*.txt"]);
+    assert_eq!(calls(), vec!["a:docs/report.docx", "b:todo.txt"]);
+
+    list(dir.path(), &["--filter-with", &logged("a"), "--filter-only-files"]);
+    assert_eq!(calls(), vec!["a:docs/notes.txt", "a:docs/report.docx", "a:todo.txt"]);
+
+    let output = rebackup(&[
+        dir.path().to_str().unwrap(),
+        "--filter-with",
+        &logged("a"),
+        &logged("b"),
+        &logged("c"),
+        "--filter-match",
+        "*.docx",
+        "*.txt",
+    ]);
+
+    assert_eq!(output.status.code(), Some(10));
+    assert!(calls().is_empty());
+}
+
+#[test]
+#[cfg(unix)]
+fn parallel_filter_commands() {
+    let dir = fixture(
+        &["keep/sub", "skip/sub"],
+        &["keep/a", "keep/b.tmp", "keep/sub/c", "skip/d", "skip/sub/e", "f", "g.tmp"],
+    );
+
+    let command = r#"case "$REBACKUP_ITEM_RELATIVE" in skip|*.tmp) exit 1;; esac"#;
+
+    let serial = list(dir.path(), &["--filter-with", command]);
+    assert_eq!(serial, vec!["f", "keep/a", "keep/sub/c"]);
+
+    // Descendants of excluded directories are dropped after the walk
+    assert_eq!(list(dir.path(), &["--filter-with", command, "--filter-jobs", "4"]), serial);
+}
+
+#[test]
+#[cfg(unix)]
+fn failing_filter_commands() {
+    let dir = fixture(&[], &["a", "b"]);
+    let command = r#"[ "$REBACKUP_ITEM_RELATIVE" = a ] || rebackup-missing-command"#;
+
+    let output = rebackup(&[dir.path().to_str().unwrap(), "--filter-with", command]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+
+    assert_eq!(output.status.code(), Some(3));
+    assert!(stderr.contains("command exited with code 127"), "{}", stderr);
+    assert!(stderr.contains("rebackup-missing-command: "), "{}", stderr);
+
+    assert_eq!(list(dir.path(), &["--filter-with", command, "--filter-lenient"]), vec!["a"]);
+}
+
+#[test]
+#[cfg(unix)]
+fn keep_going_on_unreadable_directories() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = fixture(&["locked", "readable"], &["a", "locked/b", "readable/c"]);
+    let locked = dir.path().join("locked");
+
+    fs::set_permissions(&locked, fs::Permissions::from_mode(0o000)).unwrap();
+
+    // Permissions are not enforced for privileged users (e.g. root)
+    if fs::read_dir(&locked).is_ok() {
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o755)).unwrap();
+        return;
+    }
+
+    let aborted = rebackup(&[dir.path().to_str().unwrap()]);
+    let output = rebackup(&[dir.path().to_str().unwrap(), "--keep-going"]);
+    fs::set_permissions(&locked, fs::Permissions::from_mode(0o755)).unwrap();
+
+    assert_eq!(aborted.status.code(), Some(3));
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+
+    assert_eq!(output.status.code(), Some(6), "{}", stderr);
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "a\nreadable/c\n");
+    assert!(
+        stderr.contains("1 item(s) could not be read and were skipped (1 permission errors)"),
+        "{}",
+        stderr
+    );
+}
+
+#[test]
+fn sources_from_stdin() {
+    let dir = fixture(&["one", "two"], &["one/a", "two/b"]);
+
+    let run = |args: &[&str], input: String| {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_rebackup"))
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+
+        child.stdin.take().unwrap().write_all(input.as_bytes()).unwrap();
+        child.wait_with_output().unwrap()
+    };
+
+    let (one, two) = (dir.path().join("one"), dir.path().join("two"));
+
+    let output = run(&["--sources-from", "-"], format!("# Sources\n{}\r\n\n{}\n", one.display(), two.display()));
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "one/a\ntwo/b\n");
+
+    let output = run(&["--sources-from", "-", "--null-sources"], format!("{}\0{}\0", one.display(), two.display()));
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "one/a\ntwo/b\n");
+
+    assert_eq!(run(&["--sources-from", "-"], "# Nothing\n".to_string()).status.code(), Some(2));
+}
+
+#[test]
+fn only_item_types() {
+    let dir = fixture(&["dir/empty", "full"], &["a", "full/b"]);
+
+    assert_eq!(list(dir.path(), &["--files-only"]), vec!["a", "full/b"]);
+    assert_eq!(list(dir.path(), &["--dirs-only"]), vec!["dir/empty"]);
+    assert_eq!(list(dir.path(), &["--dirs-only", "--list-dirs"]), vec!["dir", "dir/empty", "full"]);
+
+    #[cfg(unix)]
+    {
+        std::os::unix::fs::symlink("a", dir.path().join("link")).unwrap();
+
+        assert_eq!(list(dir.path(), &["--symlinks-only", "--keep-symlinks"]), vec!["link"]);
+        assert_eq!(
+            list(dir.path(), &["--files-only", "--symlinks-only", "--keep-symlinks"]),
+            vec!["a", "full/b", "link"]
+        );
+        assert_eq!(list(dir.path(), &["--files-only", "--keep-symlinks"]), vec!["a", "full/b"]);
+
+        // Symbolic links are only listed when they are kept
+        assert_eq!(rebackup(&[dir.path().to_str().unwrap(), "--symlinks-only"]).status.code(), Some(2));
+    }
+}
+
+#[test]
+fn relative_to_base_directory() {
+    let dir = fixture(&["src/sub", "src/empty", "other"], &["src/a", "src/sub/b"]);
+    let (src, other) = (dir.path().join("src"), dir.path().join("other"));
+
+    assert_eq!(
+        list(&src, &["--relative-to", dir.path().to_str().unwrap()]),
+        vec!["src/a", "src/empty", "src/sub/b"]
+    );
+
+    assert_eq!(list(&src, &["--relative-to", src.to_str().unwrap()]), vec!["a", "empty", "sub/b"]);
+
+    // An empty source directory is listed under its own path
+    assert_eq!(list(&other, &["--relative-to", dir.path().to_str().unwrap()]), vec!["other"]);
+
+    // The base directory must contain the source directory
+    for base in [&other, &src.join("sub")] {
+        let output = rebackup(&[src.to_str().unwrap(), "--relative-to", base.to_str().unwrap()]);
+        assert_eq!(output.status.code(), Some(2), "{}", base.display());
+        assert!(String::from_utf8_lossy(&output.stderr).contains("is not located inside base directory"));
+    }
+}
+
+#[test]
+fn sort_by_size_and_mtime() {
+    let dir = fixture(&[], &[]);
+    let now = SystemTime::now();
+
+    for (name, size, age_secs) in [("a", 10, 300), ("b", 30, 100), ("c", 10, 200), ("d", 20, 100)] {
+        let path = dir.path().join(name);
+        fs::write(&path, vec![b'x'; size]).unwrap();
+        File::open(&path).unwrap().set_modified(now - Duration::from_secs(age_secs)).unwrap();
+    }
+
+    // Ties are broken by name
+    assert_eq!(list(dir.path(), &["--sort-by", "size"]), vec!["b", "d", "a", "c"]);
+    assert_eq!(list(dir.path(), &["--sort-by", "mtime"]), vec!["b", "d", "c", "a"]);
+    assert_eq!(list(dir.path(), &["--sort-by", "size", "--reverse"]), vec!["c", "a", "d", "b"]);
+    assert_eq!(list(dir.path(), &["--sort-by", "mtime", "--reverse"]), vec!["a", "c", "d", "b"]);
+    assert_eq!(list(dir.path(), &["--reverse"]), vec!["d", "c", "b", "a"]);
+}
+
+#[test]
+fn prefix_and_suffix_do_not_affect_sorting() {
+    let dir = fixture(&["one", "two"], &["one/a", "one/a-b", "one/B", "two/c"]);
+    let (one, two) = (dir.path().join("one"), dir.path().join("two"));
+
+    assert_eq!(list(&one, &[]), vec!["B", "a", "a-b"]);
+
+    // Sorting by the output lines would put 'a-b~' before 'a~'
+    assert_eq!(list(&one, &["--prefix", "_", "--suffix", "~"]), vec!["_B~", "_a~", "_a-b~"]);
+
+    // Items of different source directories are ordered by their prefix first
+    let output = rebackup(&[
+        one.to_str().unwrap(),
+        two.to_str().unwrap(),
+        "--prefix",
+        "z/",
+        "--prefix",
+        "y/",
+        "--suffix",
+        "~",
+    ]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "y/c~\nz/B~\nz/a~\nz/a-b~\n");
+}
+
+#[test]
+fn diff_against_previous_list() {
+    let dir = fixture(&["src/dir"], &["src/kept", "src/dir/new", "src/added"]);
+    let src = dir.path().join("src");
+
+    let previous = dir.path().join("previous.txt");
+    fs::write(&previous, "kept\nremoved\ndir/gone\n").unwrap();
+
+    // Only the added entries are listed, removed ones are reported on STDERR when asked to
+    let output = rebackup(&[src.to_str().unwrap(), "--diff-against", previous.to_str().unwrap()]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "added\ndir/new\n");
+    assert!(!String::from_utf8_lossy(&output.stderr).contains("REMOVED"));
+
+    let output = rebackup(&[src.to_str().unwrap(), "--diff-against", previous.to_str().unwrap(), "--diff-removed"]);
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "added\ndir/new\n");
+    assert_eq!(String::from_utf8(output.stderr).unwrap(), "REMOVED dir/gone\nREMOVED removed\n");
+
+    let removed = dir.path().join("removed.txt");
+
+    let output = rebackup(&[
+        src.to_str().unwrap(),
+        "--diff-against",
+        previous.to_str().unwrap(),
+        "--removed-output",
+        removed.to_str().unwrap(),
+    ]);
+
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "added\ndir/new\n");
+    assert!(output.stderr.is_empty());
+    assert_eq!(fs::read_to_string(&removed).unwrap(), "dir/gone\nremoved\n");
+}
+
+#[test]
+fn count_and_total_size() {
+    let dir = fixture(&["dir/empty"], &[]);
+    fs::write(dir.path().join("a"), vec![b'x'; 1000]).unwrap();
+    fs::write(dir.path().join("dir/b"), vec![b'x'; 1536]).unwrap();
+
+    assert_eq!(list(dir.path(), &["--count"]), vec!["3"]);
+    assert_eq!(list(dir.path(), &["--total-size"]), vec!["2536"]);
+    assert_eq!(list(dir.path(), &["--total-size", "--human-readable"]), vec!["2.48 KiB"]);
+    assert_eq!(list(dir.path(), &["--count", "--total-size"]), vec!["3", "2536"]);
+
+    // Sizes are read again when the files list is compared to a previous one
+    let previous = TempDir::new().unwrap();
+    let previous = previous.path().join("previous.txt");
+    fs::write(&previous, "a\n").unwrap();
+
+    let diff_against = ["--diff-against", previous.to_str().unwrap()];
+
+    assert_eq!(list(dir.path(), &[&diff_against[..], &["--count", "--total-size"]].concat()), vec!["2", "1536"]);
+    assert_eq!(
+        list(dir.path(), &[&diff_against[..], &["--total-size", "--human-readable"]].concat()),
+        vec!["1.50 KiB"]
+    );
+}
@@ -0,0 +1,437 @@
+//! Tests of the walker
+
+use rebackup::filesystem::{FsOperation, MockFs};
+use rebackup::{
+    walk, walk_parallel, walk_with_stats, DuplicateCause, ErrorPolicy, SymlinkEscapePolicy, SymlinkMode, WalkerConfig, WalkerErr, WalkerItemTypes,
+    WalkerRule, WalkerRuleErr, WalkerRuleResult, WalkerRuleStage,
+};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc};
+use std::time::Duration;
+use std::{fs, io, panic, thread};
+use tempfile::TempDir;
+
+/// Create a temporary directory containing the provided directories and (empty) files
+///
+/// The returned path is canonicalized, like the paths returned by the walker.
+fn fixture(dirs: &[&str], files: &[&str]) -> (TempDir, PathBuf) {
+    let tmp = TempDir::new().unwrap();
+    let root = fs::canonicalize(tmp.path()).unwrap();
+
+    for dir in dirs {
+        fs::create_dir_all(root.join(dir)).unwrap();
+    }
+
+    for file in files {
+        fs::write(root.join(file), "").unwrap();
+    }
+
+    (tmp, root)
+}
+
+/// Rule which doesn't do anything, but calls a function on each item it is run on
+fn observer(name: &'static str, on_item: impl Fn(&Path) + Send + Sync + 'static) -> WalkerRule {
+    WalkerRule {
+        name,
+        description: None,
+        priority: 0,
+        only_for: WalkerItemTypes::all(),
+        min_depth: None,
+        max_depth: None,
+        cacheable: false,
+        timeout: None,
+        matches: Box::new(move |ctx| {
+            on_item(ctx.path);
+            Ok(false)
+        }),
+        action: Box::new(|_| Ok(WalkerRuleResult::IncludeItem)),
+    }
+}
+
+#[test]
+fn cancelled_walk_stops_within_bounded_number_of_items() {
+    let files: Vec<String> = (0..500).map(|i| format!("dir/file_{:03}", i)).collect();
+    let files: Vec<&str> = files.iter().map(String::as_str).collect();
+    let (_tmp, root) = fixture(&["dir"], &files);
+
+    let cancel = Arc::new(AtomicBool::new(false));
+    let treated = Arc::new(AtomicUsize::new(0));
+
+    let rule = {
+        let cancel = Arc::clone(&cancel);
+        let treated = Arc::clone(&treated);
+
+        observer("cancel_after_10", move |_| {
+            if treated.fetch_add(1, Ordering::SeqCst) + 1 == 10 {
+                cancel.store(true, Ordering::SeqCst);
+            }
+        })
+    };
+
+    let config = WalkerConfig::builder().rule(rule).cancel(Arc::clone(&cancel)).build();
+
+    assert!(matches!(walk(&root, &config), Err(WalkerErr::Cancelled)));
+    assert!(
+        treated.load(Ordering::SeqCst) <= 11,
+        "{} items treated after cancellation",
+        treated.load(Ordering::SeqCst)
+    );
+}
+
+/// Walk a directory and return the sorted list of paths relative to it
+fn walk_sorted(root: &Path, config: &WalkerConfig) -> Vec<PathBuf> {
+    let mut items: Vec<PathBuf> = walk(root, config)
+        .unwrap()
+        .into_iter()
+        .map(|path| path.strip_prefix(root).unwrap().to_path_buf())
+        .collect();
+
+    items.sort();
+    items
+}
+
+#[test]
+fn max_depth_limits_traversal() {
+    let (_tmp, root) = fixture(&["a/b/c"], &["top", "a/file", "a/b/file", "a/b/c/file"]);
+
+    let walk_depth = |max_depth| walk_sorted(&root, &WalkerConfig::builder().max_depth(Some(max_depth)).build());
+
+    assert_eq!(walk_depth(0), vec![PathBuf::from("a"), PathBuf::from("top")]);
+    assert_eq!(walk_depth(1), vec![PathBuf::from("a/b"), PathBuf::from("a/file"), PathBuf::from("top")]);
+    assert_eq!(
+        walk_depth(2),
+        vec![PathBuf::from("a/b/c"), PathBuf::from("a/b/file"), PathBuf::from("a/file"), PathBuf::from("top")]
+    );
+}
+
+#[test]
+fn sort_entries_makes_output_order_deterministic() {
+    // Files are created in reverse order, so the order of the directory's entries is unlikely to be sorted
+    let (_tmp, root) = fixture(&["b", "a/sub"], &["c", "b/2", "b/1", "a/sub/z", "a/y", "a/x"]);
+
+    let config = WalkerConfig::builder().sort_entries(true).build();
+    let list = || -> Vec<PathBuf> {
+        walk(&root, &config)
+            .unwrap()
+            .into_iter()
+            .map(|path| path.strip_prefix(&root).unwrap().to_path_buf())
+            .collect()
+    };
+
+    let expected: Vec<PathBuf> = ["a/sub/z", "a/x", "a/y", "b/1", "b/2", "c"].iter().map(PathBuf::from).collect();
+
+    for _ in 0..5 {
+        assert_eq!(list(), expected);
+    }
+}
+
+#[test]
+fn walk_parallel_returns_same_items_as_walk() {
+    let dirs: Vec<String> = (0..20).map(|i| format!("dir_{}/sub_{}", i, i % 3)).collect();
+    let files: Vec<String> = (0..20)
+        .flat_map(|i| vec![format!("dir_{}/file", i), format!("dir_{}/sub_{}/file", i, i % 3)])
+        .collect();
+
+    let (_tmp, root) = fixture(
+        &dirs.iter().map(String::as_str).collect::<Vec<_>>(),
+        &files.iter().map(String::as_str).collect::<Vec<_>>(),
+    );
+
+    let config = WalkerConfig::new(vec![WalkerRule::exclude_dir_named("sub_1")]);
+
+    let mut expected = walk(&root, &config).unwrap();
+    expected.sort();
+
+    for threads in [1, 2, 8] {
+        let mut items = walk_parallel(&root, &config, threads).unwrap();
+        items.sort();
+
+        assert_eq!(items, expected, "with {} threads", threads);
+    }
+}
+
+#[test]
+fn walk_parallel_collects_errors_like_walk() {
+    let (_tmp, root) = fixture(&["ok/sub", "locked", "other"], &["ok/file", "ok/sub/file", "locked/file", "other/file"]);
+
+    let config = WalkerConfig::builder()
+        .filesystem(MockFs::new().fail(FsOperation::ReadDir, root.join("locked"), io::ErrorKind::PermissionDenied))
+        .on_error(ErrorPolicy::Collect)
+        .build();
+
+    let mut expected = walk(&root, &config).unwrap();
+    expected.sort();
+
+    assert!(expected.contains(&root.join("other/file")));
+    assert!(!expected.contains(&root.join("locked/file")));
+
+    for threads in [1, 4] {
+        let mut items = walk_parallel(&root, &config, threads).unwrap();
+        items.sort();
+
+        assert_eq!(items, expected, "with {} threads", threads);
+    }
+}
+
+#[test]
+fn walk_parallel_propagates_rule_panics() {
+    let dirs: Vec<String> = (0..10).map(|i| format!("dir_{}", i)).collect();
+    let (_tmp, root) = fixture(&dirs.iter().map(String::as_str).collect::<Vec<_>>(), &["dir_5/boom"]);
+
+    let (tx, rx) = mpsc::channel();
+
+    thread::spawn(move || {
+        let rule = observer("panicking", |path| {
+            if path.ends_with("boom") {
+                panic!("rule panicked");
+            }
+        });
+
+        let config = WalkerConfig::new(vec![rule]);
+        let result = panic::catch_unwind(panic::AssertUnwindSafe(|| walk_parallel(&root, &config, 4)));
+        tx.send(result.is_err()).unwrap();
+    });
+
+    let panicked = rx
+        .recv_timeout(Duration::from_secs(10))
+        .expect("parallel walk deadlocked after a rule panicked");
+    assert!(panicked);
+}
+
+#[test]
+fn items_are_not_canonicalized() {
+    let files: Vec<String> = (0..50).map(|i| format!("dir/sub/file_{}", i)).collect();
+    let (_tmp, root) = fixture(&["dir/sub"], &files.iter().map(String::as_str).collect::<Vec<_>>());
+
+    let mock_fs = MockFs::new();
+    let items = walk(&root, &WalkerConfig::builder().filesystem(mock_fs.clone()).build()).unwrap();
+
+    assert_eq!(items.len(), 50);
+
+    // Only the source directory is canonicalized, not the items inside it
+    assert_eq!(mock_fs.calls(FsOperation::Canonicalize), 1);
+    assert_eq!(mock_fs.calls(FsOperation::SymlinkMetadata), 53);
+}
+
+#[cfg(unix)]
+#[test]
+fn followed_symlinks_are_canonicalized_at_most_once() {
+    let (_tmp, root) = fixture(&["dir/sub"], &["dir/sub/file_1", "dir/sub/file_2"]);
+
+    std::os::unix::fs::symlink(root.join("dir/sub"), root.join("link_1")).unwrap();
+    std::os::unix::fs::symlink(root.join("dir/sub/file_1"), root.join("link_2")).unwrap();
+
+    let mock_fs = MockFs::new();
+    let config = WalkerConfig::builder()
+        .filesystem(mock_fs.clone())
+        .symlink_mode(SymlinkMode::Follow)
+        .symlink_escape(SymlinkEscapePolicy::Skip)
+        .build();
+
+    walk(&root, &config).unwrap();
+
+    // One call for the source directory, and at most one for each of the two symbolic links
+    assert!(
+        mock_fs.calls(FsOperation::Canonicalize) <= 3,
+        "{} calls",
+        mock_fs.calls(FsOperation::Canonicalize)
+    );
+}
+
+#[test]
+fn failing_predicate_reports_rule_name_and_path() {
+    let (_tmp, root) = fixture(&["dir"], &["dir/broken", "dir/fine"]);
+
+    let rule = WalkerRule {
+        matches: Box::new(|ctx| match ctx.path.ends_with("broken") {
+            true => Err(io::Error::other("predicate failed")),
+            false => Ok(false),
+        }),
+        ..observer("failing_predicate", |_| {})
+    };
+
+    match walk(&root, &WalkerConfig::new(vec![rule])) {
+        Err(WalkerErr::RuleFailedToRun {
+            rule_name,
+            item_path,
+            stage,
+            err,
+            ..
+        }) => {
+            assert_eq!(rule_name, "failing_predicate");
+            assert_eq!(item_path, root.join("dir/broken"));
+            assert_eq!(stage, WalkerRuleStage::Matching);
+            assert!(matches!(err, WalkerRuleErr::Io(err) if err.to_string() == "predicate failed"));
+        }
+        result => panic!("Unexpected result: {:?}", result),
+    }
+}
+
+/// Rule including directories named `name` without their descendants
+fn prune(name: &'static str) -> WalkerRule {
+    WalkerRule {
+        matches: Box::new(move |ctx| Ok(ctx.path.file_name().is_some_and(|file_name| file_name == name))),
+        action: Box::new(|_| Ok(WalkerRuleResult::IncludeWithoutDescendants)),
+        ..observer("prune", |_| {})
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn prune_symlinked_directories() {
+    // Symbolic links are named so they are found either before or after their target
+    for link in ["a_link", "z_link"] {
+        let (_tmp, root) = fixture(&["real/sub"], &["real/file", "real/sub/file"]);
+        std::os::unix::fs::symlink(root.join("real"), root.join(link)).unwrap();
+
+        // Followed symbolic links to directories are pruned like directories, and their target is still traversed
+        let config = WalkerConfig::builder()
+            .rule(prune(link))
+            .symlink_mode(SymlinkMode::Follow)
+            .sort_entries(true)
+            .build();
+        let items = walk_sorted(&root, &config);
+
+        assert!(!items.iter().any(|item| item.starts_with(link) && item != Path::new(link)), "{:?}", items);
+        assert!(items.contains(&PathBuf::from("real/file")), "{:?}", items);
+        assert!(items.contains(&PathBuf::from("real/sub/file")), "{:?}", items);
+
+        // Pruning a directory also prunes it when it is reached through a followed symbolic link
+        let config = WalkerConfig::builder().rule(prune("sub")).symlink_mode(SymlinkMode::Follow).build();
+        let items = walk_sorted(&root, &config);
+        assert!(!items.iter().any(|item| item.ends_with("sub/file")), "{:?}", items);
+        assert!(items.iter().any(|item| item.ends_with("sub")), "{:?}", items);
+
+        // Symbolic links which are not followed are listed as entries
+        let config = WalkerConfig::builder().rule(prune(link)).symlink_mode(SymlinkMode::ListAsEntry).build();
+        let items = walk_sorted(&root, &config);
+        assert!(items.contains(&PathBuf::from(link)), "{:?}", items);
+        assert!(items.contains(&PathBuf::from("real/sub/file")), "{:?}", items);
+    }
+
+    // A pruned symbolic link found before its target is listed
+    let (_tmp, root) = fixture(&["real"], &["real/file"]);
+    std::os::unix::fs::symlink(root.join("real"), root.join("a_link")).unwrap();
+
+    let config = WalkerConfig::builder()
+        .rule(prune("a_link"))
+        .symlink_mode(SymlinkMode::Follow)
+        .sort_entries(true)
+        .build();
+    assert_eq!(walk_sorted(&root, &config), vec![PathBuf::from("a_link"), PathBuf::from("real/file")]);
+}
+
+#[test]
+fn absolute_include_wins_over_lower_priority_exclusion() {
+    let (_tmp, root) = fixture(&["dir"], &["dir/keep.log", "dir/drop.log", "dir/file"]);
+
+    let exclude_logs = |priority| WalkerRule {
+        priority,
+        matches: Box::new(|ctx| Ok(ctx.path.extension().is_some_and(|ext| ext == "log"))),
+        action: Box::new(|_| Ok(WalkerRuleResult::ExcludeItem)),
+        ..observer("exclude_logs", |_| {})
+    };
+
+    let keep_file = |priority| WalkerRule {
+        priority,
+        matches: Box::new(|ctx| Ok(ctx.path.ends_with("keep.log"))),
+        action: Box::new(|_| Ok(WalkerRuleResult::IncludeItemAbsolute)),
+        ..observer("keep_file", |_| {})
+    };
+
+    let with_keep = vec![PathBuf::from("dir/file"), PathBuf::from("dir/keep.log")];
+    let without_keep = vec![PathBuf::from("dir/file")];
+
+    // The exclusion is provided first, but has a lower priority than the absolute include
+    let config = WalkerConfig::new(vec![exclude_logs(-1), keep_file(0)]);
+    assert_eq!(walk_sorted(&root, &config), with_keep);
+
+    let config = WalkerConfig::new(vec![exclude_logs(0), keep_file(10)]);
+    assert_eq!(walk_sorted(&root, &config), with_keep);
+
+    // With the same priority, rules are run in the order they were provided in
+    let config = WalkerConfig::new(vec![exclude_logs(0), keep_file(0)]);
+    assert_eq!(walk_sorted(&root, &config), without_keep);
+
+    let config = WalkerConfig::new(vec![keep_file(0), exclude_logs(0)]);
+    assert_eq!(walk_sorted(&root, &config), with_keep);
+
+    // A higher priority exclusion is run before the absolute include
+    let config = WalkerConfig::new(vec![keep_file(0), exclude_logs(1)]);
+    assert_eq!(walk_sorted(&root, &config), without_keep);
+}
+
+#[cfg(unix)]
+#[test]
+fn symlink_loop_is_reported_once() {
+    use std::os::unix::fs::symlink;
+
+    // a/to_b -> b, and b/to_a -> a
+    let (_tmp, root) = fixture(&["a", "b"], &["a/file", "b/file"]);
+    symlink(root.join("b"), root.join("a/to_b")).unwrap();
+    symlink(root.join("a"), root.join("b/to_a")).unwrap();
+
+    let config = WalkerConfig::builder().symlink_mode(SymlinkMode::Follow).build();
+    let (items, stats) = walk_with_stats(&root.join("a"), &config).unwrap();
+
+    let mut items: Vec<_> = items.iter().map(|item| item.strip_prefix(&root).unwrap().to_path_buf()).collect();
+    items.sort();
+
+    assert_eq!(items, vec![PathBuf::from("a/file"), PathBuf::from("a/to_b/file")]);
+
+    assert_eq!(stats.duplicates.len(), 1);
+    assert_eq!(stats.duplicates[0].original, Some(root.join("a")));
+    assert_eq!(stats.duplicates[0].duplicate, root.join("a/to_b/to_a"));
+    assert_eq!(stats.duplicates[0].cause, DuplicateCause::SymlinkTargetVisited);
+
+    // Symbolic links pointing to each other are skipped without making the walk fail
+    let (_tmp, root) = fixture(&[], &["file"]);
+    symlink(root.join("b"), root.join("a")).unwrap();
+    symlink(root.join("a"), root.join("b")).unwrap();
+
+    assert_eq!(walk(&root, &config).unwrap(), vec![root.join("file")]);
+}
+
+#[test]
+fn max_items_boundary() {
+    let (_tmp, root) = fixture(&["dir"], &["dir/1", "dir/2", "dir/3", "dir/4", "dir/5"]);
+    let walk_limit = |limit| walk(&root, &WalkerConfig::builder().max_items(Some(limit)).build());
+
+    // The limit is exceeded
+    match walk_limit(4) {
+        Err(WalkerErr::TooManyItems { limit, at_path }) => {
+            assert_eq!(limit, 4);
+            assert!(at_path.starts_with(root.join("dir")));
+        }
+        result => panic!("Unexpected result: {:?}", result),
+    }
+
+    // The files list contains exactly as many items as allowed
+    assert_eq!(walk_limit(5).unwrap().len(), 5);
+
+    // The limit isn't reached
+    assert_eq!(walk_limit(6).unwrap().len(), 5);
+}
+
+#[cfg(unix)]
+#[test]
+fn followed_symlinks_types_are_read_through_filesystem() {
+    // The target is located in a sub-directory, so the symbolic link is found first
+    let (_tmp, root) = fixture(&["sub/real"], &["sub/real/file"]);
+    std::os::unix::fs::symlink(root.join("sub/real"), root.join("link")).unwrap();
+
+    // The source directory's type is checked once, and the symbolic link's target is read once for its identity
+    // and once to know if it is a directory (either to traverse it or to prune it)
+    for rules in [vec![], vec![prune("link")]] {
+        let mock_fs = MockFs::new();
+        let config = WalkerConfig::builder()
+            .filesystem(mock_fs.clone())
+            .symlink_mode(SymlinkMode::Follow)
+            .rules(rules)
+            .build();
+
+        walk(&root, &config).unwrap();
+        assert_eq!(mock_fs.calls(FsOperation::Metadata), 3);
+    }
+}